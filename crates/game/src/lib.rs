@@ -2,12 +2,15 @@ include!(concat!(env!("OUT_DIR"), "/materials.rs"));
 
 use goldfish::build::{CBuffer, StructuredBuffer};
 use goldfish::game::GameLib;
-use goldfish::package::{AssetType, Package};
+use goldfish::package::{AssetType, Package, ShaderPackage};
 use goldfish::renderer;
 use goldfish::GoldfishEngine;
 use goldfish::{Mat4, Quat, UVec2, Vec3, Vec4, Vec4Swizzles};
 use renderer::*;
-use uuid::uuid;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use uuid::{uuid, Uuid};
 use winit::event::VirtualKeyCode;
 
 #[derive(Default, Clone, Copy)]
@@ -71,6 +74,18 @@ const LIGHT_CULL_DESC_INFO: &'static DescriptorSetInfo = &DescriptorSetInfo {
 
 const Z_NEAR: f32 = 0.01;
 
+const CUBE_MESH: Uuid = uuid!("471cb8ab-2bd0-4e91-9ea9-0d0573cb9e0a");
+// The `.hlsl` asset that the vertex shader of the depth pre-pass is read from, so that it can be edited while the game runs. Without it the
+// vertex shader of `test_shader.hlsl` that the build script compiled is used.
+const CUBE_SHADER: Uuid = uuid!("0c7f9a4e-5d1b-4f3a-9b86-2e4d7c1a8f53");
+
+const RENDER_GRAPH_DOT_PATH: &'static str = ".build/render_graph.dot";
+const RENDER_GRAPH_JSON_PATH: &'static str = ".build/render_graph.json";
+const PIPELINE_CACHE_PATH: &'static str = ".build/pipeline_cache.bin";
+
+// Newly compiled pipelines are written to the pipeline cache at most this often, so that they survive a crash.
+const PIPELINE_CACHE_SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
 struct Game {
 	vs: Shader,
 	ps: Shader,
@@ -95,10 +110,35 @@ struct Game {
 	cube_transform: Transform,
 
 	render_graph_cache: RenderGraphCache,
+	export_render_graph_held: bool,
+	// The same graph is built every frame, so every problem with it is only printed the first time it shows up.
+	reported_render_graph_problems: HashSet<String>,
+	// Printed whenever pooling changes, which is only when the graph or the output size does.
+	reported_pooling_report: Option<RenderGraphPoolingReport>,
+	saved_pipeline_count: u32,
+	pipeline_cache_saved_at: Instant,
+}
+
+// Kept across reloads of the game library.
+#[derive(Serialize, Deserialize)]
+struct SavedState {
+	camera_position: [f32; 3],
+	camera_rotation: [f32; 4],
+	camera_heading: f64,
+	camera_pitch: f64,
 }
 
 impl Game {
 	fn update(&mut self, engine: &mut GoldfishEngine) {
+		if engine.reloaded_assets().contains(&CUBE_MESH) {
+			self.reload_cube(engine);
+		}
+
+		if engine.reloaded_assets().contains(&CUBE_SHADER) {
+			self.reload_cube_shader(engine);
+		}
+
+		let output_size = engine.get_output_size();
 		let graphics_device = &mut engine.graphics_device;
 		let graphics_context = &mut engine.graphics_context;
 
@@ -136,12 +176,12 @@ impl Game {
 		let speed = 0.05;
 		self.camera_transform.position += speed * (self.camera_transform.forward() * dz + self.camera_transform.right() * dx + Vec3 { x: 0.0, y: 1.0, z: 0.0 } * dy);
 
-		if let Ok(_) = graphics_context.begin_frame(&engine.window) {
+		if let Ok(_) = graphics_context.begin_frame(output_size) {
 			let model = common_inc::Model {
 				matrix: Mat4::from_scale_rotation_translation(self.cube_transform.scale, self.cube_transform.rotation, self.cube_transform.position),
 			};
 
-			let proj = Mat4::perspective_infinite_reverse_lh(1.6, engine.window.get_size().aspect() as f32, Z_NEAR);
+			let proj = Mat4::perspective_infinite_reverse_lh(1.6, output_size.aspect() as f32, Z_NEAR);
 			let inverse_proj = proj.inverse();
 
 			let view = Mat4::look_at_lh(
@@ -162,7 +202,7 @@ impl Game {
 			graphics_device.update_buffer(
 				&mut self.light_cull_cbuffer,
 				&light_cull_compute::CullInfo {
-					screen_size: UVec2::new(engine.window.get_size().width, engine.window.get_size().height),
+					screen_size: UVec2::new(output_size.width, output_size.height),
 					view,
 					z_near: Z_NEAR,
 					inverse_proj,
@@ -189,8 +229,8 @@ impl Game {
 				// let mut output = geometry_pass.add_attachment(AttachmentDesc {
 				// 	name: "Geometry output",
 				// 	format: TextureFormat::RGBA8,
-				// 	width: engine.window.get_size().width,
-				// 	height: engine.window.get_size().height,
+				// 	width: output_size.width,
+				// 	height: output_size.height,
 				// 	load_op: LoadOp::Clear,
 				// 	store_op: StoreOp::Store,
 				// 	usage: TextureUsage::SAMPLED | TextureUsage::ATTACHMENT,
@@ -199,11 +239,14 @@ impl Game {
 				let mut depth = geometry_pass.add_attachment(AttachmentDesc {
 					name: "Geometry depth",
 					format: TextureFormat::Depth,
-					width: engine.window.get_size().width,
-					height: engine.window.get_size().height,
+					width: output_size.width,
+					height: output_size.height,
 					load_op: LoadOp::Clear,
 					store_op: StoreOp::Store,
 					usage: TextureUsage::SAMPLED | TextureUsage::ATTACHMENT,
+					samples: SampleCount::X1,
+					mip_levels: 1,
+					array_layers: 1,
 				});
 
 				let descriptor = geometry_pass.add_graphics_descriptor_set(DescriptorDesc {
@@ -219,6 +262,7 @@ impl Game {
 					name: "Geometry render pass",
 					color_attachments: &mut [],
 					depth_attachment: Some(&mut depth),
+					resolve_attachments: &mut [],
 				});
 
 				let pipeline = geometry_pass.add_raster_pipeline(RasterPipelineDesc {
@@ -233,6 +277,9 @@ impl Game {
 					push_constant_bytes: 0,
 					vertex_input_info: Vertex::VERTEX_INFO,
 					polygon_mode: PolygonMode::Fill,
+					blend_states: &[],
+					stencil: None,
+					samples: SampleCount::X1,
 				});
 
 				geometry_pass.cmd_begin_render_pass(render_pass, &[ClearValue::DepthStencil { depth: 0.0, stencil: 0 }]);
@@ -248,16 +295,19 @@ impl Game {
 			};
 
 			let cull_attachment = {
-				let mut cull_pass = render_graph.add_pass("cull");
+				let mut cull_pass = render_graph.add_async_compute_pass("cull");
 
 				let mut max_depth = cull_pass.add_attachment(AttachmentDesc {
 					name: "Max Depth",
 					format: TextureFormat::RGBA8UNorm,
-					width: engine.window.get_size().width,
-					height: engine.window.get_size().height,
+					width: output_size.width,
+					height: output_size.height,
 					load_op: LoadOp::Clear,
 					store_op: StoreOp::Store,
 					usage: TextureUsage::SAMPLED | TextureUsage::STORAGE,
+					samples: SampleCount::X1,
+					mip_levels: 1,
+					array_layers: 1,
 				});
 
 				let descriptor = cull_pass.add_compute_descriptor_set(DescriptorDesc {
@@ -279,8 +329,8 @@ impl Game {
 
 				cull_pass.cmd_bind_compute_pipeline(pipeline);
 				cull_pass.cmd_bind_compute_descriptor(descriptor, 0, pipeline);
-				let work_groups_x = (engine.window.get_size().width + (engine.window.get_size().width % 16)) / 16;
-				let work_groups_y = (engine.window.get_size().height + (engine.window.get_size().height % 16)) / 16;
+				let work_groups_x = (output_size.width + (output_size.width % 16)) / 16;
+				let work_groups_y = (output_size.height + (output_size.height % 16)) / 16;
 				cull_pass.cmd_dispatch(work_groups_x, work_groups_y, 1);
 
 				max_depth
@@ -302,7 +352,7 @@ impl Game {
 			// 		descriptor_layout: SAMPLER_DESC_INFO,
 			// 		bindings: &mut [
 			// 			(0, DescriptorBindingDesc::Attachment(geometry_output_attachment.read())),
-			// 			(1, DescriptorBindingDesc::Attachment(geometry_output_attachment.read())),
+			// 			(1, DescriptorBindingDesc::Sampler(SamplerDesc::LINEAR_CLAMP)),
 			// 		],
 			// 	});
 
@@ -348,6 +398,9 @@ impl Game {
 					push_constant_bytes: 0,
 					vertex_input_info: EMPTY_VERTEX_INFO,
 					polygon_mode: PolygonMode::Fill,
+					blend_states: &[ColorBlendState::OPAQUE],
+					stencil: None,
+					samples: SampleCount::X1,
 				});
 
 				let descriptor0 = fullscreen.add_graphics_descriptor_set(DescriptorDesc {
@@ -355,7 +408,7 @@ impl Game {
 					descriptor_layout: FULLSCREEN_DESC_INFO,
 					bindings: &mut [
 						(0, DescriptorBindingDesc::Attachment(cull_attachment.read())),
-						(1, DescriptorBindingDesc::Attachment(cull_attachment.read())),
+						(1, DescriptorBindingDesc::Sampler(SamplerDesc::LINEAR_CLAMP)),
 					],
 				});
 
@@ -368,12 +421,99 @@ impl Game {
 				fullscreen.cmd_end_render_pass();
 			}
 
-			render_graph.execute(graphics_context, graphics_device);
+			// F10 dumps the render graph of this frame, render it with `dot -Tsvg .build/render_graph.dot`.
+			let export_render_graph = engine.keys[VirtualKeyCode::F10 as usize];
+			if export_render_graph && !self.export_render_graph_held {
+				let json = serde_json::to_string_pretty(&render_graph.export_json()).unwrap();
+				match std::fs::write(RENDER_GRAPH_DOT_PATH, render_graph.export_dot()).and_then(|_| std::fs::write(RENDER_GRAPH_JSON_PATH, json)) {
+					Ok(_) => println!("Exported render graph to {} and {}", RENDER_GRAPH_DOT_PATH, RENDER_GRAPH_JSON_PATH),
+					Err(err) => println!("Failed to export render graph: {}", err),
+				}
+			}
+			self.export_render_graph_held = export_render_graph;
+
+			let problems = match render_graph.execute(graphics_context, graphics_device) {
+				Ok(warnings) => warnings.iter().map(|warning| format!("Render graph warning: {}", warning)).collect::<Vec<_>>(),
+				Err(errors) => {
+					// Nothing was recorded, so the output still has to be cleared to have something to present.
+					graphics_context.begin_output_render_pass(&[ClearValue::Color { r: 0.0, g: 0.0, b: 0.0, a: 1.0 }]);
+					graphics_context.end_render_pass();
 
-			graphics_context.end_frame(&engine.window);
+					errors.iter().map(|error| format!("Render graph error: {}", error)).collect::<Vec<_>>()
+				}
+			};
+
+			for problem in problems {
+				if self.reported_render_graph_problems.insert(problem.clone()) {
+					println!("{}", problem);
+				}
+			}
+
+			let pooling_report = self.render_graph_cache.get_pooling_report();
+			if self.reported_pooling_report != Some(pooling_report) {
+				println!(
+					"Render graph pooling: {} attachments in {} textures, {} buffers in {} buffers, {} bytes saved per frame",
+					pooling_report.virtual_attachments,
+					pooling_report.physical_attachments,
+					pooling_report.virtual_buffers,
+					pooling_report.physical_buffers,
+					pooling_report.bytes_reused()
+				);
+				self.reported_pooling_report = Some(pooling_report);
+			}
+
+			graphics_context.end_frame(output_size);
+
+			let pipeline_count = self.render_graph_cache.get_pipeline_cache_stats().pipelines_created;
+			if pipeline_count != self.saved_pipeline_count && self.pipeline_cache_saved_at.elapsed() >= PIPELINE_CACHE_SAVE_INTERVAL {
+				self.render_graph_cache.save_pipeline_cache(graphics_device);
+				self.saved_pipeline_count = pipeline_count;
+				self.pipeline_cache_saved_at = Instant::now();
+			}
 		}
 	}
 
+	// The old mesh is kept if the new one can't be loaded, so a broken export doesn't take the game down.
+	fn reload_cube(&mut self, engine: &mut GoldfishEngine) {
+		let mesh_package = match engine.read_package(CUBE_MESH, AssetType::Mesh) {
+			Ok(Package::Mesh(mesh_package)) => mesh_package,
+			Ok(_) => {
+				println!("Failed to reload cube: Incorrect package type loaded");
+				return;
+			}
+			Err(err) => {
+				println!("Failed to reload cube: {}", err);
+				return;
+			}
+		};
+
+		let cube = self.upload_context.create_mesh(&mesh_package.vertices, &mesh_package.indices);
+		engine.graphics_device.destroy_mesh(std::mem::replace(&mut self.cube, cube));
+	}
+
+	// Like the mesh, the old shader is kept if the new one can't be loaded. The queued destruction of the old shader waits for the frames
+	// that still use it.
+	fn reload_cube_shader(&mut self, engine: &mut GoldfishEngine) {
+		let vs_ir = match engine.read_package(CUBE_SHADER, AssetType::Shader) {
+			Ok(Package::Shader(ShaderPackage { vs_ir: Some(vs_ir), .. })) => vs_ir,
+			Ok(Package::Shader(_)) => {
+				println!("Failed to reload cube shader: The shader has no vertex shader");
+				return;
+			}
+			Ok(_) => {
+				println!("Failed to reload cube shader: Incorrect package type loaded");
+				return;
+			}
+			Err(err) => {
+				println!("Failed to reload cube shader: {}", err);
+				return;
+			}
+		};
+
+		let vs = engine.graphics_device.create_shader_with_code(&vs_ir);
+		engine.graphics_device.destroy_shader(std::mem::replace(&mut self.vs, vs));
+	}
+
 	fn destroy(self, engine: &mut GoldfishEngine) {
 		let graphics_device = &mut engine.graphics_device;
 		self.render_graph_cache.destroy(graphics_device);
@@ -396,9 +536,30 @@ impl Game {
 }
 
 extern "C" fn on_load(engine: &mut GoldfishEngine) {
+	// The mesh is read before anything is created, so that nothing has to be cleaned up if it fails to load.
+	let mesh_package = match engine.read_package(CUBE_MESH, AssetType::Mesh) {
+		Ok(Package::Mesh(mesh_package)) => mesh_package,
+		Ok(_) => {
+			println!("WARNING: Failed to load the cube mesh, the game is not loaded! Incorrect package type loaded");
+			return;
+		}
+		Err(err) => {
+			println!("WARNING: Failed to load the cube mesh, the game is not loaded! {}", err);
+			return;
+		}
+	};
+
+	let cube_shader = match engine.read_package(CUBE_SHADER, AssetType::Shader) {
+		Ok(Package::Shader(shader_package)) => shader_package.vs_ir,
+		_ => None,
+	};
+
 	let graphics_device = &mut engine.graphics_device;
 
-	let vs = graphics_device.create_shader(&test_shader::VS_BYTES);
+	let vs = match cube_shader {
+		Some(vs_ir) => graphics_device.create_shader_with_code(&vs_ir),
+		None => graphics_device.create_shader(&test_shader::VS_BYTES),
+	};
 	let ps = graphics_device.create_shader(&test_shader::PS_BYTES);
 
 	let vs_textured = graphics_device.create_shader(&test_sampler::VS_BYTES);
@@ -428,19 +589,11 @@ extern "C" fn on_load(engine: &mut GoldfishEngine) {
 	let light_cull_cbuffer = upload_context.create_buffer(light_cull_compute::CullInfo::size(), MemoryLocation::CpuToGpu, BufferUsage::UniformBuffer, None, None);
 	let point_lights_sbuffer = upload_context.create_buffer(light_cull_compute::PointLight::size() * 3, MemoryLocation::CpuToGpu, BufferUsage::StorageBuffer, None, None);
 
-	let Package::Mesh(mesh_package) = engine.read_package(
-			uuid!("471cb8ab-2bd0-4e91-9ea9-0d0573cb9e0a"),
-			AssetType::Mesh,
-	      ).expect("Failed to load mesh package!") else
-	      {
-	          panic!("Incorrect package type loaded?");
-	      };
-
 	let cube = upload_context.create_mesh(&mesh_package.vertices, &mesh_package.indices);
 
-	let render_graph_cache = RenderGraphCache::default();
+	let render_graph_cache = RenderGraphCache::new(&engine.graphics_device, std::path::Path::new(PIPELINE_CACHE_PATH));
 
-	let game = Box::new(Game {
+	let game = Game {
 		vs,
 		ps,
 		vs_textured,
@@ -471,24 +624,70 @@ extern "C" fn on_load(engine: &mut GoldfishEngine) {
 			..Default::default()
 		},
 		render_graph_cache,
-	});
-
-	engine.game_state = Box::into_raw(game) as *mut ();
+		export_render_graph_held: false,
+		reported_render_graph_problems: Default::default(),
+		reported_pooling_report: None,
+		saved_pipeline_count: 0,
+		pipeline_cache_saved_at: Instant::now(),
+	};
+
+	if let Err((game, err)) = engine.set_game_state(game) {
+		println!("WARNING: Failed to set the game state, the new game is destroyed again! {}", err);
+		game.destroy(engine);
+	}
 }
 
 extern "C" fn on_unload(engine: &mut GoldfishEngine) {
-	let game = unsafe { Box::from_raw(engine.game_state as *mut Game) };
-	game.destroy(engine);
-
-	engine.game_state = std::ptr::null_mut();
+	match engine.take_game_state::<Game>() {
+		Ok(game) => game.destroy(engine),
+		Err(err) => println!("WARNING: Failed to take the game state, there is nothing to destroy! {}", err),
+	}
 }
 
 extern "C" fn on_update(engine: &mut GoldfishEngine) {
-	let game = unsafe { &mut *(engine.game_state as *mut Game) };
-	game.update(engine);
+	if let Err(err) = engine.with_game_state(|engine, game: &mut Game| game.update(engine)) {
+		println!("WARNING: Failed to get the game state, the game is not updated! {}", err);
+	}
 }
 
-#[no_mangle]
-extern "C" fn _goldfish_create_game_lib() -> GameLib {
-	GameLib { on_load, on_unload, on_update }
+extern "C" fn on_save_state(engine: &mut GoldfishEngine) {
+	let game = match engine.game_state::<Game>() {
+		Ok(game) => game,
+		Err(err) => {
+			println!("WARNING: Failed to get the game state, nothing is saved! {}", err);
+			return;
+		}
+	};
+
+	let saved_state = SavedState {
+		camera_position: game.camera_transform.position.to_array(),
+		camera_rotation: game.camera_transform.rotation.to_array(),
+		camera_heading: game.camera_heading,
+		camera_pitch: game.camera_pitch,
+	};
+	engine.saved_game_state = serde_json::to_vec(&saved_state).ok();
 }
+
+// A state that no longer deserializes was saved by a library with a different `SavedState`, so the game starts over.
+extern "C" fn on_restore_state(engine: &mut GoldfishEngine) {
+	let Some(saved_state) = engine.saved_game_state.take().and_then(|saved_state| serde_json::from_slice::<SavedState>(&saved_state).ok()) else {
+		return;
+	};
+
+	let restored = engine.with_game_state(|_, game: &mut Game| {
+		game.camera_transform.position = Vec3::from(saved_state.camera_position);
+		game.camera_transform.rotation = Quat::from_array(saved_state.camera_rotation);
+		game.camera_heading = saved_state.camera_heading;
+		game.camera_pitch = saved_state.camera_pitch;
+	});
+
+	if let Err(err) = restored {
+		println!("WARNING: Failed to get the game state, the saved state is dropped! {}", err);
+	}
+}
+
+goldfish::export_game_lib!(GameLib {
+	on_save_state: Some(on_save_state),
+	on_restore_state: Some(on_restore_state),
+	..GameLib::new(on_load, on_unload, on_update)
+});
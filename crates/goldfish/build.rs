@@ -0,0 +1,62 @@
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const ENGINE_SOURCE_DIR: &'static str = "src/engine";
+// Dependencies are part of the engine types too, the lock file of the workspace pins down which versions were built.
+const CARGO_LOCK_PATH: &'static str = "../../Cargo.lock";
+
+// The editor and game libraries share engine types by reference, so they have to be built from the same engine sources with the same compiler and settings.
+// Everything that the layout of those types depends on is hashed into `goldfish::game::ENGINE_BUILD_HASH`.
+fn main() {
+	let mut hasher = DefaultHasher::new();
+	hash_sources(Path::new(ENGINE_SOURCE_DIR), &mut hasher);
+
+	// Cargo writes the lock file before building anything, so it is only missing if the crate is built outside of the workspace.
+	// A missing path would make cargo rerun this script on every build, so it is only watched if it exists.
+	let cargo_lock = fs::read(CARGO_LOCK_PATH).ok();
+	cargo_lock.hash(&mut hasher);
+
+	for var in ["PROFILE", "TARGET", "OPT_LEVEL", "DEBUG"] {
+		env::var(var).unwrap_or_default().hash(&mut hasher);
+	}
+
+	let mut features = env::vars().map(|(key, _)| key).filter(|key| key.starts_with("CARGO_FEATURE_")).collect::<Vec<_>>();
+	features.sort();
+	features.hash(&mut hasher);
+
+	let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+	match Command::new(rustc).arg("--version").output() {
+		Ok(output) => output.stdout.hash(&mut hasher),
+		Err(err) => println!("cargo:warning=Failed to get the rustc version for the engine build hash: {}", err),
+	}
+
+	let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+	fs::write(out_dir.join("build_hash.rs"), format!("pub const ENGINE_BUILD_HASH: u64 = {:#018x};\n", hasher.finish())).expect("Failed to write the engine build hash!");
+
+	println!("cargo:rerun-if-changed={}", ENGINE_SOURCE_DIR);
+	if cargo_lock.is_some() {
+		println!("cargo:rerun-if-changed={}", CARGO_LOCK_PATH);
+	}
+	println!("cargo:rerun-if-changed=build.rs");
+}
+
+fn hash_sources(dir: &Path, hasher: &mut DefaultHasher) {
+	let mut paths = fs::read_dir(dir)
+		.expect("Failed to read engine sources!")
+		.map(|entry| entry.expect("Failed to read engine sources!").path())
+		.collect::<Vec<_>>();
+	paths.sort();
+
+	for path in paths {
+		if path.is_dir() {
+			hash_sources(&path, hasher);
+		} else {
+			path.hash(hasher);
+			fs::read(&path).expect("Failed to read engine sources!").hash(hasher);
+		}
+	}
+}
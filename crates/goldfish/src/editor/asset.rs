@@ -1,17 +1,19 @@
+use super::mesh_importer::{ImportedModel, ModelOutput};
 use super::shader_compiler;
 use super::{EditorError, BUILD_ASSET_DIR};
 use bincode::serialize;
 use filetime::FileTime;
-use goldfish::package::{AssetType, MeshPackage, Package, ShaderPackage};
+use goldfish::package::{AssetType, MaterialPackage, MeshPackage, Package, ScenePackage, ShaderPackage, TexturePackage};
 use goldfish::renderer::TextureFormat;
 use goldfish::{GoldfishError, GoldfishResult};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::io::prelude::*;
 use std::path::Path;
 use uuid::Uuid;
 
-const ASSET_META_EXTENSION: &'static str = "meta";
+pub const ASSET_META_EXTENSION: &'static str = "meta";
 const BUILD_ASSET_EXTENSION: &'static str = "asset";
 
 #[derive(Serialize, Deserialize, PartialEq, PartialOrd, Eq)]
@@ -55,8 +57,10 @@ impl Ord for Version {
 #[derive(Serialize, Deserialize)]
 pub enum AdditionalAssetData {
 	Mesh,
+	Scene,
 	Texture(TextureAsset),
 	Shader,
+	Material,
 	Other,
 }
 
@@ -66,23 +70,79 @@ pub struct Asset {
 	pub version: Version,
 	pub asset_type: AssetType,
 	pub additional_data: AdditionalAssetData,
+	// What each of the uuids of a model holds, empty for every other asset.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub model_outputs: Vec<ModelOutput>,
+}
+
+// Which block compressed format a texture is encoded into, based on what the texture holds.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureCompression {
+	// Imported uncompressed as `format`.
+	None,
+	// BC1, or BC3 if the texture has any transparency.
+	Color,
+	// BC7, slower to encode than Color but with far fewer artifacts.
+	ColorHighQuality,
+	// BC5 of the red and green channels, the blue channel has to be reconstructed in the shader.
+	NormalMap,
+	// BC4 of the red channel.
+	SingleChannel,
+}
+
+impl Default for TextureCompression {
+	fn default() -> Self {
+		Self::None
+	}
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct TextureAsset {
+	// Only used by uncompressed textures.
 	pub format: TextureFormat,
+	// Imports RGBA8UNorm and color compressed textures as sRGB so that they get converted to linear when sampled.
+	#[serde(default)]
+	pub srgb: bool,
+	#[serde(default = "TextureAsset::default_generate_mips")]
+	pub generate_mips: bool,
+	// Compression is opt-in since not every device supports block compressed formats, and textures imported before compression
+	// was supported stay uncompressed.
+	#[serde(default)]
+	pub compression: TextureCompression,
+}
+
+impl TextureAsset {
+	fn default_generate_mips() -> bool {
+		true
+	}
+}
+
+impl Default for TextureAsset {
+	fn default() -> Self {
+		Self {
+			format: TextureFormat::RGBA8UNorm,
+			srgb: false,
+			generate_mips: Self::default_generate_mips(),
+			compression: TextureCompression::None,
+		}
+	}
 }
 
 impl Asset {
-	const CURRENT_ASSET_VERSION: Version = Version::new(1, 0);
+	// 1.1: Meshes pick between 16 and 32 bit indices.
+	// 1.2: Models get an extra uuid for their scene and record what each of their uuids holds.
+	// 1.3: Mesh packages hold joints and scenes hold materials and skins.
+	const CURRENT_ASSET_VERSION: Version = Version::new(1, 3);
 
 	pub fn new(asset_type: AssetType, count: u32) -> Self {
 		// let uuid = Uuid::new_v4();
 
 		let additional_data = match asset_type {
 			AssetType::Mesh => AdditionalAssetData::Mesh,
-			AssetType::Texture => AdditionalAssetData::Texture(TextureAsset { format: TextureFormat::RGBA8UNorm }),
+			AssetType::Scene => AdditionalAssetData::Scene,
+			AssetType::Texture => AdditionalAssetData::Texture(Default::default()),
 			AssetType::Shader => AdditionalAssetData::Shader,
+			AssetType::Material => AdditionalAssetData::Material,
 			AssetType::Other => AdditionalAssetData::Other,
 		};
 
@@ -91,8 +151,35 @@ impl Asset {
 			version: Self::CURRENT_ASSET_VERSION,
 			asset_type,
 			additional_data,
+			model_outputs: Vec::new(),
 		}
 	}
+
+	pub fn new_model(outputs: Vec<ModelOutput>) -> Self {
+		let asset = Self::new(AssetType::Mesh, outputs.len() as u32);
+		Self { model_outputs: outputs, ..asset }
+	}
+
+	// Before 1.2 the uuids of a model were given out by position, one for every mesh.
+	fn assign_positional_model_outputs(&mut self, outputs: &[ModelOutput]) {
+		self.model_outputs = outputs.iter().copied().filter(|output| matches!(output, ModelOutput::Mesh(_))).take(self.uuids.len()).collect();
+		self.uuids.truncate(self.model_outputs.len());
+
+		self.model_outputs.push(ModelOutput::Scene);
+		self.uuids.push(Uuid::new_v4());
+	}
+
+	// Gives every output of a model a uuid, outputs that the model already had keep theirs and the uuids of outputs that the model
+	// doesn't have anymore are dropped. Returns whether anything changed.
+	fn sync_model_outputs(&mut self, outputs: &[ModelOutput]) -> bool {
+		let old_uuids = self.model_outputs.iter().copied().zip(self.uuids.iter().copied()).collect::<HashMap<_, _>>();
+		let uuids = outputs.iter().map(|output| old_uuids.get(output).copied().unwrap_or_else(Uuid::new_v4)).collect::<Vec<_>>();
+
+		let changed = uuids != self.uuids || outputs != self.model_outputs.as_slice();
+		self.uuids = uuids;
+		self.model_outputs = outputs.to_vec();
+		changed
+	}
 }
 
 pub fn import_assets(asset_dir: &Path) -> Result<(), EditorError> {
@@ -100,133 +187,254 @@ pub fn import_assets(asset_dir: &Path) -> Result<(), EditorError> {
 		fs::create_dir(BUILD_ASSET_DIR).map_err(move |err| EditorError::Filesystem(err))?;
 	}
 
+	// Materials are validated against the reflection of their shader, so they are imported once every shader has been built.
+	import_directory(asset_dir, false, false)?;
+	import_directory(asset_dir, true, false)?;
+	Ok(())
+}
+
+// Materials are checked against their shader again whenever it has been rebuilt, returns the uuids of the materials that were rebuilt.
+// Materials that fail to import are reported and skipped, so that one broken material doesn't hold back the others.
+pub fn reimport_materials(asset_dir: &Path) -> Result<Vec<Uuid>, EditorError> {
+	import_directory(asset_dir, true, true)
+}
+
+// With `skip_failed` assets that fail to import are reported instead of stopping the import of the rest of the directory.
+fn import_directory(asset_dir: &Path, materials: bool, skip_failed: bool) -> Result<Vec<Uuid>, EditorError> {
+	let mut reimported = Vec::new();
 	for asset in fs::read_dir(asset_dir).map_err(move |err| EditorError::Filesystem(err))? {
 		let asset = asset.map_err(move |err| EditorError::Filesystem(err))?;
 		let asset_path = asset.path();
 
 		if asset_path.is_dir() {
-			import_assets(asset_path.as_path())?;
+			reimported.extend(import_directory(asset_path.as_path(), materials, skip_failed)?);
 		} else if asset_path.extension().unwrap_or_default() != ASSET_META_EXTENSION {
-			let meta_extension = if let Some(extension) = asset_path.extension() {
-				extension.to_str().unwrap().to_owned() + "." + ASSET_META_EXTENSION
-			} else {
-				ASSET_META_EXTENSION.to_owned()
-			};
+			let asset_type = AssetType::from_extension(asset_path.extension().unwrap_or_default().to_str().unwrap());
+			if matches!(asset_type, AssetType::Material) == materials {
+				match import_asset(&asset_path) {
+					Ok(uuids) => reimported.extend(uuids),
+					Err(err) if skip_failed => println!("Failed to reimport asset {}: {}", asset_path.to_str().unwrap_or("UNKNOWN_ASSET_PATH"), err),
+					Err(err) => return Err(err),
+				}
+			}
+		}
+	}
+	Ok(reimported)
+}
 
-			let meta_path = asset_path.with_extension(&meta_extension);
+// Imports a single source file if its packages are missing or older than the source or its meta file.
+// Returns the uuids of the packages that were rebuilt.
+pub fn import_asset(asset_path: &Path) -> Result<Vec<Uuid>, EditorError> {
+	let meta_extension = if let Some(extension) = asset_path.extension() {
+		extension.to_str().unwrap().to_owned() + "." + ASSET_META_EXTENSION
+	} else {
+		ASSET_META_EXTENSION.to_owned()
+	};
 
-			let asset_type = AssetType::from_extension(asset_path.extension().unwrap_or_default().to_str().unwrap());
+	let meta_path = asset_path.with_extension(&meta_extension);
 
-			let mut meta_file_was_created = false;
+	let asset_type = AssetType::from_extension(asset_path.extension().unwrap_or_default().to_str().unwrap());
 
-			let mut mesh_packages: Option<Vec<MeshPackage>> = None;
+	let mut meta_file_was_created = false;
 
-			let import_mesh_packages = || -> Result<Option<Vec<MeshPackage>>, EditorError> {
-				let extension = asset_path.extension().unwrap().to_str().unwrap();
-				let data = fs::read(&asset_path).map_err(move |err| EditorError::Filesystem(err))?;
-				Ok(Some(super::mesh_importer::import_mesh(&data, &extension)?))
-			};
+	let mut model: Option<ImportedModel> = None;
 
-			let asset = if meta_path.exists() {
-				match fs::read_to_string(&meta_path) {
-					Ok(contents) => match serde_json::from_str::<Asset>(contents.as_str()) {
-						Ok(asset) => asset,
-						Err(err) => {
-							println!("Failed to deserialize metadata for asset! {}", err);
-							continue;
-						}
-					},
-					Err(err) => {
-						println!("Failed to load metadata for asset! {}", err);
-						continue;
-					}
+	let import_model = || -> Result<Option<ImportedModel>, EditorError> {
+		let extension = asset_path.extension().unwrap().to_str().unwrap().to_ascii_lowercase();
+		let model = if extension == "gltf" || extension == "glb" {
+			super::gltf_importer::import_gltf(asset_path)?
+		} else {
+			let data = fs::read(asset_path).map_err(move |err| EditorError::Filesystem(err))?;
+			super::mesh_importer::import_mesh(&data, &extension)?
+		};
+		for warning in model.warnings.iter() {
+			println!("WARNING: {}: {}", asset_path.to_str().unwrap(), warning);
+		}
+
+		Ok(Some(model))
+	};
+
+	let mut asset = if meta_path.exists() {
+		match fs::read_to_string(&meta_path) {
+			Ok(contents) => match serde_json::from_str::<Asset>(contents.as_str()) {
+				Ok(asset) => asset,
+				Err(err) => {
+					println!("Failed to deserialize metadata for asset! {}", err);
+					return Ok(Vec::new());
 				}
-			} else {
-				println!("Failed to find meta file {}! Creating...", meta_path.as_path().to_str().unwrap());
+			},
+			Err(err) => {
+				println!("Failed to load metadata for asset! {}", err);
+				return Ok(Vec::new());
+			}
+		}
+	} else {
+		println!("Failed to find meta file {}! Creating...", meta_path.as_path().to_str().unwrap());
 
-				mesh_packages = match asset_type {
-					AssetType::Mesh => import_mesh_packages()?,
-					_ => None,
-				};
+		model = match asset_type {
+			AssetType::Mesh => import_model()?,
+			_ => None,
+		};
 
-				let metadata = match mesh_packages {
-					Some(ref mesh_packages) => Asset::new(asset_type, mesh_packages.len() as u32),
-					None => Asset::new(asset_type, 1),
-				};
+		let metadata = match model {
+			Some(ref model) => Asset::new_model(model.outputs()),
+			None => Asset::new(asset_type, 1),
+		};
 
-				let serialized = serde_json::to_string_pretty(&metadata).map_err(move |_| EditorError::Serialize)?;
+		let serialized = serde_json::to_string_pretty(&metadata).map_err(move |_| EditorError::Serialize)?;
 
-				fs::write(&meta_path, serialized).map_err(move |err| EditorError::Filesystem(err))?;
-				meta_file_was_created = true;
+		fs::write(&meta_path, serialized).map_err(move |err| EditorError::Filesystem(err))?;
+		meta_file_was_created = true;
 
-				metadata
-			};
+		metadata
+	};
 
-			for (i, uuid) in asset.uuids.iter().enumerate() {
-				let build_path = Path::new(BUILD_ASSET_DIR).join(uuid.to_string()).with_extension(BUILD_ASSET_EXTENSION);
+	// Packages built by an older version of the editor can't be read anymore, so everything gets reimported and the meta file
+	// is bumped to the current version. The meta file is written before reimporting so that its modified time ends up before the builds.
+	let outdated = asset.version != Asset::CURRENT_ASSET_VERSION;
+	if outdated {
+		if matches!(asset.asset_type, AssetType::Mesh) && asset.version < Version::new(1, 2) {
+			if model.is_none() {
+				model = import_model()?;
+			}
+			if let Some(ref model) = model {
+				asset.assign_positional_model_outputs(&model.outputs());
+			}
+		}
 
-				let mut needs_reimport = asset.version != Asset::CURRENT_ASSET_VERSION || meta_file_was_created || !build_path.is_file();
+		asset.version = Asset::CURRENT_ASSET_VERSION;
+		let serialized = serde_json::to_string_pretty(&asset).map_err(move |_| EditorError::Serialize)?;
+		fs::write(&meta_path, serialized).map_err(move |err| EditorError::Filesystem(err))?;
+	}
 
-				if !needs_reimport {
-					let build_meta = fs::metadata(&build_path).map_err(move |err| EditorError::Filesystem(err))?;
-					let asset_meta = fs::metadata(&asset_path).map_err(move |err| EditorError::Filesystem(err))?;
-					let meta_meta = fs::metadata(&meta_path).map_err(move |err| EditorError::Filesystem(err))?;
+	// The outputs of a model change whenever meshes or textures are added to or removed from it, so the meta file is brought up to date
+	// with the model before anything gets built. The scene refers to every other output, so they are all rebuilt together.
+	let mut rebuild_model = false;
+	if matches!(asset.asset_type, AssetType::Mesh) {
+		rebuild_model = outdated || meta_file_was_created;
+		for uuid in asset.uuids.iter() {
+			rebuild_model = rebuild_model || package_outdated(&asset, asset_path, &meta_path, *uuid)?;
+		}
 
-					let asset_modified_time = FileTime::from_last_modification_time(&asset_meta);
-					let build_modified_time = FileTime::from_last_modification_time(&build_meta);
-					let meta_modified_time = FileTime::from_last_modification_time(&meta_meta);
+		if rebuild_model {
+			if model.is_none() {
+				model = import_model()?;
+			}
 
-					needs_reimport = asset_modified_time > build_modified_time || meta_modified_time > build_modified_time;
+			if let Some(ref model) = model {
+				if asset.sync_model_outputs(&model.outputs()) {
+					let serialized = serde_json::to_string_pretty(&asset).map_err(move |_| EditorError::Serialize)?;
+					fs::write(&meta_path, serialized).map_err(move |err| EditorError::Filesystem(err))?;
 				}
+			}
+		}
+	}
+
+	let mut reimported = Vec::new();
+	for (i, uuid) in asset.uuids.iter().enumerate() {
+		let build_path = Path::new(BUILD_ASSET_DIR).join(uuid.to_string()).with_extension(BUILD_ASSET_EXTENSION);
 
-				if needs_reimport {
-					let serialized = match asset.asset_type {
-						AssetType::Shader => {
-							let shader_data = fs::read_to_string(&asset_path).map_err(move |err| EditorError::Filesystem(err))?;
-							let shader_asset = shader_compiler::compile_hlsl(&asset_path, &shader_data)?;
-
-							Some(bincode::serialize(&shader_asset).map_err(move |_| EditorError::Serialize)?)
-						}
-						AssetType::Mesh => {
-							if mesh_packages.is_none() {
-								mesh_packages = import_mesh_packages()?;
-							}
-
-							let Some(ref mesh_packages) = mesh_packages else
-							{
-								panic!("??");
-							};
-
-							Some(bincode::serialize(&mesh_packages[i]).map_err(move |_| EditorError::Serialize)?)
-						}
-						_ => None,
+		let needs_reimport = if matches!(asset.asset_type, AssetType::Mesh) {
+			rebuild_model
+		} else {
+			outdated || meta_file_was_created || package_outdated(&asset, asset_path, &meta_path, *uuid)?
+		};
+
+		if needs_reimport {
+			let serialized = match asset.asset_type {
+				AssetType::Shader => {
+					let shader_data = fs::read_to_string(asset_path).map_err(move |err| EditorError::Filesystem(err))?;
+					let shader_asset = shader_compiler::compile_hlsl(asset_path, &shader_data)?;
+
+					Some(bincode::serialize(&shader_asset).map_err(move |_| EditorError::Serialize)?)
+				}
+				AssetType::Mesh => {
+					if model.is_none() {
+						model = import_model()?;
+					}
+
+					let Some(ref model) = model else
+					{
+						panic!("??");
 					};
 
-					if let Some(serialized) = serialized {
-						let mut output = fs::File::create(&build_path).map_err(move |err| EditorError::Filesystem(err))?;
-						output.write_all(&serialized).map_err(move |err| EditorError::Filesystem(err))?;
-
-						// Touch asset files
-						let now = FileTime::now();
-						if let Err(err) = filetime::set_file_mtime(&build_path, now) {
-							println!(
-								"WARNING: Failed to update date modified for build file {}! Maybe it wasn't created properly? {}",
-								build_path.as_path().to_str().unwrap_or("UNKNOWN_BUILD_PATH"),
-								err
-							);
-						}
-
-						if let Err(err) = filetime::set_file_mtime(&meta_path, now) {
-							println!("WARNING: Failed to update date modified for metadata file! Maybe it wasn't created properly? {}", err);
-						}
-					} else {
-						println!("No output was created for asset {}!", uuid);
+					match asset.model_outputs.get(i) {
+						Some(&output) => model.serialize_output(output, &asset.model_outputs, &asset.uuids)?,
+						None => None,
 					}
 				}
+				AssetType::Texture => {
+					let data = fs::read(asset_path).map_err(move |err| EditorError::Filesystem(err))?;
+					let texture_package = match asset.additional_data {
+						AdditionalAssetData::Texture(ref texture_asset) => super::texture_importer::import_texture(&data, texture_asset)?,
+						_ => super::texture_importer::import_texture(&data, &Default::default())?,
+					};
+
+					Some(bincode::serialize(&texture_package).map_err(move |_| EditorError::Serialize)?)
+				}
+				AssetType::Material => {
+					let material_package = super::material_importer::import_material(asset_path)?;
+
+					Some(bincode::serialize(&material_package).map_err(move |_| EditorError::Serialize)?)
+				}
+				_ => None,
+			};
+
+			if let Some(serialized) = serialized {
+				let mut output = fs::File::create(&build_path).map_err(move |err| EditorError::Filesystem(err))?;
+				output.write_all(&serialized).map_err(move |err| EditorError::Filesystem(err))?;
+				reimported.push(*uuid);
+
+				// Touch asset files
+				let now = FileTime::now();
+				if let Err(err) = filetime::set_file_mtime(&build_path, now) {
+					println!(
+						"WARNING: Failed to update date modified for build file {}! Maybe it wasn't created properly? {}",
+						build_path.as_path().to_str().unwrap_or("UNKNOWN_BUILD_PATH"),
+						err
+					);
+				}
+
+				if let Err(err) = filetime::set_file_mtime(&meta_path, now) {
+					println!("WARNING: Failed to update date modified for metadata file! Maybe it wasn't created properly? {}", err);
+				}
+			} else {
+				println!("No output was created for asset {}!", uuid);
 			}
 		}
 	}
-	Ok(())
+	Ok(reimported)
+}
+
+// Whether the package of `uuid` is missing or older than the source or meta file that it was built from.
+fn package_outdated(asset: &Asset, asset_path: &Path, meta_path: &Path, uuid: Uuid) -> Result<bool, EditorError> {
+	let build_path = Path::new(BUILD_ASSET_DIR).join(uuid.to_string()).with_extension(BUILD_ASSET_EXTENSION);
+	if !build_path.is_file() {
+		return Ok(true);
+	}
+
+	let build_meta = fs::metadata(&build_path).map_err(move |err| EditorError::Filesystem(err))?;
+	let asset_meta = fs::metadata(asset_path).map_err(move |err| EditorError::Filesystem(err))?;
+	let meta_meta = fs::metadata(meta_path).map_err(move |err| EditorError::Filesystem(err))?;
+
+	let asset_modified_time = FileTime::from_last_modification_time(&asset_meta);
+	let build_modified_time = FileTime::from_last_modification_time(&build_meta);
+	let meta_modified_time = FileTime::from_last_modification_time(&meta_meta);
+
+	if asset_modified_time > build_modified_time || meta_modified_time > build_modified_time {
+		return Ok(true);
+	}
+
+	// A material has to be checked against its shader again whenever the shader is rebuilt.
+	if matches!(asset.asset_type, AssetType::Material) {
+		let shader = super::material_importer::read_material_asset(asset_path)?.shader;
+		let shader_build_path = Path::new(BUILD_ASSET_DIR).join(shader.to_string()).with_extension(BUILD_ASSET_EXTENSION);
+		if let Ok(shader_build_meta) = fs::metadata(&shader_build_path) {
+			return Ok(FileTime::from_last_modification_time(&shader_build_meta) > build_modified_time);
+		}
+	}
+
+	Ok(false)
 }
 
 pub fn read_asset(uuid: Uuid, asset_type: AssetType) -> GoldfishResult<Package> {
@@ -249,6 +457,30 @@ pub fn read_asset(uuid: Uuid, asset_type: AssetType) -> GoldfishResult<Package>
 
 			Ok(Package::Mesh(package))
 		}
+		AssetType::Scene => {
+			let contents = fs::read(&build_path).map_err(move |err| GoldfishError::Filesystem(err))?;
+
+			let package = bincode::deserialize::<ScenePackage>(&contents)
+				.map_err(move |err| GoldfishError::Unknown("Failed to deserialize scene package: ".to_string() + &err.to_string() + ". Try cleaning '.build' and reimporting all assets."))?;
+
+			Ok(Package::Scene(package))
+		}
+		AssetType::Texture => {
+			let contents = fs::read(&build_path).map_err(move |err| GoldfishError::Filesystem(err))?;
+
+			let package = bincode::deserialize::<TexturePackage>(&contents)
+				.map_err(move |err| GoldfishError::Unknown("Failed to deserialize texture package: ".to_string() + &err.to_string() + ". Try cleaning '.build' and reimporting all assets."))?;
+
+			Ok(Package::Texture(package))
+		}
+		AssetType::Material => {
+			let contents = fs::read(&build_path).map_err(move |err| GoldfishError::Filesystem(err))?;
+
+			let package = bincode::deserialize::<MaterialPackage>(&contents)
+				.map_err(move |err| GoldfishError::Unknown("Failed to deserialize material package: ".to_string() + &err.to_string() + ". Try cleaning '.build' and reimporting all assets."))?;
+
+			Ok(Package::Material(package))
+		}
 		_ => unimplemented!(),
 	}
 }
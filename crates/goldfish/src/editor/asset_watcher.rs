@@ -0,0 +1,96 @@
+use super::asset;
+use super::EditorError;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+use uuid::Uuid;
+
+// Editors tend to save a file in several steps, so changes that arrive this close to each other are reimported together.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+// Reimports assets on a background thread whenever their source or meta file changes, the uuids of the rebuilt packages are sent to the engine.
+// Dropping the watcher stops the thread.
+pub struct AssetWatcher {
+	watcher: RecommendedWatcher,
+}
+
+impl AssetWatcher {
+	pub fn new(asset_dir: &Path, reloaded: Sender<Uuid>) -> Result<Self, EditorError> {
+		let (sender, receiver) = mpsc::channel();
+		let mut watcher = notify::recommended_watcher(sender).map_err(move |err| EditorError::Watch(err))?;
+		watcher.watch(asset_dir, RecursiveMode::Recursive).map_err(move |err| EditorError::Watch(err))?;
+
+		let asset_dir = asset_dir.to_path_buf();
+		thread::Builder::new()
+			.name("Asset Watcher".to_string())
+			.spawn(move || watch_assets(&asset_dir, receiver, reloaded))
+			.map_err(move |err| EditorError::Filesystem(err))?;
+
+		Ok(Self { watcher })
+	}
+}
+
+fn watch_assets(asset_dir: &Path, events: Receiver<notify::Result<Event>>, reloaded: Sender<Uuid>) {
+	// The watcher owns the other end of the channel, so this stops once it is dropped.
+	while let Ok(event) = events.recv() {
+		let mut changed = HashSet::new();
+		collect_changed_assets(event, &mut changed);
+		while let Ok(event) = events.recv_timeout(DEBOUNCE) {
+			collect_changed_assets(event, &mut changed);
+		}
+
+		let mut reimported = Vec::new();
+		for asset_path in changed {
+			match asset::import_asset(&asset_path) {
+				Ok(uuids) => reimported.extend(uuids),
+				Err(err) => println!("Failed to reimport asset {}: {}", asset_path.to_str().unwrap_or("UNKNOWN_ASSET_PATH"), err),
+			}
+		}
+
+		if reimported.is_empty() {
+			continue;
+		}
+
+		match asset::reimport_materials(asset_dir) {
+			Ok(uuids) => reimported.extend(uuids),
+			Err(err) => println!("Failed to reimport materials: {}", err),
+		}
+
+		for uuid in reimported {
+			println!("Reimported asset {}", uuid);
+			// The engine is only gone when the editor is shutting down, so there's nobody left to notify.
+			let _ = reloaded.send(uuid);
+		}
+	}
+}
+
+// Maps the files touched by `event` to the source files of the assets that they belong to.
+// NOTE: Files that are only included by other assets, such as `.hlsli` headers, don't cause the assets that include them to be reimported.
+fn collect_changed_assets(event: notify::Result<Event>, changed: &mut HashSet<PathBuf>) {
+	let event = match event {
+		Ok(event) => event,
+		Err(err) => {
+			println!("WARNING: Failed to watch assets: {}", err);
+			return;
+		}
+	};
+
+	if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+		return;
+	}
+
+	for path in event.paths {
+		let asset_path = if path.extension().unwrap_or_default() == asset::ASSET_META_EXTENSION {
+			path.with_extension("")
+		} else {
+			path
+		};
+
+		if asset_path.is_file() {
+			changed.insert(asset_path);
+		}
+	}
+}
@@ -2,39 +2,139 @@
 #![allow(unused_imports)]
 
 mod asset;
+mod asset_watcher;
+mod game_library;
+mod gltf_importer;
+mod material_importer;
 mod mesh_importer;
 mod shader_compiler;
-use goldfish::game::{CreateGamelibApi, GameLib};
-use goldfish::GoldfishEngine;
-use libloading::{Library, Symbol};
-use std::path::Path;
+mod texture_importer;
+use goldfish::golden::{self, GoldenResult, GoldenTolerance};
+use goldfish::{GoldfishEngine, Size};
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 use asset::read_asset;
+use asset_watcher::AssetWatcher;
+use game_library::GameLibrary;
 
 const ASSET_DIR: &'static str = "assets/";
 const BUILD_DIR: &'static str = ".build/";
 const BUILD_ASSET_DIR: &'static str = ".build/assets/";
+const GAME_LIB_PATH: &'static str = "target/debug/libgame.so";
+
+const HEADLESS_SIZE: Size = Size { width: 1280, height: 720 };
+const HEADLESS_DEFAULT_FRAMES: usize = 60;
+// The last headless frame is compared against `goldens/game.png`, `scripts/check_goldens.sh` runs the whole check.
+const GOLDEN_NAME: &'static str = "game";
+
+const USAGE: &'static str = "Usage: goldfish_editor [--headless [frames]] [--golden <dir>]";
 
 #[derive(Error, Debug)]
 pub enum EditorError {
 	#[error("Failed to import mesh: {0}")]
 	MeshImport(russimp::RussimpError),
+	#[error("Mesh {mesh} has {vertices} vertices, which is more than 32 bit indices can address")]
+	MeshTooLarge { mesh: String, vertices: usize },
+	#[error("Failed to import glTF: {0}")]
+	GltfImport(gltf::Error),
+	#[error("Failed to import texture: {0}")]
+	TextureImport(image::ImageError),
+	#[error("Texture format {0:?} can't be imported")]
+	UnsupportedTextureFormat(goldfish::renderer::TextureFormat),
 	#[error("Failed to compile shader: {0}")]
 	ShaderCompilation(hassle_rs::HassleError),
 	#[error("Failed to reflect spirv: {0}")]
 	ShaderReflection(rspirv_reflect::ReflectError),
+	#[error("Invalid material {0:?}: {1}")]
+	Material(PathBuf, material_importer::MaterialImportError),
 	#[error("Failed to serialize")]
 	Serialize,
 	#[error("Failed to deserialize")]
 	Deserialize,
+	#[error("Failed to load game library: {0}")]
+	GameLibrary(libloading::Error),
+	#[error("Game library doesn't export its version, it was built against an older engine")]
+	GameLibVersionMissing,
+	#[error("Game library uses ABI version {found} but the editor uses {expected}, rebuild the game")]
+	GameLibAbiMismatch { found: u32, expected: u32 },
+	#[error("Game library was built against a different engine build ({found:#018x}, the editor has {expected:#018x}), rebuild the editor and the game together")]
+	GameLibEngineMismatch { found: u64, expected: u64 },
+	#[error("Failed to watch assets: {0}")]
+	Watch(notify::Error),
 	#[error("An unknown OS filesystem error occurred")]
 	Filesystem(std::io::Error),
 	#[error("An unknown error occurred")]
 	Unknown,
 }
 
+struct EditorArgs {
+	// Number of frames to render without a window, `--headless [frames]`.
+	headless_frames: Option<usize>,
+	// Directory with the reference images to compare the final frame against, `--golden <dir>`. Implies headless.
+	golden_dir: Option<PathBuf>,
+}
+
+fn parse_args() -> Result<EditorArgs, String> {
+	let mut headless_frames = None;
+	let mut golden_dir = None;
+
+	let mut args = std::env::args().skip(1).peekable();
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--headless" => {
+				let frames = args.peek().and_then(|frames| frames.parse().ok());
+				if frames.is_some() {
+					args.next();
+				}
+				headless_frames = Some(frames.unwrap_or(HEADLESS_DEFAULT_FRAMES));
+			}
+			"--golden" => {
+				let dir = args.next().ok_or_else(|| "--golden requires a directory".to_string())?;
+				golden_dir = Some(PathBuf::from(dir));
+			}
+			_ => return Err(format!("Unknown argument {}", arg)),
+		}
+	}
+
+	if golden_dir.is_some() && headless_frames.is_none() {
+		headless_frames = Some(HEADLESS_DEFAULT_FRAMES);
+	}
+
+	Ok(EditorArgs { headless_frames, golden_dir })
+}
+
+// Compares the last frame of a headless run against the reference image, returns whether it passed.
+fn check_golden(engine: &mut GoldfishEngine, golden_dir: &Path) -> bool {
+	let size = engine.get_output_size();
+	let pixels = engine.capture_output();
+	let update = std::env::var_os(golden::UPDATE_GOLDENS_ENV).is_some();
+
+	match golden::check_golden(golden_dir, GOLDEN_NAME, pixels, size, GoldenTolerance::default(), update) {
+		Ok(GoldenResult::Passed(comparison)) => {
+			println!("Golden image {} passed (max difference {})", GOLDEN_NAME, comparison.max_difference);
+			true
+		}
+		Ok(GoldenResult::Updated(path)) => {
+			println!("Updated golden image {:?}", path);
+			true
+		}
+		Err(err) => {
+			println!("Golden image {} failed: {}", GOLDEN_NAME, err);
+			false
+		}
+	}
+}
+
 fn main() {
+	let args = match parse_args() {
+		Ok(args) => args,
+		Err(err) => {
+			println!("{}\n{}", err, USAGE);
+			std::process::exit(2);
+		}
+	};
+
 	if !Path::new(BUILD_DIR).is_dir() {
 		panic!("Failed to find build directory!");
 	}
@@ -43,22 +143,53 @@ fn main() {
 		panic!("Failed to find resource directory!");
 	}
 
-	let lib = unsafe { Library::new(Path::new("target/debug/libgame.so")).expect("Failed to load libgame!") };
-
-	let game_lib = unsafe { lib.get::<Symbol<CreateGamelibApi>>(b"_goldfish_create_game_lib").expect("No gamelib constructor found!")() };
+	let mut game_library = match GameLibrary::load(Path::new(GAME_LIB_PATH)) {
+		Ok(game_library) => game_library,
+		Err(err) => panic!("Failed to load libgame! {}", err),
+	};
 
 	match asset::import_assets(Path::new(ASSET_DIR)) {
 		Err(err) => panic!("Failed to import assets: {}", err),
 		_ => (),
 	}
 
-	let mut engine = GoldfishEngine::new("Goldfish Editor", read_asset);
+	let mut engine = match args.headless_frames {
+		Some(_) => GoldfishEngine::new_headless("Goldfish Editor", read_asset, HEADLESS_SIZE),
+		None => GoldfishEngine::new("Goldfish Editor", read_asset),
+	};
 
-	(game_lib.on_load)(&mut engine);
+	// Headless runs have to be reproducible, so assets are only reloaded while editing.
+	let _asset_watcher = match args.headless_frames {
+		Some(_) => None,
+		None => match AssetWatcher::new(Path::new(ASSET_DIR), engine.asset_reload_sender()) {
+			Ok(asset_watcher) => Some(asset_watcher),
+			Err(err) => {
+				println!("WARNING: Assets won't be reloaded while the editor is running! {}", err);
+				None
+			}
+		},
+	};
 
-	engine.run(|engine, _| {
-		(game_lib.on_update)(engine);
-	});
+	(game_library.api().on_load)(&mut engine);
 
-	(game_lib.on_unload)(&mut engine);
+	// Like assets, the game is only reloaded while editing.
+	match args.headless_frames {
+		Some(frame_count) => engine.run_headless(frame_count, |engine, dt| {
+			game_library.update(engine, dt);
+		}),
+		None => engine.run(|engine, dt| {
+			game_library.reload_if_changed(engine);
+			game_library.update(engine, dt);
+			game_library.editor_ui(engine);
+		}),
+	}
+
+	let golden_passed = args.golden_dir.map_or(true, |golden_dir| check_golden(&mut engine, &golden_dir));
+
+	game_library.unload_game(&mut engine);
+
+	if !golden_passed {
+		std::mem::drop(engine);
+		std::process::exit(1);
+	}
 }
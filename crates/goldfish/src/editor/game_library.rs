@@ -0,0 +1,190 @@
+use super::{EditorError, BUILD_DIR};
+use goldfish::game::{CreateGamelibApi, GameLib, GameLibVersion, GameLibVersionApi, CREATE_GAME_LIB_SYMBOL, FIXED_UPDATE_DT, GAME_LIB_VERSION_SYMBOL};
+use goldfish::{GoldfishEngine, Size};
+use libloading::Library;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// The linker writes the library in several steps, so a new build is only loaded once it hasn't been touched for this long.
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+// Fixed updates that fall further behind than this are dropped, otherwise a slow frame makes the next one even slower.
+const MAX_FIXED_UPDATES_PER_FRAME: u32 = 5;
+
+// The game library, loaded from a copy in the build directory so that cargo can replace the original while the editor is running.
+pub struct GameLibrary {
+	path: PathBuf,
+	modified: SystemTime,
+	generation: u32,
+	shadow_path: PathBuf,
+	api: GameLib,
+	library: Library,
+	fixed_update_time: Duration,
+	output_size: Option<Size>,
+}
+
+impl GameLibrary {
+	pub fn load(path: &Path) -> Result<Self, EditorError> {
+		let modified = modified_time(path)?;
+		let (shadow_path, library, api) = load_shadow_copy(path, 0)?;
+
+		Ok(Self {
+			path: path.to_path_buf(),
+			modified,
+			generation: 0,
+			shadow_path,
+			api,
+			library,
+			fixed_update_time: Duration::ZERO,
+			output_size: None,
+		})
+	}
+
+	pub fn api(&self) -> &GameLib {
+		&self.api
+	}
+
+	pub fn update(&mut self, engine: &mut GoldfishEngine, dt: Duration) {
+		let output_size = engine.get_output_size();
+		if self.output_size.map_or(false, |old_size| old_size != output_size) {
+			if let Some(on_resize) = self.api.on_resize {
+				on_resize(engine, output_size);
+			}
+		}
+		self.output_size = Some(output_size);
+
+		if let Some(on_fixed_update) = self.api.on_fixed_update {
+			self.fixed_update_time = (self.fixed_update_time + dt).min(FIXED_UPDATE_DT * MAX_FIXED_UPDATES_PER_FRAME);
+			while self.fixed_update_time >= FIXED_UPDATE_DT {
+				self.fixed_update_time -= FIXED_UPDATE_DT;
+				on_fixed_update(engine);
+			}
+		}
+
+		(self.api.on_update)(engine);
+	}
+
+	// The game state has to be gone before the library is closed, since the code that drops it lives in the library.
+	pub fn unload_game(&self, engine: &mut GoldfishEngine) {
+		(self.api.on_unload)(engine);
+		if let Some(type_name) = engine.drop_game_state() {
+			println!(
+				"WARNING: The game library didn't take back its {} state in on_unload, it is dropped without being destroyed!",
+				type_name
+			);
+		}
+	}
+
+	pub fn editor_ui(&mut self, engine: &mut GoldfishEngine) {
+		if let Some(on_editor_ui) = self.api.on_editor_ui {
+			on_editor_ui(engine);
+		}
+	}
+
+	// Swaps in a rebuilt library, the old library saves whatever it wants to keep in `on_save_state` and the new library picks it up
+	// in `on_restore_state`. A build that fails to load or is incompatible is skipped and the old library keeps running.
+	// Returns whether the library was reloaded.
+	pub fn reload_if_changed(&mut self, engine: &mut GoldfishEngine) -> bool {
+		// Cargo removes the library while it is relinking it.
+		let Ok(modified) = modified_time(&self.path) else {
+			return false;
+		};
+
+		let settled = SystemTime::now().duration_since(modified).map_or(false, |age| age >= SETTLE_TIME);
+		if modified <= self.modified || !settled {
+			return false;
+		}
+		self.modified = modified;
+
+		let generation = self.generation + 1;
+		let (shadow_path, library, api) = match load_shadow_copy(&self.path, generation) {
+			Ok(loaded) => loaded,
+			Err(err) => {
+				println!("WARNING: Failed to reload the game library, the old one keeps running! {}", err);
+				return false;
+			}
+		};
+
+		if let Some(on_save_state) = self.api.on_save_state {
+			on_save_state(engine);
+		}
+		self.unload_game(engine);
+
+		self.api = api;
+		let old_library = std::mem::replace(&mut self.library, library);
+		if let Err(err) = old_library.close() {
+			println!("WARNING: Failed to close the old game library! {}", err);
+		}
+
+		let old_shadow_path = std::mem::replace(&mut self.shadow_path, shadow_path);
+		if let Err(err) = fs::remove_file(&old_shadow_path) {
+			println!("WARNING: Failed to remove the old game library {}! {}", old_shadow_path.to_str().unwrap_or("UNKNOWN_LIBRARY_PATH"), err);
+		}
+		self.generation = generation;
+
+		(self.api.on_load)(engine);
+		if let Some(on_restore_state) = self.api.on_restore_state.filter(|_| engine.saved_game_state.is_some()) {
+			on_restore_state(engine);
+		}
+		// Whatever the new library didn't take would be restored by the next reload even though it is out of date by then.
+		engine.saved_game_state = None;
+
+		println!("Reloaded game library {}", self.path.to_str().unwrap_or("UNKNOWN_LIBRARY_PATH"));
+		true
+	}
+}
+
+impl Drop for GameLibrary {
+	fn drop(&mut self) {
+		// The library is still mapped at this point, which is fine since the file only goes away once it is closed.
+		let _ = fs::remove_file(&self.shadow_path);
+	}
+}
+
+fn modified_time(path: &Path) -> Result<SystemTime, EditorError> {
+	fs::metadata(path).and_then(|metadata| metadata.modified()).map_err(move |err| EditorError::Filesystem(err))
+}
+
+// Every generation gets its own copy, since the dynamic loader hands out the already loaded library for a path that it has seen before.
+fn load_shadow_copy(path: &Path, generation: u32) -> Result<(PathBuf, Library, GameLib), EditorError> {
+	let shadow_path = Path::new(BUILD_DIR).join(path.file_name().unwrap_or_default()).with_extension(format!("{}.so", generation));
+	fs::copy(path, &shadow_path).map_err(move |err| EditorError::Filesystem(err))?;
+
+	// Nothing but the version can be read from the library until it is known to be compatible.
+	let loaded = unsafe {
+		Library::new(&shadow_path).map_err(move |err| EditorError::GameLibrary(err)).and_then(|library| {
+			let version = library.get::<GameLibVersionApi>(GAME_LIB_VERSION_SYMBOL).map_err(move |_| EditorError::GameLibVersionMissing)?();
+			check_version(version)?;
+
+			let api = library.get::<CreateGamelibApi>(CREATE_GAME_LIB_SYMBOL).map_err(move |err| EditorError::GameLibrary(err))?();
+			Ok((library, api))
+		})
+	};
+
+	match loaded {
+		Ok((library, api)) => Ok((shadow_path, library, api)),
+		Err(err) => {
+			let _ = fs::remove_file(&shadow_path);
+			Err(err)
+		}
+	}
+}
+
+fn check_version(version: GameLibVersion) -> Result<(), EditorError> {
+	if version.abi_version != GameLibVersion::CURRENT.abi_version {
+		return Err(EditorError::GameLibAbiMismatch {
+			found: version.abi_version,
+			expected: GameLibVersion::CURRENT.abi_version,
+		});
+	}
+
+	if version.engine_build_hash != GameLibVersion::CURRENT.engine_build_hash {
+		return Err(EditorError::GameLibEngineMismatch {
+			found: version.engine_build_hash,
+			expected: GameLibVersion::CURRENT.engine_build_hash,
+		});
+	}
+
+	Ok(())
+}
@@ -0,0 +1,300 @@
+use super::asset::TextureAsset;
+use super::mesh_importer::{build_vertices, ImportedMaterial, ImportedModel, ImportedNode, MeshImportWarning};
+use super::texture_importer::import_image;
+use super::EditorError;
+use glam::{vec2, vec3, Mat4, Quat, Vec3};
+use gltf::image::Format;
+use gltf::mesh::Mode;
+use goldfish::package::{MaterialSlot, MeshPackage, SceneSkin, VertexJoints};
+use goldfish::renderer::MeshIndices;
+use image::error::{ImageError, ParameterError, ParameterErrorKind};
+use image::{DynamicImage, ImageBuffer};
+use std::path::Path;
+
+// glTF is right handed, so everything gets mirrored along Z the same way assimp's `MakeLeftHanded` does it for the other formats.
+fn mirror_z(v: Vec3) -> Vec3 {
+	vec3(v.x, v.y, -v.z)
+}
+
+fn mirror_transform_z(transform: Mat4) -> Mat4 {
+	let mirror = Mat4::from_scale(vec3(1.0, 1.0, -1.0));
+	mirror * transform * mirror
+}
+
+pub fn import_gltf(path: &Path) -> Result<ImportedModel, EditorError> {
+	// External buffers and images are loaded relative to `path`, so .gltf files can't be imported from memory.
+	let (document, buffers, images) = gltf::import(path).map_err(move |err| EditorError::GltfImport(err))?;
+
+	let mut warnings = Vec::new();
+	// Every primitive gets a slot, skipped primitives leave theirs empty.
+	let mut meshes = Vec::new();
+	// The slots of the primitives of every mesh that weren't skipped, along with their material slot.
+	let mut mesh_primitives = Vec::new();
+	// Primitives without a material use the default material, which gets a slot after all of the others.
+	let default_material = document.materials().len() as u32;
+	let mut uses_default_material = false;
+	for mesh in document.meshes() {
+		let name = mesh.name().map_or_else(|| format!("Mesh {}", mesh.index()), str::to_owned);
+
+		let mut primitives = Vec::new();
+		for primitive in mesh.primitives() {
+			let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+			let positions = match reader.read_positions() {
+				Some(positions) => positions.map(|position| mirror_z(Vec3::from(position))).collect::<Vec<_>>(),
+				// Only extensions can leave out positions.
+				None => {
+					meshes.push(None);
+					continue;
+				}
+			};
+
+			// NOTE: Vertices are indexed with at most 32 bits, anything bigger would have to be split up into several meshes.
+			if positions.len() > u32::MAX as usize + 1 {
+				return Err(EditorError::MeshTooLarge {
+					mesh: name.clone(),
+					vertices: positions.len(),
+				});
+			}
+
+			let indices = match reader.read_indices() {
+				Some(indices) => indices.into_u32().collect::<Vec<_>>(),
+				None => (0..positions.len() as u32).collect(),
+			};
+
+			let indices = match triangle_list(primitive.mode(), indices) {
+				Ok(indices) => indices,
+				Err(primitive) => {
+					warnings.push(MeshImportWarning::NonTrianglePrimitives { mesh: name.clone(), primitive });
+					meshes.push(None);
+					continue;
+				}
+			};
+
+			// Bitangents are worked out before mirroring, since mirroring flips the handedness of the tangent space.
+			let normals = reader.read_normals().map(|normals| normals.map(Vec3::from).collect::<Vec<_>>());
+			let tangents = match (&normals, reader.read_tangents()) {
+				(Some(normals), Some(tangents)) => Some(
+					tangents
+						.zip(normals)
+						.map(|(tangent, &normal)| {
+							let [x, y, z, sign] = tangent;
+							(mirror_z(vec3(x, y, z)), mirror_z(normal.cross(vec3(x, y, z)) * sign))
+						})
+						.unzip(),
+				),
+				_ => None,
+			};
+			let normals = normals.map(|normals| normals.into_iter().map(mirror_z).collect());
+
+			// glTF UVs start at the top left, flip them to match what assimp gives for the other formats.
+			let uvs = reader.read_tex_coords(0).map(|uvs| uvs.into_f32().map(|uv| vec2(uv[0], 1.0 - uv[1])).collect());
+
+			let joints = match (reader.read_joints(0), reader.read_weights(0)) {
+				(Some(joints), Some(weights)) => joints.into_u16().zip(weights.into_f32()).map(|(indices, weights)| VertexJoints { indices, weights }).collect(),
+				_ => Vec::new(),
+			};
+
+			let vertices = build_vertices(&name, &positions, normals, tangents, uvs, &indices, &mut warnings);
+
+			let material = primitive.material().index().map_or(default_material, |material| material as u32);
+			uses_default_material |= material == default_material;
+
+			primitives.push((meshes.len(), material));
+			meshes.push(Some(MeshPackage {
+				indices: MeshIndices::new(indices, vertices.len()),
+				vertices,
+				joints,
+			}));
+		}
+
+		mesh_primitives.push(primitives);
+	}
+
+	// glTF doesn't say what an image holds, so every image is imported for whatever the first material that uses it needs it for.
+	// Images that are used both as color and as linear data get a second texture after all of the images for the other uses.
+	// Embedded textures have no meta file to opt into compression with, so they stay uncompressed.
+	let mut image_srgb: Vec<Option<bool>> = vec![None; images.len()];
+	// The image of every second texture.
+	let mut second_textures = Vec::new();
+	let mut use_texture = |texture: gltf::Texture, srgb: bool| -> usize {
+		let index = texture.source().index();
+		if *image_srgb[index].get_or_insert(srgb) == srgb {
+			return index;
+		}
+
+		let second = second_textures.iter().position(|&image| image == index).unwrap_or_else(|| {
+			second_textures.push(index);
+			second_textures.len() - 1
+		});
+		image_srgb.len() + second
+	};
+
+	// NOTE: Only the first UV set is imported, so textures that use any other set will be sampled with the wrong UVs.
+	let mut materials = document
+		.materials()
+		.enumerate()
+		.map(|(i, material)| {
+			let pbr = material.pbr_metallic_roughness();
+			ImportedMaterial {
+				slot: MaterialSlot {
+					base_color: pbr.base_color_factor(),
+					metallic: pbr.metallic_factor(),
+					roughness: pbr.roughness_factor(),
+					emissive: material.emissive_factor(),
+					..MaterialSlot::new(material.name().map_or_else(|| format!("Material {}", i), str::to_owned))
+				},
+				base_color_texture: pbr.base_color_texture().map(|info| use_texture(info.texture(), true)),
+				metallic_roughness_texture: pbr.metallic_roughness_texture().map(|info| use_texture(info.texture(), false)),
+				normal_texture: material.normal_texture().map(|normal| use_texture(normal.texture(), false)),
+				occlusion_texture: material.occlusion_texture().map(|occlusion| use_texture(occlusion.texture(), false)),
+				emissive_texture: material.emissive_texture().map(|info| use_texture(info.texture(), true)),
+			}
+		})
+		.collect::<Vec<_>>();
+
+	if uses_default_material {
+		materials.push(ImportedMaterial::untextured(MaterialSlot::new("Default".to_owned())));
+	}
+
+	let images = images.into_iter().map(decode_image).collect::<Result<Vec<_>, _>>()?;
+	let texture_asset = |srgb: bool| TextureAsset { srgb, ..Default::default() };
+	let textures = images
+		.iter()
+		.zip(&image_srgb)
+		.map(|(image, srgb)| import_image(image, &texture_asset(srgb.unwrap_or(false))))
+		.chain(second_textures.iter().map(|&image| import_image(&images[image], &texture_asset(image_srgb[image] == Some(false)))))
+		.collect::<Result<Vec<_>, _>>()?;
+
+	// glTF can have several root nodes, so they all get placed under one root to keep the scene a single hierarchy.
+	let mut nodes = vec![ImportedNode {
+		name: document.default_scene().and_then(|scene| scene.name().map(str::to_owned)).unwrap_or_else(|| "Root".to_owned()),
+		parent: None,
+		transform: Mat4::IDENTITY,
+		meshes: Vec::new(),
+		skin: None,
+	}];
+
+	let mut has_parent = vec![false; document.nodes().len()];
+	for node in document.nodes() {
+		for child in node.children() {
+			has_parent[child.index()] = true;
+		}
+	}
+
+	// Where every node ended up in `nodes`, which is needed to find the joints of skins.
+	let mut node_indices = vec![0; has_parent.len()];
+	for root in document.nodes().filter(|node| !has_parent[node.index()]) {
+		flatten_nodes(root, 0, &mesh_primitives, &mut node_indices, &mut nodes);
+	}
+
+	let skins = document
+		.skins()
+		.map(|skin| {
+			let joints = skin.joints().map(|joint| node_indices[joint.index()]).collect::<Vec<_>>();
+
+			// Skins without inverse bind matrices have their joints already in the space of the mesh.
+			let inverse_bind_matrices = match skin.reader(|buffer| Some(&buffers[buffer.index()])).read_inverse_bind_matrices() {
+				Some(matrices) => matrices.map(|matrix| mirror_transform_z(Mat4::from_cols_array_2d(&matrix)).to_cols_array()).collect(),
+				None => vec![Mat4::IDENTITY.to_cols_array(); joints.len()],
+			};
+
+			SceneSkin { joints, inverse_bind_matrices }
+		})
+		.collect();
+
+	Ok(ImportedModel {
+		meshes,
+		textures,
+		warnings,
+		nodes,
+		materials,
+		skins,
+	})
+}
+
+// Turns the indices of a primitive into a triangle list, or returns what the primitive is made of if it has no triangles.
+fn triangle_list(mode: Mode, indices: Vec<u32>) -> Result<Vec<u32>, &'static str> {
+	match mode {
+		Mode::Triangles => Ok(indices),
+		// Every other triangle of a strip is wound the other way around.
+		Mode::TriangleStrip => Ok((0..indices.len().saturating_sub(2))
+			.flat_map(|i| {
+				if i % 2 == 0 {
+					[indices[i], indices[i + 1], indices[i + 2]]
+				} else {
+					[indices[i + 1], indices[i], indices[i + 2]]
+				}
+			})
+			.collect()),
+		Mode::TriangleFan => Ok((1..indices.len().saturating_sub(1)).flat_map(|i| [indices[0], indices[i], indices[i + 1]]).collect()),
+		Mode::Points => Err("points"),
+		Mode::Lines | Mode::LineLoop | Mode::LineStrip => Err("lines"),
+	}
+}
+
+// Appends `node` and everything below it to `nodes`, parents always end up before their children.
+fn flatten_nodes(node: gltf::Node, parent: usize, mesh_primitives: &[Vec<(usize, u32)>], node_indices: &mut [usize], nodes: &mut Vec<ImportedNode>) {
+	let (translation, rotation, scale) = node.transform().decomposed();
+	let transform = Mat4::from_scale_rotation_translation(Vec3::from(scale), Quat::from_array(rotation), Vec3::from(translation));
+
+	let index = nodes.len();
+	node_indices[node.index()] = index;
+	nodes.push(ImportedNode {
+		name: node.name().map_or_else(|| format!("Node {}", node.index()), str::to_owned),
+		parent: Some(parent),
+		transform: mirror_transform_z(transform),
+		meshes: node.mesh().map_or_else(Vec::new, |mesh| mesh_primitives[mesh.index()].clone()),
+		skin: node.skin().map(|skin| skin.index()),
+	});
+
+	for child in node.children() {
+		flatten_nodes(child, index, mesh_primitives, node_indices, nodes);
+	}
+}
+
+// glTF decodes images into tightly packed texels, 16 and 32 bit channels are stored in native byte order.
+fn decode_image(image: gltf::image::Data) -> Result<DynamicImage, EditorError> {
+	let (width, height) = (image.width, image.height);
+	let u16s = |pixels: Vec<u8>| pixels.chunks_exact(2).map(|bytes| u16::from_ne_bytes([bytes[0], bytes[1]])).collect::<Vec<_>>();
+	let f32s = |pixels: Vec<u8>| pixels.chunks_exact(4).map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])).collect::<Vec<_>>();
+
+	let decoded = match image.format {
+		Format::R8 => ImageBuffer::from_raw(width, height, image.pixels).map(DynamicImage::ImageLuma8),
+		Format::R8G8 => ImageBuffer::from_raw(width, height, image.pixels).map(DynamicImage::ImageLumaA8),
+		Format::R8G8B8 => ImageBuffer::from_raw(width, height, image.pixels).map(DynamicImage::ImageRgb8),
+		Format::R8G8B8A8 => ImageBuffer::from_raw(width, height, image.pixels).map(DynamicImage::ImageRgba8),
+		Format::R16 => ImageBuffer::from_raw(width, height, u16s(image.pixels)).map(DynamicImage::ImageLuma16),
+		Format::R16G16 => ImageBuffer::from_raw(width, height, u16s(image.pixels)).map(DynamicImage::ImageLumaA16),
+		Format::R16G16B16 => ImageBuffer::from_raw(width, height, u16s(image.pixels)).map(DynamicImage::ImageRgb16),
+		Format::R16G16B16A16 => ImageBuffer::from_raw(width, height, u16s(image.pixels)).map(DynamicImage::ImageRgba16),
+		Format::R32G32B32FLOAT => ImageBuffer::from_raw(width, height, f32s(image.pixels)).map(DynamicImage::ImageRgb32F),
+		Format::R32G32B32A32FLOAT => ImageBuffer::from_raw(width, height, f32s(image.pixels)).map(DynamicImage::ImageRgba32F),
+	};
+
+	decoded.ok_or_else(|| EditorError::TextureImport(ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::DimensionMismatch))))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn strips_flip_every_other_triangle() {
+		assert_eq!(triangle_list(Mode::TriangleStrip, vec![0, 1, 2, 3, 4]), Ok(vec![0, 1, 2, 2, 1, 3, 2, 3, 4]));
+		assert_eq!(triangle_list(Mode::TriangleStrip, vec![0, 1]), Ok(Vec::new()));
+	}
+
+	#[test]
+	fn fans_share_their_first_vertex() {
+		assert_eq!(triangle_list(Mode::TriangleFan, vec![0, 1, 2, 3, 4]), Ok(vec![0, 1, 2, 0, 2, 3, 0, 3, 4]));
+		assert_eq!(triangle_list(Mode::TriangleFan, vec![0, 1]), Ok(Vec::new()));
+	}
+
+	#[test]
+	fn points_and_lines_are_not_triangles() {
+		assert_eq!(triangle_list(Mode::Triangles, vec![2, 1, 0]), Ok(vec![2, 1, 0]));
+		assert_eq!(triangle_list(Mode::Points, vec![0, 1, 2]), Err("points"));
+		assert_eq!(triangle_list(Mode::LineLoop, vec![0, 1, 2]), Err("lines"));
+	}
+}
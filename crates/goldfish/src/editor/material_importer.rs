@@ -0,0 +1,590 @@
+use super::asset::read_asset;
+use super::EditorError;
+use goldfish::package::{AssetType, MaterialBinding, MaterialPackage, Package, ShaderPackage};
+use goldfish::renderer::SamplerDesc;
+use rspirv::dr::{Instruction, Module, Operand};
+use rspirv::spirv::{Decoration, Op};
+use rspirv_reflect::{DescriptorType, Reflection};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+use uuid::Uuid;
+
+// The source of a material, which is a `.mat` JSON file.
+#[derive(Serialize, Deserialize)]
+pub struct MaterialAsset {
+	pub shader: Uuid,
+	// The descriptor set of the shader that the material provides everything for.
+	pub set: u32,
+	// Values of cbuffer members keyed by cbuffer and then by member name, members that are left out are zeroed.
+	// Vectors and matrices are given as arrays, matrices column by column the same way glam stores them.
+	#[serde(default)]
+	pub constants: HashMap<String, HashMap<String, Value>>,
+	// Keyed by the name of the Texture2D that the texture gets bound to.
+	#[serde(default)]
+	pub textures: HashMap<String, Uuid>,
+	// Samplers that are left out use the default sampler.
+	#[serde(default)]
+	pub samplers: HashMap<String, SamplerDesc>,
+}
+
+#[derive(Error, Debug)]
+pub enum MaterialImportError {
+	#[error("Failed to parse material: {0}")]
+	Parse(serde_json::Error),
+	#[error("Shader {0} has not been imported")]
+	MissingShader(Uuid),
+	#[error("The shader has no descriptor set {0}")]
+	MissingSet(u32),
+	#[error("The shader has no {kind} named {name} in the material's descriptor set")]
+	UnknownBinding { kind: &'static str, name: String },
+	#[error("{0} is a kind of resource that materials can't provide")]
+	UnsupportedBinding(String),
+	#[error("{0} was not given a texture")]
+	MissingTexture(String),
+	#[error("{cbuffer} has no member named {member}")]
+	UnknownConstant { cbuffer: String, member: String },
+	#[error("{cbuffer}.{member} has a type that materials can't set")]
+	UnsupportedConstant { cbuffer: String, member: String },
+	#[error("{cbuffer}.{member} takes {count} {scalar:?} values")]
+	InvalidConstant { cbuffer: String, member: String, scalar: ScalarType, count: u32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalarType {
+	F32,
+	I32,
+	U32,
+}
+
+// A cbuffer member that materials can set. Its values are given column by column, the value in column `c` and row `r` is written
+// `c * column_stride + r * row_stride` bytes after the offset of the member. Scalars and vectors are a single column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ConstantType {
+	scalar: ScalarType,
+	columns: u32,
+	rows: u32,
+	column_stride: u32,
+	row_stride: u32,
+}
+
+impl ConstantType {
+	fn count(&self) -> u32 {
+		self.columns * self.rows
+	}
+}
+
+// How the matrices of a struct member are laid out, which SPIR-V decorates on the member instead of the matrix type.
+#[derive(Debug, Clone, Copy)]
+struct MatrixLayout {
+	stride: u32,
+	row_major: bool,
+}
+
+struct CBufferMember {
+	name: String,
+	offset: u32,
+	// Only set for the types that materials support.
+	ty: Option<ConstantType>,
+}
+
+enum ReflectedBinding {
+	CBuffer { name: String, members: Vec<CBufferMember>, size: u32 },
+	Texture(String),
+	Sampler(String),
+	Unsupported(String),
+}
+
+impl ReflectedBinding {
+	fn kind_and_name(&self) -> (&'static str, &str) {
+		match self {
+			ReflectedBinding::CBuffer { name, .. } => ("cbuffer", name),
+			ReflectedBinding::Texture(name) => ("texture", name),
+			ReflectedBinding::Sampler(name) => ("sampler", name),
+			ReflectedBinding::Unsupported(name) => ("resource", name),
+		}
+	}
+}
+
+pub fn read_material_asset(path: &Path) -> Result<MaterialAsset, EditorError> {
+	let contents = fs::read_to_string(path).map_err(move |err| EditorError::Filesystem(err))?;
+	serde_json::from_str::<MaterialAsset>(&contents).map_err(move |err| EditorError::Material(path.to_path_buf(), MaterialImportError::Parse(err)))
+}
+
+// Materials are imported after every other asset, so their shader has already been compiled.
+pub fn import_material(path: &Path) -> Result<MaterialPackage, EditorError> {
+	let invalid = |err: MaterialImportError| EditorError::Material(path.to_path_buf(), err);
+
+	let material = read_material_asset(path)?;
+	let shader = match read_asset(material.shader, AssetType::Shader) {
+		Ok(Package::Shader(shader)) => shader,
+		_ => return Err(invalid(MaterialImportError::MissingShader(material.shader))),
+	};
+
+	let layout = reflect_descriptor_set(&shader, material.set)?;
+	if layout.is_empty() {
+		return Err(invalid(MaterialImportError::MissingSet(material.set)));
+	}
+
+	// Everything the material sets has to end up somewhere, otherwise it is most likely a typo.
+	let unknown = material
+		.constants
+		.keys()
+		.map(|name| ("cbuffer", name))
+		.chain(material.textures.keys().map(|name| ("texture", name)))
+		.chain(material.samplers.keys().map(|name| ("sampler", name)))
+		.find(|&(kind, name)| layout.values().all(|binding| binding.kind_and_name() != (kind, name.as_str())));
+
+	if let Some((kind, name)) = unknown {
+		return Err(invalid(MaterialImportError::UnknownBinding { kind, name: name.clone() }));
+	}
+
+	let mut bindings = Vec::new();
+	for (binding, reflected) in layout {
+		let value = match reflected {
+			ReflectedBinding::CBuffer { name, members, size } => {
+				let mut data = vec![0u8; size as usize];
+				for (member_name, value) in material.constants.get(&name).into_iter().flatten() {
+					let Some(member) = members.iter().find(|member| member.name == *member_name) else {
+						return Err(invalid(MaterialImportError::UnknownConstant {
+							cbuffer: name,
+							member: member_name.clone(),
+						}));
+					};
+
+					let Some(ty) = member.ty else {
+						return Err(invalid(MaterialImportError::UnsupportedConstant {
+							cbuffer: name,
+							member: member_name.clone(),
+						}));
+					};
+
+					let Some(bytes) = constant_bytes(value, ty.scalar, ty.count()) else {
+						return Err(invalid(MaterialImportError::InvalidConstant {
+							cbuffer: name,
+							member: member_name.clone(),
+							scalar: ty.scalar,
+							count: ty.count(),
+						}));
+					};
+
+					write_constant(&mut data, member.offset, ty, &bytes);
+				}
+
+				MaterialBinding::CBuffer(data)
+			}
+			ReflectedBinding::Texture(name) => match material.textures.get(&name) {
+				Some(texture) => MaterialBinding::Texture(*texture),
+				None => return Err(invalid(MaterialImportError::MissingTexture(name))),
+			},
+			ReflectedBinding::Sampler(name) => MaterialBinding::Sampler(material.samplers.get(&name).copied().unwrap_or_default()),
+			ReflectedBinding::Unsupported(name) => return Err(invalid(MaterialImportError::UnsupportedBinding(name))),
+		};
+
+		bindings.push((binding, value));
+	}
+
+	Ok(MaterialPackage {
+		shader: material.shader,
+		set: material.set,
+		bindings,
+	})
+}
+
+fn constant_bytes(value: &Value, scalar: ScalarType, count: u32) -> Option<Vec<u8>> {
+	let values = match value {
+		Value::Array(values) => values.iter().collect::<Vec<_>>(),
+		value => vec![value],
+	};
+
+	if values.len() != count as usize {
+		return None;
+	}
+
+	values
+		.into_iter()
+		.map(|value| match scalar {
+			ScalarType::F32 => value.as_f64().map(|value| (value as f32).to_ne_bytes()),
+			ScalarType::I32 => value.as_i64().and_then(|value| i32::try_from(value).ok()).map(|value| value.to_ne_bytes()),
+			ScalarType::U32 => value.as_u64().and_then(|value| u32::try_from(value).ok()).map(|value| value.to_ne_bytes()),
+		})
+		.collect::<Option<Vec<_>>>()
+		.map(|values| values.concat())
+}
+
+// Writes the values of a member given column by column, `bytes` holds 4 bytes for every value.
+fn write_constant(data: &mut [u8], offset: u32, ty: ConstantType, bytes: &[u8]) {
+	for (i, bytes) in bytes.chunks_exact(4).enumerate() {
+		let (column, row) = (i as u32 / ty.rows, i as u32 % ty.rows);
+		let offset = offset + column * ty.column_stride + row * ty.row_stride;
+		data[offset as usize..][..bytes.len()].copy_from_slice(bytes);
+	}
+}
+
+// The bindings that the stages of `shader` declare in `set`.
+fn reflect_descriptor_set(shader: &ShaderPackage, set: u32) -> Result<BTreeMap<u32, ReflectedBinding>, EditorError> {
+	let mut bindings = BTreeMap::new();
+	for ir in [&shader.vs_ir, &shader.ps_ir].into_iter().flatten() {
+		let reflection = Reflection::new_from_spirv(bytemuck::cast_slice(ir.as_slice())).map_err(move |err| EditorError::ShaderReflection(err))?;
+		let sets = reflection.get_descriptor_sets().map_err(move |err| EditorError::ShaderReflection(err))?;
+
+		for (&binding, info) in sets.get(&set).into_iter().flatten() {
+			bindings.entry(binding).or_insert_with(|| match info.ty {
+				DescriptorType::UNIFORM_BUFFER => match reflect_cbuffer(&reflection.0, set, binding) {
+					Some((members, size)) => ReflectedBinding::CBuffer {
+						name: info.name.clone(),
+						members,
+						size,
+					},
+					None => ReflectedBinding::Unsupported(info.name.clone()),
+				},
+				DescriptorType::SAMPLED_IMAGE => ReflectedBinding::Texture(info.name.clone()),
+				DescriptorType::SAMPLER => ReflectedBinding::Sampler(info.name.clone()),
+				_ => ReflectedBinding::Unsupported(info.name.clone()),
+			});
+		}
+	}
+
+	Ok(bindings)
+}
+
+fn find_type(module: &Module, id: u32) -> Option<&Instruction> {
+	module.types_global_values.iter().find(|inst| inst.result_id == Some(id))
+}
+
+// The operands that follow `decoration` on member `member` of struct `id`, if the member has that decoration.
+fn member_decoration(module: &Module, id: u32, member: u32, decoration: Decoration) -> Option<&[Operand]> {
+	module.annotations.iter().find_map(|inst| match inst.operands.as_slice() {
+		[Operand::IdRef(target), Operand::LiteralInt32(index), Operand::Decoration(decorated), operands @ ..] if *target == id && *index == member && *decorated == decoration => Some(operands),
+		_ => None,
+	})
+}
+
+fn matrix_layout(module: &Module, id: u32, member: u32) -> Option<MatrixLayout> {
+	let stride = match member_decoration(module, id, member, Decoration::MatrixStride)? {
+		[Operand::LiteralInt32(stride)] => *stride,
+		_ => return None,
+	};
+
+	Some(MatrixLayout {
+		stride,
+		row_major: member_decoration(module, id, member, Decoration::RowMajor).is_some(),
+	})
+}
+
+// The members of the cbuffer bound to `binding` of `set` and its size, with members in the order that they are declared.
+// Cbuffers with members whose size can't be worked out aren't supported.
+fn reflect_cbuffer(module: &Module, set: u32, binding: u32) -> Option<(Vec<CBufferMember>, u32)> {
+	let is_decorated = |id: u32, decoration: Decoration, value: u32| {
+		module
+			.annotations
+			.iter()
+			.any(|inst| inst.class.opcode == Op::Decorate && inst.operands == [Operand::IdRef(id), Operand::Decoration(decoration), Operand::LiteralInt32(value)])
+	};
+
+	let variable = module
+		.types_global_values
+		.iter()
+		.filter(|inst| inst.class.opcode == Op::Variable)
+		.filter_map(|inst| inst.result_id)
+		.find(|&id| is_decorated(id, Decoration::DescriptorSet, set) && is_decorated(id, Decoration::Binding, binding))?;
+
+	let pointer = find_type(module, variable)?.result_type?;
+	let block = match find_type(module, pointer)?.operands.as_slice() {
+		[Operand::StorageClass(_), Operand::IdRef(block)] => *block,
+		_ => return None,
+	};
+
+	let mut members = Vec::new();
+	for (i, operand) in find_type(module, block)?.operands.iter().enumerate() {
+		let Operand::IdRef(member_type) = operand else {
+			return None;
+		};
+
+		let name = module.debug_names.iter().find_map(|inst| match inst.operands.as_slice() {
+			[Operand::IdRef(id), Operand::LiteralInt32(member), Operand::LiteralString(name)] if *id == block && *member == i as u32 => Some(name.clone()),
+			_ => None,
+		})?;
+
+		let offset = match member_decoration(module, block, i as u32, Decoration::Offset)? {
+			[Operand::LiteralInt32(offset)] => *offset,
+			_ => return None,
+		};
+
+		let ty = reflect_constant_type(module, *member_type, matrix_layout(module, block, i as u32));
+		members.push(CBufferMember { name, offset, ty });
+	}
+
+	let size = struct_size(module, block)?;
+	Some((members, (size + 15) / 16 * 16))
+}
+
+// `matrix` is the layout of the member that the type belongs to, only matrices need it.
+fn reflect_constant_type(module: &Module, id: u32, matrix: Option<MatrixLayout>) -> Option<ConstantType> {
+	let scalar = |scalar: ScalarType| ConstantType {
+		scalar,
+		columns: 1,
+		rows: 1,
+		column_stride: 0,
+		row_stride: 0,
+	};
+
+	let inst = find_type(module, id)?;
+	match (inst.class.opcode, inst.operands.as_slice()) {
+		(Op::TypeFloat, [Operand::LiteralInt32(32)]) => Some(scalar(ScalarType::F32)),
+		(Op::TypeInt, [Operand::LiteralInt32(32), Operand::LiteralInt32(0)]) => Some(scalar(ScalarType::U32)),
+		(Op::TypeInt, [Operand::LiteralInt32(32), Operand::LiteralInt32(1)]) => Some(scalar(ScalarType::I32)),
+		(Op::TypeVector, [Operand::IdRef(component), Operand::LiteralInt32(count)]) => {
+			let component = reflect_constant_type(module, *component, None)?;
+			Some(ConstantType {
+				rows: *count,
+				row_stride: 4,
+				..component
+			})
+		}
+		(Op::TypeMatrix, [Operand::IdRef(column), Operand::LiteralInt32(count)]) => {
+			let column = reflect_constant_type(module, *column, None)?;
+			let matrix = matrix?;
+			Some(if matrix.row_major {
+				// Every row is a vector of its own, so the components of a column are a row stride apart.
+				ConstantType {
+					columns: *count,
+					column_stride: 4,
+					row_stride: matrix.stride,
+					..column
+				}
+			} else {
+				ConstantType {
+					columns: *count,
+					column_stride: matrix.stride,
+					..column
+				}
+			})
+		}
+		_ => None,
+	}
+}
+
+// The number of bytes from the start of a struct to the end of its last member.
+fn struct_size(module: &Module, id: u32) -> Option<u32> {
+	let mut size = 0;
+	for (i, operand) in find_type(module, id)?.operands.iter().enumerate() {
+		let Operand::IdRef(member_type) = operand else {
+			return None;
+		};
+
+		let offset = match member_decoration(module, id, i as u32, Decoration::Offset)? {
+			[Operand::LiteralInt32(offset)] => *offset,
+			_ => return None,
+		};
+
+		size = size.max(offset + type_size(module, *member_type, matrix_layout(module, id, i as u32))?);
+	}
+
+	Some(size)
+}
+
+// The number of bytes from the start of a value of type `id` to the end of its last byte, padding after the last byte isn't included.
+// `matrix` is the layout of the member that the type belongs to, which also applies to arrays of matrices.
+fn type_size(module: &Module, id: u32, matrix: Option<MatrixLayout>) -> Option<u32> {
+	let inst = find_type(module, id)?;
+	match (inst.class.opcode, inst.operands.as_slice()) {
+		(Op::TypeInt | Op::TypeFloat, [Operand::LiteralInt32(width), ..]) => Some(width / 8),
+		(Op::TypeVector, [Operand::IdRef(component), Operand::LiteralInt32(count)]) => Some(type_size(module, *component, None)? * count),
+		(Op::TypeMatrix, [Operand::IdRef(column), Operand::LiteralInt32(columns)]) => {
+			let matrix = matrix?;
+			let (component, rows) = match find_type(module, *column)?.operands.as_slice() {
+				[Operand::IdRef(component), Operand::LiteralInt32(rows)] => (*component, *rows),
+				_ => return None,
+			};
+
+			let component_size = type_size(module, component, None)?;
+			Some(if matrix.row_major {
+				(rows - 1) * matrix.stride + columns * component_size
+			} else {
+				(columns - 1) * matrix.stride + rows * component_size
+			})
+		}
+		(Op::TypeArray, [Operand::IdRef(element), Operand::IdRef(length)]) => {
+			let length = match find_type(module, *length)?.operands.as_slice() {
+				[Operand::LiteralInt32(length)] if *length > 0 => *length,
+				_ => return None,
+			};
+
+			let stride = module.annotations.iter().find_map(|inst| match inst.operands.as_slice() {
+				[Operand::IdRef(target), Operand::Decoration(Decoration::ArrayStride), Operand::LiteralInt32(stride)] if *target == id => Some(*stride),
+				_ => None,
+			})?;
+
+			Some((length - 1) * stride + type_size(module, *element, matrix)?)
+		}
+		(Op::TypeStruct, _) => struct_size(module, id),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rspirv::spirv::StorageClass;
+	use serde_json::json;
+
+	const FLOAT: u32 = 1;
+	const FLOAT4: u32 = 2;
+	const FLOAT4X4: u32 = 3;
+	const UINT: u32 = 4;
+	const THREE: u32 = 5;
+	const FLOAT4_ARRAY: u32 = 6;
+	const BLOCK: u32 = 7;
+	const BLOCK_POINTER: u32 = 8;
+	const VARIABLE: u32 = 9;
+
+	fn instruction(opcode: Op, result_type: Option<u32>, result_id: u32, operands: Vec<Operand>) -> Instruction {
+		Instruction::new(opcode, result_type, Some(result_id), operands)
+	}
+
+	fn decorate(target: u32, decoration: Decoration, value: u32) -> Instruction {
+		Instruction::new(Op::Decorate, None, None, vec![Operand::IdRef(target), Operand::Decoration(decoration), Operand::LiteralInt32(value)])
+	}
+
+	fn member_decorate(member: u32, decoration: Decoration, value: Option<u32>) -> Instruction {
+		let operands = [Operand::IdRef(BLOCK), Operand::LiteralInt32(member), Operand::Decoration(decoration)]
+			.into_iter()
+			.chain(value.map(Operand::LiteralInt32));
+		Instruction::new(Op::MemberDecorate, None, None, operands.collect())
+	}
+
+	fn member_name(member: u32, name: &str) -> Instruction {
+		Instruction::new(
+			Op::MemberName,
+			None,
+			None,
+			vec![Operand::IdRef(BLOCK), Operand::LiteralInt32(member), Operand::LiteralString(name.to_owned())],
+		)
+	}
+
+	// Only holds what cbuffer reflection looks at, for a cbuffer at binding 0 of set 1 with a row major and a column major float4x4,
+	// a float4[3] and a float.
+	fn cbuffer_module() -> Module {
+		let mut module = Module::new();
+		module.types_global_values = vec![
+			instruction(Op::TypeFloat, None, FLOAT, vec![Operand::LiteralInt32(32)]),
+			instruction(Op::TypeVector, None, FLOAT4, vec![Operand::IdRef(FLOAT), Operand::LiteralInt32(4)]),
+			instruction(Op::TypeMatrix, None, FLOAT4X4, vec![Operand::IdRef(FLOAT4), Operand::LiteralInt32(4)]),
+			instruction(Op::TypeInt, None, UINT, vec![Operand::LiteralInt32(32), Operand::LiteralInt32(0)]),
+			instruction(Op::Constant, Some(UINT), THREE, vec![Operand::LiteralInt32(3)]),
+			instruction(Op::TypeArray, None, FLOAT4_ARRAY, vec![Operand::IdRef(FLOAT4), Operand::IdRef(THREE)]),
+			instruction(
+				Op::TypeStruct,
+				None,
+				BLOCK,
+				vec![Operand::IdRef(FLOAT4X4), Operand::IdRef(FLOAT4X4), Operand::IdRef(FLOAT4_ARRAY), Operand::IdRef(FLOAT)],
+			),
+			instruction(Op::TypePointer, None, BLOCK_POINTER, vec![Operand::StorageClass(StorageClass::Uniform), Operand::IdRef(BLOCK)]),
+			instruction(Op::Variable, Some(BLOCK_POINTER), VARIABLE, vec![Operand::StorageClass(StorageClass::Uniform)]),
+		];
+		module.annotations = vec![
+			decorate(FLOAT4_ARRAY, Decoration::ArrayStride, 16),
+			member_decorate(0, Decoration::Offset, Some(0)),
+			member_decorate(0, Decoration::MatrixStride, Some(16)),
+			member_decorate(0, Decoration::RowMajor, None),
+			member_decorate(1, Decoration::Offset, Some(64)),
+			member_decorate(1, Decoration::MatrixStride, Some(16)),
+			member_decorate(1, Decoration::ColMajor, None),
+			member_decorate(2, Decoration::Offset, Some(128)),
+			member_decorate(3, Decoration::Offset, Some(176)),
+			decorate(VARIABLE, Decoration::DescriptorSet, 1),
+			decorate(VARIABLE, Decoration::Binding, 0),
+		];
+		module.debug_names = vec![
+			member_name(0, "row_major_matrix"),
+			member_name(1, "column_major_matrix"),
+			member_name(2, "colors"),
+			member_name(3, "roughness"),
+		];
+		module
+	}
+
+	fn matrix_type(column_stride: u32, row_stride: u32) -> ConstantType {
+		ConstantType {
+			scalar: ScalarType::F32,
+			columns: 4,
+			rows: 4,
+			column_stride,
+			row_stride,
+		}
+	}
+
+	#[test]
+	fn cbuffer_members_are_reflected_with_their_layout() {
+		let module = cbuffer_module();
+		let (members, size) = reflect_cbuffer(&module, 1, 0).unwrap();
+
+		let reflected = members.iter().map(|member| (member.name.as_str(), member.offset, member.ty)).collect::<Vec<_>>();
+		assert_eq!(
+			reflected,
+			[
+				("row_major_matrix", 0, Some(matrix_type(4, 16))),
+				("column_major_matrix", 64, Some(matrix_type(16, 4))),
+				// Arrays can't be set by materials, but they still take up space.
+				("colors", 128, None),
+				(
+					"roughness",
+					176,
+					Some(ConstantType {
+						scalar: ScalarType::F32,
+						columns: 1,
+						rows: 1,
+						column_stride: 0,
+						row_stride: 0
+					})
+				),
+			]
+		);
+
+		// The last member ends at 180 bytes, which is rounded up to a whole number of float4s.
+		assert_eq!(size, 192);
+		assert!(reflect_cbuffer(&module, 0, 0).is_none());
+	}
+
+	#[test]
+	fn array_and_matrix_sizes_leave_out_trailing_padding() {
+		let module = cbuffer_module();
+		assert_eq!(type_size(&module, FLOAT4_ARRAY, None), Some(48));
+		assert_eq!(type_size(&module, FLOAT4X4, matrix_layout(&module, BLOCK, 0)), Some(64));
+		// Matrices can't be laid out without the decorations of their member.
+		assert_eq!(type_size(&module, FLOAT4X4, None), None);
+		assert_eq!(struct_size(&module, BLOCK), Some(180));
+	}
+
+	#[test]
+	fn matrices_are_written_in_the_majorness_of_their_member() {
+		let values = json!((0..16).collect::<Vec<_>>());
+		let bytes = constant_bytes(&values, ScalarType::U32, 16).unwrap();
+
+		let mut data = vec![0u8; 64];
+		write_constant(&mut data, 0, matrix_type(16, 4), &bytes);
+		assert_eq!(data, bytes);
+
+		// Row major matrices store the first row, made of the first value of every column, first.
+		let mut data = vec![0u8; 64];
+		write_constant(&mut data, 0, matrix_type(4, 16), &bytes);
+		let words = data.chunks_exact(4).map(|bytes| u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])).collect::<Vec<_>>();
+		assert_eq!(words[..8], [0, 4, 8, 12, 1, 5, 9, 13]);
+	}
+
+	#[test]
+	fn constants_need_exactly_one_value_per_component() {
+		assert_eq!(constant_bytes(&json!(0.5), ScalarType::F32, 1), Some(0.5f32.to_ne_bytes().to_vec()));
+		assert_eq!(constant_bytes(&json!([1, -2]), ScalarType::I32, 2), Some([1i32.to_ne_bytes(), (-2i32).to_ne_bytes()].concat()));
+
+		// Too many values are as much of a mistake as too few.
+		assert_eq!(constant_bytes(&json!([1.0, 2.0, 3.0, 4.0, 5.0]), ScalarType::F32, 4), None);
+		assert_eq!(constant_bytes(&json!([1.0, 2.0, 3.0]), ScalarType::F32, 4), None);
+		assert_eq!(constant_bytes(&json!([-1]), ScalarType::U32, 1), None);
+		assert_eq!(constant_bytes(&json!("red"), ScalarType::F32, 1), None);
+	}
+}
@@ -1,9 +1,157 @@
 use super::EditorError;
-use glam::{vec2, vec3};
-use goldfish::{package::MeshPackage, renderer::Vertex};
+use glam::{vec2, vec3, Mat4, Vec2, Vec3};
+use goldfish::{
+	package::{MaterialSlot, MeshPackage, SceneMesh, SceneNode, ScenePackage, SceneSkin, TexturePackage},
+	renderer::{MeshIndices, Vertex},
+};
+use russimp::material::PropertyTypeInfo;
+use russimp::node::Node;
 use russimp::scene::{PostProcess, Scene};
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+use thiserror::Error;
+use uuid::Uuid;
 
-pub fn import_mesh(data: &[u8], extension: &str) -> Result<Vec<MeshPackage>, EditorError> {
+// Problems with a mesh that the importer worked around, the mesh is still usable but probably not what was intended.
+#[derive(Error, Debug)]
+pub enum MeshImportWarning {
+	#[error("Mesh {0} has no normals, smooth normals were generated")]
+	GeneratedNormals(String),
+	#[error("Mesh {0} has no tangents, they were generated with MikkTSpace")]
+	GeneratedTangents(String),
+	#[error("Mesh {0} has no UVs, its tangents are arbitrary")]
+	MissingUvs(String),
+	#[error("Mesh {mesh} is made of {primitive} instead of triangles and was skipped")]
+	NonTrianglePrimitives { mesh: String, primitive: &'static str },
+	#[error("MikkTSpace failed to generate tangents for mesh {0}, its tangents are arbitrary")]
+	TangentGenerationFailed(String),
+}
+
+// What one of the uuids of a model holds, recorded in the meta file so that uuids stay with their outputs when meshes or textures are
+// added to or removed from the model.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum ModelOutput {
+	// Index into `ImportedModel::meshes`.
+	Mesh(usize),
+	// Index into `ImportedModel::textures`.
+	Texture(usize),
+	Scene,
+}
+
+pub struct ImportedNode {
+	pub name: String,
+	pub parent: Option<usize>,
+	pub transform: Mat4,
+	// Indices into `ImportedModel::meshes` and the material slot of each mesh.
+	pub meshes: Vec<(usize, u32)>,
+	// Index into `ImportedModel::skins`.
+	pub skin: Option<usize>,
+}
+
+// A material slot whose textures are still indices into `ImportedModel::textures`.
+pub struct ImportedMaterial {
+	pub slot: MaterialSlot,
+	pub base_color_texture: Option<usize>,
+	pub metallic_roughness_texture: Option<usize>,
+	pub normal_texture: Option<usize>,
+	pub occlusion_texture: Option<usize>,
+	pub emissive_texture: Option<usize>,
+}
+
+impl ImportedMaterial {
+	pub fn untextured(slot: MaterialSlot) -> Self {
+		Self {
+			slot,
+			base_color_texture: None,
+			metallic_roughness_texture: None,
+			normal_texture: None,
+			occlusion_texture: None,
+			emissive_texture: None,
+		}
+	}
+}
+
+pub struct ImportedModel {
+	// A slot for every mesh of the source file, so that skipping a mesh doesn't move the meshes after it onto other uuids.
+	pub meshes: Vec<Option<MeshPackage>>,
+	pub textures: Vec<TexturePackage>,
+	pub warnings: Vec<MeshImportWarning>,
+	pub nodes: Vec<ImportedNode>,
+	pub materials: Vec<ImportedMaterial>,
+	pub skins: Vec<SceneSkin>,
+}
+
+impl ImportedModel {
+	// Models get a uuid for every mesh slot, every texture and the scene that places the meshes.
+	pub fn outputs(&self) -> Vec<ModelOutput> {
+		(0..self.meshes.len())
+			.map(ModelOutput::Mesh)
+			.chain((0..self.textures.len()).map(ModelOutput::Texture))
+			.chain([ModelOutput::Scene])
+			.collect()
+	}
+
+	// `outputs` and `uuids` are the outputs and uuids recorded in the meta file. Skipped meshes keep their uuid but don't have an output.
+	pub fn serialize_output(&self, output: ModelOutput, outputs: &[ModelOutput], uuids: &[Uuid]) -> Result<Option<Vec<u8>>, EditorError> {
+		let serialized = match output {
+			ModelOutput::Mesh(mesh) => match self.meshes.get(mesh) {
+				Some(Some(mesh_package)) => bincode::serialize(mesh_package),
+				_ => return Ok(None),
+			},
+			ModelOutput::Texture(texture) => match self.textures.get(texture) {
+				Some(texture_package) => bincode::serialize(texture_package),
+				None => return Ok(None),
+			},
+			ModelOutput::Scene => bincode::serialize(&self.scene_package(outputs, uuids)),
+		};
+
+		serialized.map(Some).map_err(move |_| EditorError::Serialize)
+	}
+
+	// The uuids of the meshes and textures are only known once the meta file exists, so the scene is resolved separately.
+	fn scene_package(&self, outputs: &[ModelOutput], uuids: &[Uuid]) -> ScenePackage {
+		let uuid = |output: ModelOutput| outputs.iter().position(|&o| o == output).and_then(|i| uuids.get(i).copied());
+		let texture_uuid = |texture: Option<usize>| texture.and_then(|texture| uuid(ModelOutput::Texture(texture)));
+
+		ScenePackage {
+			nodes: self
+				.nodes
+				.iter()
+				.map(|node| {
+					let (scale, rotation, translation) = node.transform.to_scale_rotation_translation();
+					SceneNode {
+						name: node.name.clone(),
+						parent: node.parent,
+						translation,
+						rotation: rotation.to_array(),
+						scale,
+						meshes: node
+							.meshes
+							.iter()
+							.filter_map(|&(mesh, material_slot)| uuid(ModelOutput::Mesh(mesh)).map(|mesh| SceneMesh { mesh, material_slot }))
+							.collect(),
+						skin: node.skin,
+					}
+				})
+				.collect(),
+			material_slots: self
+				.materials
+				.iter()
+				.map(|material| MaterialSlot {
+					base_color_texture: texture_uuid(material.base_color_texture),
+					metallic_roughness_texture: texture_uuid(material.metallic_roughness_texture),
+					normal_texture: texture_uuid(material.normal_texture),
+					occlusion_texture: texture_uuid(material.occlusion_texture),
+					emissive_texture: texture_uuid(material.emissive_texture),
+					..material.slot.clone()
+				})
+				.collect(),
+			skins: self.skins.clone(),
+		}
+	}
+}
+
+pub fn import_mesh(data: &[u8], extension: &str) -> Result<ImportedModel, EditorError> {
 	let scene = Scene::from_buffer(
 		data,
 		vec![
@@ -16,30 +164,241 @@ pub fn import_mesh(data: &[u8], extension: &str) -> Result<Vec<MeshPackage>, Edi
 		extension,
 	)
 	.map_err(move |err| EditorError::MeshImport(err))?;
-	Ok(scene
-		.meshes
-		.iter()
-		.map(|mesh| {
-			let vertices = (0..mesh.vertices.len())
-				.map(|i| Vertex {
-					position: vec3(mesh.vertices[i].x, mesh.vertices[i].y, mesh.vertices[i].z),
-					normal: vec3(mesh.normals[i].x, mesh.normals[i].y, mesh.normals[i].z),
-					tangent: vec3(mesh.tangents[i].x, mesh.tangents[i].y, mesh.tangents[i].z),
-					uv: if let Some(ref uv) = mesh.texture_coords[0] { vec2(uv[i].x, uv[i].y) } else { vec2(0.0, 0.0) },
-					bitangent: vec3(mesh.bitangents[i].x, mesh.bitangents[i].y, mesh.bitangents[i].z),
-				})
-				.collect::<Vec<_>>();
 
-			let indices = mesh
-				.faces
+	let mut warnings = Vec::new();
+	let mut packages = Vec::new();
+	for mesh in scene.meshes.iter() {
+		// NOTE: Vertices are indexed with at most 32 bits, anything bigger would have to be split up into several meshes.
+		if mesh.vertices.len() > u32::MAX as usize + 1 {
+			return Err(EditorError::MeshTooLarge {
+				mesh: mesh.name.clone(),
+				vertices: mesh.vertices.len(),
+			});
+		}
+
+		// Triangulating leaves points and lines alone, and sorting by primitive type puts those into meshes of their own.
+		if let Some(face) = mesh.faces.iter().find(|face| face.0.len() != 3) {
+			warnings.push(MeshImportWarning::NonTrianglePrimitives {
+				mesh: mesh.name.clone(),
+				primitive: match face.0.len() {
+					1 => "points",
+					2 => "lines",
+					_ => "polygons",
+				},
+			});
+			packages.push(None);
+			continue;
+		}
+
+		let indices = mesh.faces.iter().flat_map(|face| [face.0[0], face.0[1], face.0[2]]).collect::<Vec<u32>>();
+
+		let positions = mesh.vertices.iter().map(|v| vec3(v.x, v.y, v.z)).collect::<Vec<_>>();
+		let uvs = match mesh.texture_coords.first().and_then(|uvs| uvs.as_ref()) {
+			Some(uvs) => Some(uvs.iter().map(|uv| vec2(uv.x, uv.y)).collect::<Vec<_>>()),
+			None => None,
+		};
+
+		let normals = if mesh.normals.len() == positions.len() {
+			Some(mesh.normals.iter().map(|n| vec3(n.x, n.y, n.z)).collect::<Vec<_>>())
+		} else {
+			None
+		};
+
+		let tangents = if mesh.tangents.len() == positions.len() && mesh.bitangents.len() == positions.len() {
+			Some((
+				mesh.tangents.iter().map(|t| vec3(t.x, t.y, t.z)).collect::<Vec<_>>(),
+				mesh.bitangents.iter().map(|b| vec3(b.x, b.y, b.z)).collect::<Vec<_>>(),
+			))
+		} else {
+			None
+		};
+
+		let vertices = build_vertices(&mesh.name, &positions, normals, tangents, uvs, &indices, &mut warnings);
+
+		packages.push(Some(MeshPackage {
+			indices: MeshIndices::new(indices, vertices.len()),
+			vertices,
+			joints: Vec::new(),
+		}));
+	}
+
+	let mut nodes = Vec::new();
+	if let Some(root) = &scene.root {
+		flatten_nodes(root, None, &scene, &packages, &mut nodes);
+	}
+
+	// NOTE: Only the names of the materials are imported, their parameters and textures are left at their defaults.
+	let materials = scene
+		.materials
+		.iter()
+		.enumerate()
+		.map(|(i, material)| {
+			let name = material
+				.properties
 				.iter()
-				.flat_map(|face| {
-					assert_eq!(face.0.len(), 3, "Invalid number of indices!");
-					[face.0[0] as u16, face.0[1] as u16, face.0[2] as u16]
+				.find_map(|property| match &property.data {
+					PropertyTypeInfo::String(name) if property.key == "?mat.name" => Some(name.clone()),
+					_ => None,
 				})
-				.collect::<Vec<u16>>();
+				.unwrap_or_else(|| format!("Material {}", i));
+
+			ImportedMaterial::untextured(MaterialSlot::new(name))
+		})
+		.collect();
+
+	Ok(ImportedModel {
+		meshes: packages,
+		textures: Vec::new(),
+		warnings,
+		nodes,
+		materials,
+		skins: Vec::new(),
+	})
+}
+
+// Builds the vertices of a triangle list, generating whatever attributes the source file didn't have.
+// `tangents` holds the tangents and bitangents.
+pub fn build_vertices(
+	name: &str,
+	positions: &[Vec3],
+	normals: Option<Vec<Vec3>>,
+	tangents: Option<(Vec<Vec3>, Vec<Vec3>)>,
+	uvs: Option<Vec<Vec2>>,
+	indices: &[u32],
+	warnings: &mut Vec<MeshImportWarning>,
+) -> Vec<Vertex> {
+	let normals = normals.unwrap_or_else(|| {
+		warnings.push(MeshImportWarning::GeneratedNormals(name.to_owned()));
+		generate_normals(positions, indices)
+	});
 
-			MeshPackage { vertices, indices }
+	let (tangents, bitangents) = if let Some(tangents) = tangents {
+		tangents
+	} else if let Some(uvs) = &uvs {
+		match generate_tangents(positions, &normals, uvs, indices) {
+			Some(tangents) => {
+				warnings.push(MeshImportWarning::GeneratedTangents(name.to_owned()));
+				tangents
+			}
+			None => {
+				warnings.push(MeshImportWarning::TangentGenerationFailed(name.to_owned()));
+				normals.iter().map(|normal| normal.any_orthonormal_pair()).unzip()
+			}
+		}
+	} else {
+		warnings.push(MeshImportWarning::MissingUvs(name.to_owned()));
+		normals.iter().map(|normal| normal.any_orthonormal_pair()).unzip()
+	};
+
+	(0..positions.len())
+		.map(|i| Vertex {
+			position: positions[i],
+			normal: normals[i],
+			tangent: tangents[i],
+			uv: uvs.as_ref().map_or(Vec2::ZERO, |uvs| uvs[i]),
+			bitangent: bitangents[i],
 		})
-		.collect::<Vec<_>>())
+		.collect()
+}
+
+// Appends `node` and everything below it to `nodes`, parents always end up before their children.
+fn flatten_nodes(node: &Rc<Node>, parent: Option<usize>, scene: &Scene, packages: &[Option<MeshPackage>], nodes: &mut Vec<ImportedNode>) {
+	let t = &node.transformation;
+	// Assimp matrices are row major.
+	let transform = Mat4::from_cols_array(&[t.a1, t.b1, t.c1, t.d1, t.a2, t.b2, t.c2, t.d2, t.a3, t.b3, t.c3, t.d3, t.a4, t.b4, t.c4, t.d4]);
+
+	let index = nodes.len();
+	nodes.push(ImportedNode {
+		name: node.name.clone(),
+		parent,
+		transform,
+		meshes: node
+			.meshes
+			.iter()
+			.filter(|&&mesh| packages[mesh as usize].is_some())
+			.map(|&mesh| (mesh as usize, scene.meshes[mesh as usize].material_index))
+			.collect(),
+		skin: None,
+	});
+
+	for child in node.children.borrow().iter() {
+		flatten_nodes(child, Some(index), scene, packages, nodes);
+	}
+}
+
+// Every vertex gets the average normal of the triangles around it, weighted by their area.
+fn generate_normals(positions: &[Vec3], indices: &[u32]) -> Vec<Vec3> {
+	let mut normals = vec![Vec3::ZERO; positions.len()];
+	for triangle in indices.chunks_exact(3) {
+		let [a, b, c] = [triangle[0] as usize, triangle[1] as usize, triangle[2] as usize];
+		let normal = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+		normals[a] += normal;
+		normals[b] += normal;
+		normals[c] += normal;
+	}
+
+	normals.into_iter().map(|normal| normal.try_normalize().unwrap_or(Vec3::Y)).collect()
+}
+
+struct MikkTSpaceGeometry<'a> {
+	positions: &'a [Vec3],
+	normals: &'a [Vec3],
+	uvs: &'a [Vec2],
+	indices: &'a [u32],
+	tangents: Vec<Vec3>,
+	bitangents: Vec<Vec3>,
+}
+
+impl MikkTSpaceGeometry<'_> {
+	fn index(&self, face: usize, vert: usize) -> usize {
+		self.indices[face * 3 + vert] as usize
+	}
+}
+
+impl mikktspace::Geometry for MikkTSpaceGeometry<'_> {
+	fn num_faces(&self) -> usize {
+		self.indices.len() / 3
+	}
+
+	fn num_vertices_of_face(&self, _face: usize) -> usize {
+		3
+	}
+
+	fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+		self.positions[self.index(face, vert)].to_array()
+	}
+
+	fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+		self.normals[self.index(face, vert)].to_array()
+	}
+
+	fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+		self.uvs[self.index(face, vert)].to_array()
+	}
+
+	// NOTE: MikkTSpace works on unindexed triangles, so a vertex that is shared by triangles with different tangents ends up with whichever was written last.
+	fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+		let index = self.index(face, vert);
+		let [x, y, z, sign] = tangent;
+		self.tangents[index] = vec3(x, y, z);
+		self.bitangents[index] = self.normals[index].cross(self.tangents[index]) * sign;
+	}
+}
+
+// Returns the tangents and bitangents, or nothing if MikkTSpace couldn't make sense of the mesh.
+fn generate_tangents(positions: &[Vec3], normals: &[Vec3], uvs: &[Vec2], indices: &[u32]) -> Option<(Vec<Vec3>, Vec<Vec3>)> {
+	let mut geometry = MikkTSpaceGeometry {
+		positions,
+		normals,
+		uvs,
+		indices,
+		tangents: vec![Vec3::X; positions.len()],
+		bitangents: vec![Vec3::Z; positions.len()],
+	};
+
+	if !mikktspace::generate_tangents(&mut geometry) {
+		return None;
+	}
+
+	Some((geometry.tangents, geometry.bitangents))
 }
@@ -0,0 +1,152 @@
+use super::asset::{TextureAsset, TextureCompression};
+use super::EditorError;
+use goldfish::package::TexturePackage;
+use goldfish::renderer::TextureFormat;
+use image::imageops::FilterType;
+use image::{DynamicImage, RgbaImage};
+use intel_tex_2::{bc1, bc3, bc4, bc5, bc7, RSurface, RgSurface, RgbaSurface};
+
+pub fn import_texture(data: &[u8], asset: &TextureAsset) -> Result<TexturePackage, EditorError> {
+	let image = image::load_from_memory(data).map_err(move |err| EditorError::TextureImport(err))?;
+	import_image(&image, asset)
+}
+
+// Same as `import_texture` for images that were already decoded, like the ones embedded in models.
+pub fn import_image(image: &DynamicImage, asset: &TextureAsset) -> Result<TexturePackage, EditorError> {
+	let (width, height) = (image.width(), image.height());
+
+	// NOTE: sRGB only applies to color textures, normal maps and single channel textures always hold linear data.
+	let format = match asset.compression {
+		TextureCompression::None => match (asset.format, asset.srgb) {
+			(TextureFormat::RGBA8UNorm, true) => TextureFormat::SRGBA8,
+			(TextureFormat::R8UNorm | TextureFormat::RG8UNorm | TextureFormat::RGBA8UNorm | TextureFormat::RGBA16UNorm | TextureFormat::SRGBA8, false) => asset.format,
+			(format, _) => return Err(EditorError::UnsupportedTextureFormat(format)),
+		},
+		TextureCompression::Color => match (has_transparency(image), asset.srgb) {
+			(false, false) => TextureFormat::BC1RGBAUNorm,
+			(false, true) => TextureFormat::BC1SRGBA,
+			(true, false) => TextureFormat::BC3RGBAUNorm,
+			(true, true) => TextureFormat::BC3SRGBA,
+		},
+		TextureCompression::ColorHighQuality if asset.srgb => TextureFormat::BC7SRGBA,
+		TextureCompression::ColorHighQuality => TextureFormat::BC7RGBAUNorm,
+		TextureCompression::NormalMap => TextureFormat::BC5RGUNorm,
+		TextureCompression::SingleChannel => TextureFormat::BC4RUNorm,
+	};
+
+	let mip_levels = if asset.generate_mips { 32 - width.max(height).leading_zeros() } else { 1 };
+
+	// sRGB mips have to be filtered in linear space, otherwise they come out darker than the texture they were made from.
+	let srgb = matches!(format, TextureFormat::SRGBA8 | TextureFormat::BC1SRGBA | TextureFormat::BC3SRGBA | TextureFormat::BC7SRGBA);
+	let linear_image = srgb.then(|| srgb_to_linear(image));
+	let filtered_image = linear_image.as_ref().unwrap_or(image);
+
+	let mut data = texel_data(image, format);
+	for mip in 1..mip_levels {
+		let mip_image = filtered_image.resize_exact((width >> mip).max(1), (height >> mip).max(1), FilterType::Triangle);
+		let mip_image = if srgb { linear_to_srgb(&mip_image) } else { mip_image };
+		data.extend_from_slice(&texel_data(&mip_image, format));
+	}
+
+	Ok(TexturePackage {
+		width,
+		height,
+		format,
+		mip_levels,
+		data,
+	})
+}
+
+fn has_transparency(image: &DynamicImage) -> bool {
+	image.color().has_alpha() && image.to_rgba8().pixels().any(|pixel| pixel.0[3] < u8::MAX)
+}
+
+// Alpha is always linear, only the color channels are converted.
+fn srgb_to_linear(image: &DynamicImage) -> DynamicImage {
+	let mut rgba = image.to_rgba32f();
+	for pixel in rgba.pixels_mut() {
+		for channel in pixel.0.iter_mut().take(3) {
+			*channel = if *channel <= 0.04045 { *channel / 12.92 } else { ((*channel + 0.055) / 1.055).powf(2.4) };
+		}
+	}
+	DynamicImage::ImageRgba32F(rgba)
+}
+
+fn linear_to_srgb(image: &DynamicImage) -> DynamicImage {
+	let rgba = image.to_rgba32f();
+	let quantize = |value: f32| (value.clamp(0.0, 1.0) * u8::MAX as f32).round() as u8;
+	let encode = |value: f32| if value <= 0.0031308 { value * 12.92 } else { 1.055 * value.powf(1.0 / 2.4) - 0.055 };
+
+	DynamicImage::ImageRgba8(RgbaImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+		let [r, g, b, a] = rgba.get_pixel(x, y).0;
+		image::Rgba([quantize(encode(r)), quantize(encode(g)), quantize(encode(b)), quantize(a)])
+	}))
+}
+
+fn texel_data(image: &DynamicImage, format: TextureFormat) -> Vec<u8> {
+	match format {
+		TextureFormat::R8UNorm => image.to_luma8().into_raw(),
+		// Luma alpha would pack the luminance of the texel with its alpha, not the red and green channels.
+		TextureFormat::RG8UNorm => image.to_rgba8().chunks_exact(4).flat_map(|pixel| [pixel[0], pixel[1]]).collect(),
+		TextureFormat::RGBA8UNorm | TextureFormat::SRGBA8 => image.to_rgba8().into_raw(),
+		TextureFormat::RGBA16UNorm => bytemuck::cast_slice(&image.to_rgba16().into_raw()).to_vec(),
+		_ if format.is_compressed() => compress_blocks(image, format),
+		_ => unreachable!("Unsupported texture import format {:?}!", format),
+	}
+}
+
+fn compress_blocks(image: &DynamicImage, format: TextureFormat) -> Vec<u8> {
+	let rgba = image.to_rgba8();
+	let (width, height) = rgba.dimensions();
+
+	// The encoders only work on whole blocks, so the last row and column of texels get repeated to fill up the edge blocks.
+	let (block_width, block_height) = ((width + 3) / 4 * 4, (height + 3) / 4 * 4);
+	let padded = RgbaImage::from_fn(block_width, block_height, |x, y| *rgba.get_pixel(x.min(width - 1), y.min(height - 1)));
+	let channels = |count: usize| padded.pixels().flat_map(|pixel| pixel.0.into_iter().take(count)).collect::<Vec<u8>>();
+
+	let surface = RgbaSurface {
+		data: padded.as_raw(),
+		width: block_width,
+		height: block_height,
+		stride: block_width * 4,
+	};
+
+	match format {
+		TextureFormat::BC1RGBAUNorm | TextureFormat::BC1SRGBA => bc1::compress_blocks(&surface),
+		TextureFormat::BC3RGBAUNorm | TextureFormat::BC3SRGBA => bc3::compress_blocks(&surface),
+		TextureFormat::BC7RGBAUNorm | TextureFormat::BC7SRGBA => bc7::compress_blocks(&bc7::alpha_basic_settings(), &surface),
+		TextureFormat::BC4RUNorm => bc4::compress_blocks(&RSurface {
+			data: &channels(1),
+			width: block_width,
+			height: block_height,
+			stride: block_width,
+		}),
+		TextureFormat::BC5RGUNorm => bc5::compress_blocks(&RgSurface {
+			data: &channels(2),
+			width: block_width,
+			height: block_height,
+			stride: block_width * 2,
+		}),
+		_ => unreachable!("{:?} is not a block compressed format!", format),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn rg8_keeps_the_red_and_green_channels() {
+		let image = DynamicImage::ImageRgba8(RgbaImage::from_raw(2, 1, vec![10, 20, 30, 40, 50, 60, 70, 80]).unwrap());
+		let asset = TextureAsset {
+			format: TextureFormat::RG8UNorm,
+			generate_mips: false,
+			..Default::default()
+		};
+
+		let package = import_image(&image, &asset).unwrap();
+		assert_eq!(package.format, TextureFormat::RG8UNorm);
+		assert_eq!((package.width, package.height, package.mip_levels), (2, 1, 1));
+		assert_eq!(package.data, vec![10, 20, 50, 60]);
+	}
+}
@@ -1,13 +1,161 @@
-use crate::GoldfishEngine;
+use crate::{GoldfishEngine, Size};
+use std::any::Any;
 use std::time::Duration;
+use thiserror::Error;
+
+include!(concat!(env!("OUT_DIR"), "/build_hash.rs"));
+
+// Bumped whenever `GameLibVersion` or `GameLib` change.
+pub const GAME_LIB_ABI_VERSION: u32 = 1;
+
+// How often `GameLib::on_fixed_update` gets called.
+pub const FIXED_UPDATE_DT: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+pub const GAME_LIB_VERSION_SYMBOL: &'static [u8] = b"_goldfish_game_lib_version";
+pub const CREATE_GAME_LIB_SYMBOL: &'static [u8] = b"_goldfish_create_game_lib";
+
+// The layout of this can never change, since it is what tells the editor whether the rest of the game library can be used.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameLibVersion {
+	pub abi_version: u32,
+	pub engine_build_hash: u64,
+}
+
+impl GameLibVersion {
+	pub const CURRENT: Self = Self {
+		abi_version: GAME_LIB_ABI_VERSION,
+		engine_build_hash: ENGINE_BUILD_HASH,
+	};
+}
 
 #[repr(C)]
 pub struct GameLib {
 	pub on_load: extern "C" fn(&mut GoldfishEngine),
 	pub on_unload: extern "C" fn(&mut GoldfishEngine),
 	pub on_update: extern "C" fn(&mut GoldfishEngine),
-	// setup: fn(),
-	// destroy: fn(),
+	// Called every `FIXED_UPDATE_DT`, before `on_update` of the same frame.
+	pub on_fixed_update: Option<extern "C" fn(&mut GoldfishEngine)>,
+	pub on_resize: Option<extern "C" fn(&mut GoldfishEngine, Size)>,
+	// Called before `on_unload` when the library is about to be reloaded, the game writes what it wants to keep into `GoldfishEngine::saved_game_state`.
+	pub on_save_state: Option<extern "C" fn(&mut GoldfishEngine)>,
+	// Called after `on_load` of the reloaded library if the old library saved any state.
+	pub on_restore_state: Option<extern "C" fn(&mut GoldfishEngine)>,
+	// Called every frame after `on_update` when running in the editor with a window, for tooling that only exists while editing.
+	pub on_editor_ui: Option<extern "C" fn(&mut GoldfishEngine)>,
+}
+
+impl GameLib {
+	// A game library without any of the optional callbacks, which are added with struct update syntax.
+	pub const fn new(on_load: extern "C" fn(&mut GoldfishEngine), on_unload: extern "C" fn(&mut GoldfishEngine), on_update: extern "C" fn(&mut GoldfishEngine)) -> Self {
+		Self {
+			on_load,
+			on_unload,
+			on_update,
+			on_fixed_update: None,
+			on_resize: None,
+			on_save_state: None,
+			on_restore_state: None,
+			on_editor_ui: None,
+		}
+	}
+}
+
+pub type GameLibVersionApi = unsafe extern "C" fn() -> GameLibVersion;
+pub type CreateGamelibApi = unsafe extern "C" fn() -> GameLib;
+
+// Exports a game library along with the version of the engine that it was built against, which the editor checks before touching anything else.
+//
+// The callbacks are called from the editor through the C ABI, where a panic aborts the whole editor. Game state errors are reported
+// and the callback returns instead:
+//
+//     extern "C" fn on_update(engine: &mut GoldfishEngine) {
+//         if let Err(err) = engine.with_game_state(|engine, game: &mut Game| game.update(engine)) {
+//             println!("WARNING: Failed to get the game state, the game is not updated! {}", err);
+//         }
+//     }
+#[macro_export]
+macro_rules! export_game_lib {
+	($game_lib:expr) => {
+		#[no_mangle]
+		extern "C" fn _goldfish_game_lib_version() -> $crate::game::GameLibVersion {
+			$crate::game::GameLibVersion::CURRENT
+		}
+
+		#[no_mangle]
+		extern "C" fn _goldfish_create_game_lib() -> $crate::game::GameLib {
+			$game_lib
+		}
+	};
+}
+
+#[derive(Error, Debug)]
+pub enum GameStateError {
+	#[error("There is no game state, it was either never set, already taken or is lent out")]
+	Missing,
+	#[error("The game state is a {found} and not a {expected}")]
+	TypeMismatch { found: &'static str, expected: &'static str },
+	#[error("The game state is already a {0}, it has to be taken before it can be set again")]
+	Occupied(&'static str),
 }
 
-pub type CreateGamelibApi = unsafe fn() -> GameLib;
+// The state of the game library, owned by the engine. The editor drops whatever is left in it when a library is unloaded,
+// so a state can never outlive the library that it belongs to.
+pub(crate) struct GameState {
+	value: Box<dyn Any>,
+	type_name: &'static str,
+}
+
+impl GoldfishEngine {
+	// Hands `state` back if there already is a game state, so that the caller can still clean it up.
+	pub fn set_game_state<T: Any>(&mut self, state: T) -> Result<(), (T, GameStateError)> {
+		if let Some(game_state) = &self.game_state {
+			return Err((state, GameStateError::Occupied(game_state.type_name)));
+		}
+
+		self.game_state = Some(GameState {
+			value: Box::new(state),
+			type_name: std::any::type_name::<T>(),
+		});
+		Ok(())
+	}
+
+	pub fn take_game_state<T: Any>(&mut self) -> Result<T, GameStateError> {
+		let game_state = self.game_state.take().ok_or(GameStateError::Missing)?;
+		match game_state.value.downcast::<T>() {
+			Ok(value) => Ok(*value),
+			Err(value) => {
+				let found = game_state.type_name;
+				self.game_state = Some(GameState { value, type_name: found });
+				Err(GameStateError::TypeMismatch {
+					found,
+					expected: std::any::type_name::<T>(),
+				})
+			}
+		}
+	}
+
+	pub fn game_state<T: Any>(&self) -> Result<&T, GameStateError> {
+		let game_state = self.game_state.as_ref().ok_or(GameStateError::Missing)?;
+		game_state.value.downcast_ref::<T>().ok_or(GameStateError::TypeMismatch {
+			found: game_state.type_name,
+			expected: std::any::type_name::<T>(),
+		})
+	}
+
+	// Lends the game state to `f` along with the engine, the state is taken out of the engine while `f` runs.
+	pub fn with_game_state<T: Any, R>(&mut self, f: impl FnOnce(&mut Self, &mut T) -> R) -> Result<R, GameStateError> {
+		let mut state = self.take_game_state::<T>()?;
+		let result = f(self, &mut state);
+
+		if let Err((_, err)) = self.set_game_state(state) {
+			println!("WARNING: The game state was set while it was lent out, the lent out state is dropped! {}", err);
+		}
+		Ok(result)
+	}
+
+	// Drops the game state without giving it back to the game, returns the name of its type if there was one.
+	pub fn drop_game_state(&mut self) -> Option<&'static str> {
+		self.game_state.take().map(|game_state| game_state.type_name)
+	}
+}
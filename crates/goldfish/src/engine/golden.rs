@@ -0,0 +1,263 @@
+use crate::types::Size;
+use image::RgbaImage;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+// Setting this environment variable makes the editor overwrite the reference images with whatever was rendered instead of comparing.
+pub const UPDATE_GOLDENS_ENV: &'static str = "GOLDFISH_UPDATE_GOLDENS";
+
+#[derive(Error, Debug)]
+pub enum GoldenError {
+	#[error("Failed to read or write golden image: {0}")]
+	Image(image::ImageError),
+	#[error("A filesystem error occurred {0}")]
+	Filesystem(std::io::Error),
+	#[error("Output image buffer does not match the size {0}x{1}")]
+	InvalidBuffer(u32, u32),
+	#[error("Reference image {0:?} does not exist, rerun with GOLDFISH_UPDATE_GOLDENS=1 to create it")]
+	MissingReference(PathBuf),
+	#[error("Reference image is {expected_width}x{expected_height} but the output is {actual_width}x{actual_height}")]
+	SizeMismatch {
+		expected_width: u32,
+		expected_height: u32,
+		actual_width: u32,
+		actual_height: u32,
+	},
+	#[error("{mismatched_pixels} pixels differ from the reference by more than the tolerance (max difference {max_difference}), see {diff_path:?}")]
+	Mismatch {
+		mismatched_pixels: usize,
+		max_difference: u8,
+		actual_path: PathBuf,
+		diff_path: PathBuf,
+	},
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GoldenTolerance {
+	// Max absolute difference allowed in any single channel before a pixel counts as mismatched.
+	pub per_channel: u8,
+	// Number of mismatched pixels that are still allowed, software rasterizers tend to disagree on a few edge pixels.
+	pub max_mismatched_pixels: usize,
+}
+
+impl Default for GoldenTolerance {
+	fn default() -> Self {
+		Self {
+			per_channel: 2,
+			max_mismatched_pixels: 0,
+		}
+	}
+}
+
+pub struct GoldenComparison {
+	pub mismatched_pixels: usize,
+	pub max_difference: u8,
+	pub diff: RgbaImage,
+}
+
+pub enum GoldenResult {
+	Passed(GoldenComparison),
+	Updated(PathBuf),
+}
+
+// Compares two RGBA8 images pixel by pixel. The diff image is black where the pixels match within the tolerance,
+// and shows the per channel difference in red (scaled up to be visible) where they don't.
+pub fn compare_images(actual: &RgbaImage, expected: &RgbaImage, tolerance: GoldenTolerance) -> GoldenComparison {
+	assert!(actual.dimensions() == expected.dimensions(), "Cannot compare images of different sizes!");
+
+	let mut mismatched_pixels = 0;
+	let mut max_difference = 0;
+	let mut diff = RgbaImage::new(actual.width(), actual.height());
+
+	for ((a, e), d) in actual.pixels().zip(expected.pixels()).zip(diff.pixels_mut()) {
+		let difference = a.0.iter().zip(e.0.iter()).map(|(&a, &e)| a.abs_diff(e)).max().unwrap_or(0);
+		max_difference = max_difference.max(difference);
+
+		if difference > tolerance.per_channel {
+			mismatched_pixels += 1;
+			d.0 = [difference.saturating_mul(4).max(64), 0, 0, 255];
+		} else {
+			d.0 = [0, 0, 0, 255];
+		}
+	}
+
+	GoldenComparison {
+		mismatched_pixels,
+		max_difference,
+		diff,
+	}
+}
+
+// Checks the RGBA8 `pixels` of an output of `size` against `<dir>/<name>.png`. On failure `<name>.actual.png` and
+// `<name>.diff.png` are written next to the reference. When `update` is set the reference is overwritten instead.
+pub fn check_golden(dir: &Path, name: &str, pixels: Vec<u8>, size: Size, tolerance: GoldenTolerance, update: bool) -> Result<GoldenResult, GoldenError> {
+	let actual = RgbaImage::from_raw(size.width, size.height, pixels).ok_or(GoldenError::InvalidBuffer(size.width, size.height))?;
+
+	let reference_path = dir.join(format!("{}.png", name));
+	let actual_path = dir.join(format!("{}.actual.png", name));
+	let diff_path = dir.join(format!("{}.diff.png", name));
+
+	if update {
+		std::fs::create_dir_all(dir).map_err(|err| GoldenError::Filesystem(err))?;
+		actual.save(&reference_path).map_err(|err| GoldenError::Image(err))?;
+		return Ok(GoldenResult::Updated(reference_path));
+	}
+
+	if !reference_path.is_file() {
+		return Err(GoldenError::MissingReference(reference_path));
+	}
+
+	let expected = image::open(&reference_path).map_err(|err| GoldenError::Image(err))?.into_rgba8();
+
+	if expected.dimensions() != actual.dimensions() {
+		actual.save(&actual_path).map_err(|err| GoldenError::Image(err))?;
+		return Err(GoldenError::SizeMismatch {
+			expected_width: expected.width(),
+			expected_height: expected.height(),
+			actual_width: actual.width(),
+			actual_height: actual.height(),
+		});
+	}
+
+	let comparison = compare_images(&actual, &expected, tolerance);
+
+	if comparison.mismatched_pixels > tolerance.max_mismatched_pixels {
+		actual.save(&actual_path).map_err(|err| GoldenError::Image(err))?;
+		comparison.diff.save(&diff_path).map_err(|err| GoldenError::Image(err))?;
+
+		return Err(GoldenError::Mismatch {
+			mismatched_pixels: comparison.mismatched_pixels,
+			max_difference: comparison.max_difference,
+			actual_path,
+			diff_path,
+		});
+	}
+
+	Ok(GoldenResult::Passed(comparison))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use image::Rgba;
+
+	const REFERENCE_NAME: &'static str = "gradient";
+
+	// The contents of `goldens/gradient.png`.
+	fn gradient() -> RgbaImage {
+		RgbaImage::from_fn(8, 8, |x, y| Rgba([(x * 32) as u8, (y * 32) as u8, ((x + y) % 2 * 255) as u8, 255]))
+	}
+
+	fn size_of(image: &RgbaImage) -> Size {
+		Size {
+			width: image.width(),
+			height: image.height(),
+		}
+	}
+
+	// `check_golden` writes the actual and diff images next to the reference, so every test gets its own copy of it.
+	fn reference_dir(test: &str) -> PathBuf {
+		let dir = std::env::temp_dir().join("goldfish_golden_tests").join(test);
+		std::fs::create_dir_all(&dir).unwrap();
+		std::fs::copy(Path::new(env!("CARGO_MANIFEST_DIR")).join("goldens").join("gradient.png"), dir.join("gradient.png")).unwrap();
+		dir
+	}
+
+	#[test]
+	fn identical_images_match() {
+		let image = gradient();
+		let comparison = compare_images(&image, &image, GoldenTolerance::default());
+
+		assert_eq!(comparison.mismatched_pixels, 0);
+		assert_eq!(comparison.max_difference, 0);
+	}
+
+	#[test]
+	fn differences_within_tolerance_match() {
+		let expected = gradient();
+		let mut actual = expected.clone();
+		actual.get_pixel_mut(3, 4).0[0] += 2;
+
+		let comparison = compare_images(&actual, &expected, GoldenTolerance::default());
+
+		assert_eq!(comparison.mismatched_pixels, 0);
+		assert_eq!(comparison.max_difference, 2);
+	}
+
+	#[test]
+	fn differences_over_tolerance_mismatch() {
+		let expected = gradient();
+		let mut actual = expected.clone();
+		actual.get_pixel_mut(3, 4).0[1] += 3;
+
+		let comparison = compare_images(&actual, &expected, GoldenTolerance::default());
+
+		assert_eq!(comparison.mismatched_pixels, 1);
+		assert_eq!(comparison.max_difference, 3);
+		assert_ne!(comparison.diff.get_pixel(3, 4).0[0], 0);
+		assert_eq!(comparison.diff.get_pixel(0, 0).0, [0, 0, 0, 255]);
+	}
+
+	#[test]
+	fn committed_reference_passes() {
+		let actual = gradient();
+		let result = check_golden(&reference_dir("passes"), REFERENCE_NAME, actual.to_vec(), size_of(&actual), GoldenTolerance::default(), false);
+
+		assert!(matches!(result, Ok(GoldenResult::Passed(_))));
+	}
+
+	#[test]
+	fn mismatched_pixels_over_budget_fail() {
+		let tolerance = GoldenTolerance {
+			max_mismatched_pixels: 1,
+			..Default::default()
+		};
+
+		let mut actual = gradient();
+		actual.get_pixel_mut(0, 0).0[2] = 128;
+		let result = check_golden(&reference_dir("within_budget"), REFERENCE_NAME, actual.to_vec(), size_of(&actual), tolerance, false);
+		assert!(matches!(result, Ok(GoldenResult::Passed(_))));
+
+		actual.get_pixel_mut(1, 0).0[2] = 128;
+		let dir = reference_dir("over_budget");
+		let result = check_golden(&dir, REFERENCE_NAME, actual.to_vec(), size_of(&actual), tolerance, false);
+		assert!(matches!(result, Err(GoldenError::Mismatch { mismatched_pixels: 2, .. })));
+		assert!(dir.join("gradient.actual.png").is_file());
+		assert!(dir.join("gradient.diff.png").is_file());
+	}
+
+	#[test]
+	fn size_mismatch_fails() {
+		let actual = RgbaImage::new(4, 8);
+		let result = check_golden(&reference_dir("size_mismatch"), REFERENCE_NAME, actual.to_vec(), size_of(&actual), GoldenTolerance::default(), false);
+
+		assert!(matches!(
+			result,
+			Err(GoldenError::SizeMismatch {
+				expected_width: 8,
+				expected_height: 8,
+				actual_width: 4,
+				actual_height: 8,
+			})
+		));
+	}
+
+	#[test]
+	fn missing_reference_fails() {
+		let actual = gradient();
+		let result = check_golden(&reference_dir("missing"), "missing", actual.to_vec(), size_of(&actual), GoldenTolerance::default(), false);
+
+		assert!(matches!(result, Err(GoldenError::MissingReference(_))));
+	}
+
+	#[test]
+	fn update_overwrites_reference() {
+		let dir = reference_dir("update");
+		let actual = RgbaImage::new(4, 8);
+		let result = check_golden(&dir, REFERENCE_NAME, actual.to_vec(), size_of(&actual), GoldenTolerance::default(), true);
+		assert!(matches!(result, Ok(GoldenResult::Updated(_))));
+
+		let result = check_golden(&dir, REFERENCE_NAME, actual.to_vec(), size_of(&actual), GoldenTolerance::default(), false);
+		assert!(matches!(result, Ok(GoldenResult::Passed(_))));
+	}
+}
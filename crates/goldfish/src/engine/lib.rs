@@ -3,15 +3,18 @@
 
 pub mod build;
 pub mod game;
+pub mod golden;
 pub mod package;
 pub mod renderer;
 pub mod tracy_gpu;
 pub mod types;
 pub mod window;
 
+use game::GameState;
 pub use glam::*;
 use package::{AssetType, Package, ReadAssetFn};
 use renderer::{GraphicsContext, GraphicsDevice};
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::time::Duration;
 use thiserror::Error;
 use tracy_client as tracy;
@@ -33,14 +36,20 @@ pub type GoldfishResult<T> = Result<T, GoldfishError>;
 extern crate scopeguard;
 
 pub struct GoldfishEngine {
-	pub window: Window,
+	pub window: Option<Window>,
 	package_reader: ReadAssetFn,
 	pub graphics_device: GraphicsDevice,
 	pub graphics_context: GraphicsContext,
-	pub game_state: *mut (),
+	game_state: Option<GameState>,
+	// What the game wants to keep when its library is reloaded, written in `GameLib::on_save_state` and taken in `GameLib::on_restore_state`.
+	// It has to be serialized since the types of the old library aren't the same as the ones of the new library.
+	pub saved_game_state: Option<Vec<u8>>,
 	tracy: tracy::Client,
 	pub keys: [bool; 255],
 	pub mouse_delta: DVec2,
+	asset_reload_sender: Sender<Uuid>,
+	asset_reload_receiver: Receiver<Uuid>,
+	reloaded_assets: Vec<Uuid>,
 }
 
 #[global_allocator]
@@ -50,34 +59,97 @@ impl GoldfishEngine {
 	pub fn new(title: &'static str, package_reader: ReadAssetFn) -> Self {
 		let tracy = tracy::Client::start();
 		let window = Window::new(title).unwrap();
-		let game_state = std::ptr::null_mut();
 		let keys = [false; 255];
 		let mouse_delta = Default::default();
+		let (asset_reload_sender, asset_reload_receiver) = mpsc::channel();
 
 		let (graphics_device, graphics_context) = GraphicsDevice::new_with_context(&window);
 
 		Self {
-			window,
+			window: Some(window),
 			graphics_device,
 			graphics_context,
 			package_reader,
 			tracy,
-			game_state,
+			game_state: None,
+			saved_game_state: None,
 			keys,
 			mouse_delta,
+			asset_reload_sender,
+			asset_reload_receiver,
+			reloaded_assets: Vec::new(),
 		}
 	}
 
+	// Creates an engine without a window or surface that renders into an offscreen target of a fixed size.
+	pub fn new_headless(title: &'static str, package_reader: ReadAssetFn, size: Size) -> Self {
+		let tracy = tracy::Client::start();
+		let keys = [false; 255];
+		let mouse_delta = Default::default();
+		let (asset_reload_sender, asset_reload_receiver) = mpsc::channel();
+
+		let (graphics_device, graphics_context) = GraphicsDevice::new_headless_with_context(title, size);
+
+		Self {
+			window: None,
+			graphics_device,
+			graphics_context,
+			package_reader,
+			tracy,
+			game_state: None,
+			saved_game_state: None,
+			keys,
+			mouse_delta,
+			asset_reload_sender,
+			asset_reload_receiver,
+			reloaded_assets: Vec::new(),
+		}
+	}
+
+	pub fn is_headless(&self) -> bool {
+		self.window.is_none()
+	}
+
+	pub fn get_output_size(&self) -> Size {
+		match &self.window {
+			Some(window) => window.get_size(),
+			None => self.graphics_context.output_size(),
+		}
+	}
+
+	// Reads back the last rendered frame as RGBA8, only available on headless engines.
+	pub fn capture_output(&mut self) -> Vec<u8> {
+		assert!(self.is_headless(), "Only headless engines can capture their output!");
+		self.graphics_context.read_output_image()
+	}
+
 	pub fn read_package(&self, uuid: Uuid, asset_type: AssetType) -> GoldfishResult<Package> {
 		let fn_ptr = self.package_reader;
 		fn_ptr(uuid, asset_type)
 	}
 
+	// Whoever rebuilds packages while the engine is running sends their uuids through this, the game sees them in
+	// `reloaded_assets` at the start of the next frame.
+	pub fn asset_reload_sender(&self) -> Sender<Uuid> {
+		self.asset_reload_sender.clone()
+	}
+
+	// The packages that were rebuilt since the last frame, resources created from them should be recreated this frame.
+	pub fn reloaded_assets(&self) -> &[Uuid] {
+		&self.reloaded_assets
+	}
+
+	fn receive_reloaded_assets(&mut self) {
+		self.reloaded_assets.clear();
+		self.reloaded_assets.extend(self.asset_reload_receiver.try_iter());
+	}
+
 	pub fn run<F>(&mut self, mut editor_update: F)
 	where
 		F: FnMut(&mut Self, Duration),
 	{
-		Window::run(self.window.get_run_context(), |dt, keys, mouse_delta, new_size| {
+		let run_context = self.window.as_mut().expect("Cannot run a headless engine with a window event loop, use run_headless instead!").get_run_context();
+		Window::run(run_context, |dt, keys, mouse_delta, new_size| {
 			self.keys.copy_from_slice(keys);
 			self.mouse_delta = mouse_delta;
 
@@ -94,19 +166,43 @@ impl GoldfishEngine {
 			}
 			// renderer.update(&self.window);
 
+			self.receive_reloaded_assets();
 			editor_update(self, dt);
 			tracy::frame_mark();
 		});
 	}
 
+	// Steps the engine a fixed number of frames without any input. Every frame gets the same delta time
+	// so that the output is deterministic between runs.
+	pub fn run_headless<F>(&mut self, frame_count: usize, mut editor_update: F)
+	where
+		F: FnMut(&mut Self, Duration),
+	{
+		const HEADLESS_DT: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+		for _ in 0..frame_count {
+			tracy::span!();
+			self.keys = [false; 255];
+			self.mouse_delta = Default::default();
+
+			self.receive_reloaded_assets();
+			editor_update(self, HEADLESS_DT);
+			tracy::frame_mark();
+		}
+	}
+
 	pub fn lock_cursor(&self) {
-		self.window.winit_window.set_cursor_grab(winit::window::CursorGrabMode::Locked).unwrap();
-		self.window.winit_window.set_cursor_visible(false);
+		if let Some(window) = &self.window {
+			window.winit_window.set_cursor_grab(winit::window::CursorGrabMode::Locked).unwrap();
+			window.winit_window.set_cursor_visible(false);
+		}
 	}
 
 	pub fn unlock_cursor(&self) {
-		self.window.winit_window.set_cursor_visible(true);
-		self.window.winit_window.set_cursor_grab(winit::window::CursorGrabMode::None).unwrap();
+		if let Some(window) = &self.window {
+			window.winit_window.set_cursor_visible(true);
+			window.winit_window.set_cursor_grab(winit::window::CursorGrabMode::None).unwrap();
+		}
 	}
 }
 
@@ -114,6 +210,9 @@ impl Drop for GoldfishEngine {
 	fn drop(&mut self) {
 		// let renderer = self.renderer.take().unwrap();
 		// renderer.destroy();
+		if let Some(type_name) = self.drop_game_state() {
+			println!("WARNING: The game never took back its {} state, it is dropped without being destroyed!", type_name);
+		}
 		self.graphics_context.destroy();
 		self.graphics_device.destroy();
 	}
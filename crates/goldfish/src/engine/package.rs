@@ -1,4 +1,9 @@
-use super::{renderer::Vertex, GoldfishError, GoldfishResult};
+use super::{
+	renderer::{MeshIndices, SamplerDesc, TextureFormat, Vertex},
+	types::Vec3Serde,
+	GoldfishError, GoldfishResult,
+};
+use glam::{Mat4, Quat, Vec3};
 use serde::{Deserialize, Serialize};
 
 use uuid::Uuid;
@@ -6,8 +11,11 @@ use uuid::Uuid;
 #[derive(Serialize, Deserialize)]
 pub enum AssetType {
 	Mesh,
+	// Imported alongside the meshes of a model, there are no scene source files.
+	Scene,
 	Texture,
 	Shader,
+	Material,
 	Other,
 }
 
@@ -15,8 +23,9 @@ impl AssetType {
 	pub fn from_extension(extension: &str) -> Self {
 		match extension.to_ascii_lowercase().as_str() {
 			"png" | "jpg" | "jpeg" => Self::Texture,
-			"fbx" | "obj" => Self::Mesh,
+			"fbx" | "obj" | "gltf" | "glb" => Self::Mesh,
 			"hlsl" => Self::Shader,
+			"mat" => Self::Material,
 			_ => Self::Other,
 		}
 	}
@@ -24,7 +33,10 @@ impl AssetType {
 
 pub enum Package {
 	Mesh(MeshPackage),
+	Scene(ScenePackage),
 	Shader(ShaderPackage),
+	Texture(TexturePackage),
+	Material(MaterialPackage),
 	Text(String),
 	Bin(Vec<u8>),
 }
@@ -35,10 +47,144 @@ pub struct ShaderPackage {
 	pub ps_ir: Option<Vec<u32>>,
 }
 
+// What to bind to one of the descriptor sets of a shader, checked against the layout that the shader declares when the material was imported.
+#[derive(Serialize, Deserialize)]
+pub struct MaterialPackage {
+	pub shader: Uuid,
+	pub set: u32,
+	pub bindings: Vec<(u32, MaterialBinding)>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum MaterialBinding {
+	// The contents of the cbuffer laid out the way the shader reads them.
+	CBuffer(Vec<u8>),
+	Texture(Uuid),
+	Sampler(SamplerDesc),
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct MeshPackage {
 	pub vertices: Vec<Vertex>,
-	pub indices: Vec<u16>,
+	pub indices: MeshIndices,
+	// One per vertex if the mesh is skinned, empty otherwise.
+	pub joints: Vec<VertexJoints>,
+}
+
+// The joints of `SceneSkin::joints` that move a vertex and how much each of them does.
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+pub struct VertexJoints {
+	pub indices: [u16; 4],
+	pub weights: [f32; 4],
+}
+
+// A 2D texture with its full mip chain. `data` holds every mip tightly packed one after the other starting at mip 0.
+#[derive(Serialize, Deserialize)]
+pub struct TexturePackage {
+	pub width: u32,
+	pub height: u32,
+	pub format: TextureFormat,
+	pub mip_levels: u32,
+	pub data: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct SceneMesh {
+	pub mesh: Uuid,
+	// Index into `ScenePackage::material_slots`.
+	pub material_slot: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SceneNode {
+	pub name: String,
+	pub parent: Option<usize>,
+	#[serde(with = "Vec3Serde")]
+	pub translation: Vec3,
+	// Quaternion as x, y, z, w.
+	pub rotation: [f32; 4],
+	#[serde(with = "Vec3Serde")]
+	pub scale: Vec3,
+	pub meshes: Vec<SceneMesh>,
+	// Index into `ScenePackage::skins` that the meshes of this node are deformed by.
+	pub skin: Option<usize>,
+}
+
+impl SceneNode {
+	// Transform relative to the parent node.
+	pub fn local_transform(&self) -> Mat4 {
+		Mat4::from_scale_rotation_translation(self.scale, Quat::from_array(self.rotation), self.translation)
+	}
+}
+
+// A material that the meshes of a model were assigned in the file they were imported from, described with PBR metallic-roughness parameters.
+// Textures are uuids of textures that were imported from the same model.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MaterialSlot {
+	pub name: String,
+	pub base_color: [f32; 4],
+	pub base_color_texture: Option<Uuid>,
+	pub metallic: f32,
+	pub roughness: f32,
+	// Metalness in the blue channel and roughness in the green channel.
+	pub metallic_roughness_texture: Option<Uuid>,
+	pub normal_texture: Option<Uuid>,
+	pub occlusion_texture: Option<Uuid>,
+	pub emissive: [f32; 3],
+	pub emissive_texture: Option<Uuid>,
+}
+
+impl MaterialSlot {
+	// A material that only has a name, with the parameters that glTF defaults to.
+	pub fn new(name: String) -> Self {
+		Self {
+			name,
+			base_color: [1.0; 4],
+			base_color_texture: None,
+			metallic: 1.0,
+			roughness: 1.0,
+			metallic_roughness_texture: None,
+			normal_texture: None,
+			occlusion_texture: None,
+			emissive: [0.0; 3],
+			emissive_texture: None,
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SceneSkin {
+	// Indices into `ScenePackage::nodes`.
+	pub joints: Vec<usize>,
+	// Column major, one for every joint.
+	pub inverse_bind_matrices: Vec<[f32; 16]>,
+}
+
+impl SceneSkin {
+	pub fn inverse_bind_matrix(&self, joint: usize) -> Mat4 {
+		Mat4::from_cols_array(&self.inverse_bind_matrices[joint])
+	}
+}
+
+// The node hierarchy of a model. Nodes always come after their parent, so the first node is the root.
+#[derive(Serialize, Deserialize)]
+pub struct ScenePackage {
+	pub nodes: Vec<SceneNode>,
+	pub material_slots: Vec<MaterialSlot>,
+	pub skins: Vec<SceneSkin>,
+}
+
+impl ScenePackage {
+	// Transform of every node relative to the root of the scene.
+	pub fn world_transforms(&self) -> Vec<Mat4> {
+		let mut transforms: Vec<Mat4> = Vec::with_capacity(self.nodes.len());
+		for node in self.nodes.iter() {
+			let parent = node.parent.map_or(Mat4::IDENTITY, |parent| transforms[parent]);
+			transforms.push(parent * node.local_transform());
+		}
+
+		transforms
+	}
 }
 
 pub type ReadAssetFn = fn(Uuid, AssetType) -> GoldfishResult<Package>;
@@ -2,13 +2,22 @@ use super::{
 	device::{VulkanDestructor, VulkanDevice, VulkanUploadContext},
 	VulkanGraphicsContext, VulkanRasterCmd,
 };
-use crate::renderer::BufferUsage;
+use crate::renderer::{BufferUsage, IndexType};
 use ash::vk;
 use gpu_allocator::vulkan as vma;
 use gpu_allocator::MemoryLocation;
 
 use std::hash::{Hash, Hasher};
 
+impl From<IndexType> for vk::IndexType {
+	fn from(index_type: IndexType) -> Self {
+		match index_type {
+			IndexType::U16 => vk::IndexType::UINT16,
+			IndexType::U32 => vk::IndexType::UINT32,
+		}
+	}
+}
+
 impl From<BufferUsage> for vk::BufferUsageFlags {
 	fn from(usage: BufferUsage) -> vk::BufferUsageFlags {
 		let mut flags = vk::BufferUsageFlags::default();
@@ -180,11 +189,11 @@ impl VulkanGraphicsContext {
 		});
 	}
 
-	pub fn bind_index_buffer(&self, buffer: &VulkanBuffer) {
+	pub fn bind_index_buffer(&self, buffer: &VulkanBuffer, index_type: IndexType) {
 		self.queue_raster_cmd(VulkanRasterCmd::BindIndexBuffer {
 			buffer: buffer.raw,
 			offset: 0,
-			index_type: vk::IndexType::UINT16,
+			index_type: index_type.into(),
 		});
 	}
 }
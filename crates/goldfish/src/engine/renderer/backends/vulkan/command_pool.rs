@@ -2,6 +2,7 @@ use super::device::VulkanDevice;
 use ash::vk;
 use tracy_client as tracy;
 
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub enum QueueType {
 	GRAPHICS,
 	COMPUTE,
@@ -13,21 +14,22 @@ pub struct VulkanCommandPool {
 	raw: vk::CommandPool,
 	command_buffers: Vec<VulkanCommandBuffer>,
 	index: usize,
+	secondary_command_buffers: Vec<VulkanCommandBuffer>,
+	secondary_index: usize,
 }
 
 impl VulkanDevice {
 	pub fn create_command_pool(&self, queue_type: QueueType) -> VulkanCommandPool {
 		tracy::span!();
-		let queue_index = match queue_type {
-			QueueType::GRAPHICS => self.get_queue_family_indices().graphics_family,
-			QueueType::COMPUTE => self.get_queue_family_indices().compute_family,
-		};
+		let queue_index = self.get_queue_family_index(queue_type);
 		let raw = unsafe { self.raw.create_command_pool(&vk::CommandPoolCreateInfo::builder().queue_family_index(queue_index), None).unwrap() };
 
 		VulkanCommandPool {
 			raw,
 			command_buffers: vec![],
 			index: 0,
+			secondary_command_buffers: vec![],
+			secondary_index: 0,
 		}
 	}
 
@@ -67,6 +69,42 @@ impl VulkanCommandPool {
 		self.index += 1;
 	}
 
+	// Begins a secondary command buffer. If `render_pass` is set the command buffer executes entirely within that render pass and framebuffer.
+	pub fn begin_secondary_command_buffer(&mut self, device: &VulkanDevice, render_pass: Option<(vk::RenderPass, vk::Framebuffer)>) -> VulkanCommandBuffer {
+		tracy::span!();
+		if self.secondary_index == self.secondary_command_buffers.len() {
+			let new_cmd_buffer = self.allocate(device, vk::CommandBufferLevel::SECONDARY);
+			self.secondary_command_buffers.push(new_cmd_buffer);
+		}
+
+		let command_buffer = self.secondary_command_buffers[self.secondary_index];
+		self.secondary_index += 1;
+
+		let (flags, inheritance_info) = match render_pass {
+			Some((render_pass, framebuffer)) => (
+				vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT | vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE,
+				vk::CommandBufferInheritanceInfo::builder().render_pass(render_pass).subpass(0).framebuffer(framebuffer).build(),
+			),
+			None => (vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT, vk::CommandBufferInheritanceInfo::default()),
+		};
+
+		unsafe {
+			device
+				.raw
+				.begin_command_buffer(command_buffer, &vk::CommandBufferBeginInfo::builder().flags(flags).inheritance_info(&inheritance_info))
+				.expect("Failed to begin secondary command buffer!");
+		}
+
+		command_buffer
+	}
+
+	pub fn end_secondary_command_buffer(&mut self, device: &VulkanDevice, command_buffer: VulkanCommandBuffer) {
+		tracy::span!();
+		unsafe {
+			device.raw.end_command_buffer(command_buffer).expect("Failed to end secondary command buffer!");
+		}
+	}
+
 	pub fn recycle(&mut self, device: &VulkanDevice) {
 		tracy::span!();
 		unsafe {
@@ -76,24 +114,24 @@ impl VulkanCommandPool {
 				.expect("Failed to recycle command pool!");
 		}
 		self.index = 0;
+		self.secondary_index = 0;
 	}
 
 	fn expand(&mut self, device: &VulkanDevice) {
 		tracy::span!();
-		let new_cmd_buffer = *unsafe {
+		let new_cmd_buffer = self.allocate(device, vk::CommandBufferLevel::PRIMARY);
+
+		self.command_buffers.push(new_cmd_buffer);
+	}
+
+	fn allocate(&self, device: &VulkanDevice, level: vk::CommandBufferLevel) -> VulkanCommandBuffer {
+		*unsafe {
 			device
 				.raw
-				.allocate_command_buffers(
-					&vk::CommandBufferAllocateInfo::builder()
-						.command_pool(self.raw)
-						.level(vk::CommandBufferLevel::PRIMARY)
-						.command_buffer_count(1),
-				)
+				.allocate_command_buffers(&vk::CommandBufferAllocateInfo::builder().command_pool(self.raw).level(level).command_buffer_count(1))
 				.unwrap()
 		}
 		.first()
-		.unwrap();
-
-		self.command_buffers.push(new_cmd_buffer);
+		.unwrap()
 	}
 }
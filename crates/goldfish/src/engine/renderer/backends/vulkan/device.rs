@@ -1,3 +1,4 @@
+use crate::renderer::SamplerDesc;
 use crate::window::Window;
 
 use super::command_pool::{QueueType, VulkanCommandBuffer, VulkanCommandPool};
@@ -53,7 +54,7 @@ pub struct VulkanDevice {
 
 	pub raw: Arc<ash::Device>,
 
-	pub surface: vk::SurfaceKHR,
+	pub surface: Option<vk::SurfaceKHR>,
 	pub surface_loader: Surface,
 
 	debug_utils_loader: DebugUtils,
@@ -66,13 +67,22 @@ pub struct VulkanDevice {
 	pub present_queue: Arc<Mutex<vk::Queue>>,
 
 	pub depth_format: vk::Format,
+	pub depth_stencil_format: vk::Format,
 
 	queue_family_indices: QueueFamilyIndices,
 
+	// Whether VK_EXT_pipeline_creation_feedback is enabled, which reports if pipelines were found in the pipeline cache.
+	pub pipeline_creation_feedback: bool,
+	// Anisotropic filtering is an optional feature, samplers that ask for it fall back to regular filtering without it.
+	pub sampler_anisotropy: bool,
+	// Whether the BC formats can be sampled, which every desktop GPU supports.
+	pub texture_compression_bc: bool,
+
 	pub scratch_fence: Option<VulkanFence>,
 
 	pub frame: Arc<Mutex<VulkanPerFrameData>>,
 	pub descriptor_layouts: Arc<Mutex<HashMap<TypeId, vk::DescriptorSetLayout>>>,
+	pub samplers: Arc<Mutex<HashMap<SamplerDesc, vk::Sampler>>>,
 }
 
 pub struct SwapchainDetails {
@@ -122,17 +132,39 @@ pub struct QueueFamilyIndices {
 
 impl VulkanDevice {
 	pub fn new(window: &Window) -> Self {
+		Self::new_impl(window.name, Some(window))
+	}
+
+	// Creates a device without a surface so that rendering can happen entirely offscreen (CI, render farms, software drivers like lavapipe).
+	pub fn new_headless(name: &'static str) -> Self {
+		Self::new_impl(name, None)
+	}
+
+	fn new_impl(name: &'static str, window: Option<&Window>) -> Self {
 		unsafe {
 			let entry = Entry::linked();
 
-			let mut extension_names = ash_window::enumerate_required_extensions(&window.winit_window).expect("Failed to get required extensions!").to_vec();
+			let mut extension_names = if let Some(window) = window {
+				ash_window::enumerate_required_extensions(&window.winit_window).expect("Failed to get required extensions!").to_vec()
+			} else {
+				Vec::new()
+			};
 			extension_names.push(DebugUtils::name().as_ptr());
 
-			let layer_names = [CStr::from_bytes_with_nul_unchecked(b"VK_LAYER_KHRONOS_validation\0")];
+			let validation_layer = CStr::from_bytes_with_nul_unchecked(b"VK_LAYER_KHRONOS_validation\0");
+
+			// NOTE: CI machines running software drivers usually don't have the validation layers installed, so only request them if they exist.
+			let validation_available = entry
+				.enumerate_instance_layer_properties()
+				.unwrap_or_default()
+				.iter()
+				.any(|layer| CStr::from_ptr(layer.layer_name.as_ptr()) == validation_layer);
+
+			let layer_names = if validation_available { vec![validation_layer] } else { vec![] };
 
 			let layer_names_raw: Vec<*const c_char> = layer_names.iter().map(|raw_name| raw_name.as_ptr()).collect();
 
-			let app_name = CStr::from_bytes_with_nul_unchecked(window.name.as_bytes());
+			let app_name = CStr::from_bytes_with_nul_unchecked(name.as_bytes());
 			let app_info = vk::ApplicationInfo::builder()
 				.application_name(app_name)
 				.application_version(0)
@@ -161,7 +193,7 @@ impl VulkanDevice {
 			let debug_utils_loader = DebugUtils::new(&entry, &instance);
 			let debug_callback = debug_utils_loader.create_debug_utils_messenger(&debug_info, None).expect("Failed to create debug messenger!");
 
-			let surface = ash_window::create_surface(&entry, &instance, &window.winit_window, None).expect("Failed to create surface!");
+			let surface = window.map(|window| ash_window::create_surface(&entry, &instance, &window.winit_window, None).expect("Failed to create surface!"));
 
 			let surface_loader = Surface::new(&entry, &instance);
 
@@ -180,11 +212,23 @@ impl VulkanDevice {
 						compute_family = Some(i as u32);
 					}
 
-					if surface_loader.get_physical_device_surface_support(dev, i as u32, surface).unwrap_or(false) {
+					let present_supported = match surface {
+						Some(surface) => surface_loader.get_physical_device_surface_support(dev, i as u32, surface).unwrap_or(false),
+						// Headless devices never present, so just alias the present queue to the graphics queue.
+						None => prop.queue_flags.contains(vk::QueueFlags::GRAPHICS),
+					};
+
+					if present_supported {
 						present_family = Some(i as u32);
 					}
 
 					if let (Some(graphics_family), Some(compute_family), Some(present_family)) = (graphics_family, compute_family, present_family) {
+						// Prefer a compute only family so that async compute work can actually overlap with the graphics queue.
+						let compute_family = properties
+							.iter()
+							.position(|p| p.queue_flags.contains(vk::QueueFlags::COMPUTE) && !p.queue_flags.contains(vk::QueueFlags::GRAPHICS))
+							.map_or(compute_family, |i| i as u32);
+
 						return Some(QueueFamilyIndices {
 							graphics_family,
 							compute_family,
@@ -197,8 +241,10 @@ impl VulkanDevice {
 			};
 
 			let rate_device_suitability = |dev: vk::PhysicalDevice| -> u32 {
-				match (find_queue_families(dev), Self::query_swapchain_support_physical_device(&surface_loader, surface, dev)) {
-					(Some(_), Some(_swapchain_details)) => {
+				let swapchain_supported = surface.map_or(true, |surface| Self::query_swapchain_support_physical_device(&surface_loader, surface, dev).is_some());
+
+				match find_queue_families(dev) {
+					Some(_) if swapchain_supported => {
 						// TODO(Brandon): Add check for device extension support.
 						let mut score = 0;
 
@@ -249,9 +295,22 @@ impl VulkanDevice {
 				.map(|index| vk::DeviceQueueCreateInfo::builder().queue_family_index(*index).queue_priorities(&queue_priorities).build())
 				.collect();
 
-			let device_extension_names_raw = [Swapchain::name().as_ptr()];
+			let available_extensions = instance.enumerate_device_extension_properties(physical_device).unwrap_or_default();
+			let pipeline_creation_feedback = available_extensions
+				.iter()
+				.any(|e| CStr::from_ptr(e.extension_name.as_ptr()) == vk::ExtPipelineCreationFeedbackFn::name());
+
+			let mut device_extension_names_raw = if surface.is_some() { vec![Swapchain::name().as_ptr()] } else { vec![] };
+			if pipeline_creation_feedback {
+				device_extension_names_raw.push(vk::ExtPipelineCreationFeedbackFn::name().as_ptr());
+			}
+			let supported_features = instance.get_physical_device_features(physical_device);
+			let sampler_anisotropy = supported_features.sampler_anisotropy == vk::TRUE;
+			let texture_compression_bc = supported_features.texture_compression_bc == vk::TRUE;
 			let features = vk::PhysicalDeviceFeatures {
 				shader_clip_distance: 1,
+				sampler_anisotropy: sampler_anisotropy as vk::Bool32,
+				texture_compression_bc: texture_compression_bc as vk::Bool32,
 				..Default::default()
 			};
 
@@ -280,25 +339,17 @@ impl VulkanDevice {
 				.expect("Failed to create Vulkan memory allocator!"),
 			)));
 
-			let depth_formats = [
-				// vk::Format::D32_SFLOAT_S8_UINT,
-				vk::Format::D32_SFLOAT,
-				// vk::Format::D24_UNORM_S8_UINT,
-				// vk::Format::D16_UNORM_S8_UINT,
-				// vk::Format::D16_UNORM,
-			];
-
-			let mut depth_format: Option<vk::Format> = None;
-			for format in depth_formats {
-				let properties = instance.get_physical_device_format_properties(physical_device, format);
-
-				if properties.optimal_tiling_features.contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT) {
-					depth_format = Some(format);
-					break;
-				}
-			}
+			let find_depth_format = |formats: &[vk::Format]| {
+				formats.iter().copied().find(|&format| {
+					let properties = instance.get_physical_device_format_properties(physical_device, format);
+					properties.optimal_tiling_features.contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+				})
+			};
+
+			let depth_format = find_depth_format(&[vk::Format::D32_SFLOAT]).expect("No depth format found on this device!");
 
-			let depth_format = depth_format.expect("No depth format found on this device!");
+			let depth_stencil_format =
+				find_depth_format(&[vk::Format::D24_UNORM_S8_UINT, vk::Format::D32_SFLOAT_S8_UINT, vk::Format::D16_UNORM_S8_UINT]).expect("No depth stencil format found on this device!");
 
 			Self {
 				instance: Arc::new(instance),
@@ -320,8 +371,12 @@ impl VulkanDevice {
 				present_queue,
 
 				depth_format,
+				depth_stencil_format,
 
 				queue_family_indices,
+				pipeline_creation_feedback,
+				sampler_anisotropy,
+				texture_compression_bc,
 				scratch_fence: None,
 
 				frame: Arc::new(Mutex::new(VulkanPerFrameData {
@@ -329,6 +384,7 @@ impl VulkanDevice {
 					frame: 0,
 				})),
 				descriptor_layouts: Default::default(),
+				samplers: Default::default(),
 			}
 		}
 	}
@@ -365,6 +421,42 @@ impl VulkanDevice {
 		}
 	}
 
+	// Submits a command buffer which waits on `wait_semaphores` at the given stages before it starts and signals `signal_semaphores` once it's done.
+	pub fn queue_submit(
+		&self,
+		queue_type: QueueType,
+		command_buffer: VulkanCommandBuffer,
+		wait_semaphores: &[(vk::Semaphore, vk::PipelineStageFlags)],
+		signal_semaphores: &[vk::Semaphore],
+		fence: Option<&VulkanFence>,
+	) {
+		if let Some(fence) = fence {
+			fence.reset(self);
+		}
+
+		let queue = match queue_type {
+			QueueType::GRAPHICS => &self.graphics_queue,
+			QueueType::COMPUTE => &self.compute_queue,
+		};
+
+		let (semaphores, stages): (Vec<_>, Vec<_>) = wait_semaphores.iter().copied().unzip();
+
+		unsafe {
+			self.raw
+				.queue_submit(
+					*queue.lock().unwrap(),
+					&[vk::SubmitInfo::builder()
+						.wait_semaphores(&semaphores)
+						.wait_dst_stage_mask(&stages)
+						.command_buffers(&[command_buffer])
+						.signal_semaphores(signal_semaphores)
+						.build()],
+					fence.map_or(vk::Fence::null(), |fence| fence.raw),
+				)
+				.expect("Failed to submit to queue!");
+		}
+	}
+
 	fn query_swapchain_support_physical_device(surface_loader: &Surface, surface: vk::SurfaceKHR, dev: vk::PhysicalDevice) -> Option<SwapchainDetails> {
 		unsafe {
 			match (
@@ -383,13 +475,30 @@ impl VulkanDevice {
 	}
 
 	pub fn query_swapchain_details(&self) -> SwapchainDetails {
-		Self::query_swapchain_support_physical_device(&self.surface_loader, self.surface, self.physical_device).expect("Failed to get physical device swapchain support details!")
+		let surface = self.surface.expect("Cannot query swapchain details of a headless device!");
+		Self::query_swapchain_support_physical_device(&self.surface_loader, surface, self.physical_device).expect("Failed to get physical device swapchain support details!")
+	}
+
+	pub fn is_headless(&self) -> bool {
+		self.surface.is_none()
 	}
 
 	pub fn get_queue_family_indices(&self) -> &QueueFamilyIndices {
 		&self.queue_family_indices
 	}
 
+	pub fn get_queue_family_index(&self, queue_type: QueueType) -> u32 {
+		match queue_type {
+			QueueType::GRAPHICS => self.queue_family_indices.graphics_family,
+			QueueType::COMPUTE => self.queue_family_indices.compute_family,
+		}
+	}
+
+	// Whether the compute queue lives in a different family than the graphics queue, otherwise work submitted to it can't overlap with graphics work.
+	pub fn has_async_compute(&self) -> bool {
+		self.queue_family_indices.graphics_family != self.queue_family_indices.compute_family
+	}
+
 	pub fn destroy(&mut self) {
 		self.wait_idle();
 
@@ -402,10 +511,16 @@ impl VulkanDevice {
 		}
 
 		unsafe {
+			for (_, sampler) in self.samplers.lock().unwrap().drain() {
+				self.raw.destroy_sampler(sampler, None);
+			}
+
 			std::mem::drop(self.vma.lock().unwrap().take());
 
 			self.raw.destroy_device(None);
-			self.surface_loader.destroy_surface(self.surface, None);
+			if let Some(surface) = self.surface {
+				self.surface_loader.destroy_surface(surface, None);
+			}
 			self.debug_utils_loader.destroy_debug_utils_messenger(self.debug_callback, None);
 			self.instance.destroy_instance(None);
 		}
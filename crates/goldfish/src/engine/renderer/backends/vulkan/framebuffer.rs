@@ -3,7 +3,7 @@ use super::{
 	render_pass::VulkanRenderPass,
 	texture::VulkanTexture,
 };
-use crate::renderer::TextureUsage;
+use crate::renderer::{TextureAspect, TextureSubresource, TextureUsage};
 use ash::vk;
 
 pub struct VulkanFramebuffer {
@@ -13,8 +13,9 @@ pub struct VulkanFramebuffer {
 }
 
 impl VulkanDevice {
-	pub fn create_framebuffer(&self, width: u32, height: u32, render_pass: &VulkanRenderPass, attachments: &[&VulkanTexture]) -> VulkanFramebuffer {
-		let attachments = attachments.iter().map(|a| a.image_view).collect::<Vec<_>>();
+	// Every attachment is rendered to through the view of a single subresource.
+	pub fn create_framebuffer(&self, width: u32, height: u32, render_pass: &VulkanRenderPass, attachments: &[(&VulkanTexture, TextureSubresource)]) -> VulkanFramebuffer {
+		let attachments = attachments.iter().map(|(a, subresource)| a.view(TextureAspect::All, Some(*subresource))).collect::<Vec<_>>();
 
 		let raw = unsafe {
 			self.raw
@@ -0,0 +1,249 @@
+use super::{
+	command_pool::{QueueType, VulkanCommandBuffer},
+	device::VulkanDevice,
+	swapchain::{FrameInfo, VulkanFrame, VulkanSwapchain},
+	texture::VulkanTexture,
+	SwapchainError,
+};
+use crate::renderer::{BufferUsage, TextureDesc, TextureFormat, TextureUsage};
+use crate::types::Size;
+use ash::vk;
+use gpu_allocator::MemoryLocation;
+use tracy_client as tracy;
+
+// Offscreen replacement for the swapchain. The output render pass renders into a single texture
+// which is left in TRANSFER_SRC_OPTIMAL at the end of every frame so that it can be read back.
+pub struct VulkanHeadlessTarget {
+	pub device: VulkanDevice,
+
+	pub extent: vk::Extent2D,
+	pub render_pass: vk::RenderPass,
+
+	output: Option<VulkanTexture>,
+	framebuffer: vk::Framebuffer,
+
+	frames: Vec<VulkanFrame>,
+}
+
+impl VulkanHeadlessTarget {
+	pub const OUTPUT_FORMAT: TextureFormat = TextureFormat::RGBA8UNorm;
+
+	pub fn new(size: Size, device: VulkanDevice) -> Self {
+		let render_pass = Self::create_render_pass(&device);
+		let (output, framebuffer) = Self::create_output(size, &device, render_pass);
+
+		let frames = (0..VulkanSwapchain::MAX_FRAMES_IN_FLIGHT).map(|_| VulkanFrame::new(&device)).collect::<Vec<_>>();
+
+		Self {
+			extent: vk::Extent2D {
+				width: size.width,
+				height: size.height,
+			},
+			render_pass,
+
+			output: Some(output),
+			framebuffer,
+
+			frames,
+
+			device,
+		}
+	}
+
+	pub fn acquire(&mut self) -> Result<FrameInfo, SwapchainError> {
+		let mut guard = self.device.frame.lock().unwrap();
+		let current_frame = guard.frame as usize;
+		assert!(current_frame < VulkanSwapchain::MAX_FRAMES_IN_FLIGHT, "Invalid headless current frame!");
+		tracy::span!();
+
+		let frame = &mut self.frames[current_frame];
+
+		frame.completed_fence.wait(&self.device);
+
+		let destructors = std::mem::take(&mut guard.destructors[current_frame]);
+		for destructor in destructors.into_iter() {
+			self.device.run_destructor(destructor);
+		}
+
+		frame.command_pool.recycle(&self.device);
+		let command_buffer = frame.command_pool.begin_command_buffer(&self.device);
+
+		Ok(FrameInfo {
+			image_index: 0,
+			frame_index: current_frame,
+			output_framebuffer: self.framebuffer,
+			command_buffer,
+		})
+	}
+
+	pub fn submit(&mut self, command_buffer: VulkanCommandBuffer, wait_semaphores: &[(vk::Semaphore, vk::PipelineStageFlags)]) -> Result<(), SwapchainError> {
+		tracy::span!();
+		let mut guard = self.device.frame.lock().unwrap();
+		let current_frame = guard.frame as usize;
+
+		let frame = &mut self.frames[current_frame];
+		frame.command_pool.end_command_buffer(&self.device, command_buffer);
+
+		self.device.queue_submit(QueueType::GRAPHICS, command_buffer, wait_semaphores, &[], Some(&frame.completed_fence));
+
+		guard.frame = ((current_frame + 1) % VulkanSwapchain::MAX_FRAMES_IN_FLIGHT) as u32;
+
+		Ok(())
+	}
+
+	pub fn invalidate(&mut self, size: Size) {
+		tracy::span!();
+		self.device.wait_idle();
+
+		self.destroy_output();
+
+		let (output, framebuffer) = Self::create_output(size, &self.device, self.render_pass);
+		self.output = Some(output);
+		self.framebuffer = framebuffer;
+		self.extent = vk::Extent2D {
+			width: size.width,
+			height: size.height,
+		};
+	}
+
+	// Copies the output texture of the last submitted frame into CPU memory as tightly packed RGBA8 rows.
+	pub fn read_output(&mut self) -> Vec<u8> {
+		tracy::span!();
+		self.device.wait_idle();
+
+		let output = self.output.as_ref().expect("Headless output was destroyed!");
+		let size = (self.extent.width * self.extent.height * 4) as usize;
+
+		let mut readback = self.device.create_empty_buffer(size, MemoryLocation::GpuToCpu, BufferUsage::TransferDst, None);
+
+		let mut upload_context = self.device.create_upload_context();
+		upload_context.wait_submit(|device, cmd| unsafe {
+			device.cmd_copy_image_to_buffer(
+				cmd,
+				output.image,
+				vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+				readback.raw,
+				&[vk::BufferImageCopy::builder()
+					.image_subresource(
+						vk::ImageSubresourceLayers::builder()
+							.aspect_mask(vk::ImageAspectFlags::COLOR)
+							.mip_level(0)
+							.base_array_layer(0)
+							.layer_count(1)
+							.build(),
+					)
+					.image_extent(vk::Extent3D {
+						width: self.extent.width,
+						height: self.extent.height,
+						depth: 1,
+					})
+					.build()],
+			);
+		});
+		self.device.destroy_upload_context(upload_context);
+
+		let pixels = readback.allocation.mapped_slice_mut().expect("Failed to map readback buffer!")[0..size].to_vec();
+
+		self.device.destroy_buffer(readback);
+
+		pixels
+	}
+
+	pub fn raw_device(&self) -> &ash::Device {
+		&self.device.raw
+	}
+
+	pub fn destroy(&mut self) {
+		tracy::span!();
+		self.device.wait_idle();
+
+		self.destroy_output();
+
+		unsafe {
+			self.device.raw.destroy_render_pass(self.render_pass, None);
+		}
+
+		for frame in std::mem::take(&mut self.frames).into_iter() {
+			frame.destroy(&self.device);
+		}
+	}
+
+	fn destroy_output(&mut self) {
+		unsafe {
+			self.device.raw.destroy_framebuffer(self.framebuffer, None);
+		}
+
+		if let Some(output) = self.output.take() {
+			self.device.destroy_texture(output);
+		}
+	}
+
+	fn create_output(size: Size, device: &VulkanDevice, render_pass: vk::RenderPass) -> (VulkanTexture, vk::Framebuffer) {
+		let output = device
+			.create_texture(&TextureDesc::new_2d(size.width, size.height, Self::OUTPUT_FORMAT, TextureUsage::ATTACHMENT | TextureUsage::TRANSFER_SRC))
+			.expect("Failed to create headless output!");
+
+		let framebuffer = unsafe {
+			device
+				.raw
+				.create_framebuffer(
+					&vk::FramebufferCreateInfo::builder()
+						.render_pass(render_pass)
+						.attachments(&[output.image_view])
+						.width(size.width)
+						.height(size.height)
+						.layers(1),
+					None,
+				)
+				.expect("Failed to create framebuffer!")
+		};
+
+		(output, framebuffer)
+	}
+
+	fn create_render_pass(device: &VulkanDevice) -> vk::RenderPass {
+		unsafe {
+			device
+				.raw
+				.create_render_pass(
+					&vk::RenderPassCreateInfo::builder()
+						.attachments(&[vk::AttachmentDescription::builder()
+							.format(Self::OUTPUT_FORMAT.to_vk(device))
+							.samples(vk::SampleCountFlags::TYPE_1)
+							.load_op(vk::AttachmentLoadOp::CLEAR)
+							.store_op(vk::AttachmentStoreOp::STORE)
+							.stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+							.stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+							.initial_layout(vk::ImageLayout::UNDEFINED)
+							.final_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+							.build()])
+						.subpasses(&[vk::SubpassDescription::builder()
+							.pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+							.color_attachments(&[vk::AttachmentReference::builder().attachment(0).layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL).build()])
+							.build()])
+						.dependencies(&[
+							// Every frame in flight renders into the same output, so the color write of the previous frame has to be made
+							// available before this frame overwrites it. Reads by the readback copy only need the execution dependency.
+							vk::SubpassDependency::builder()
+								.src_subpass(vk::SUBPASS_EXTERNAL)
+								.dst_subpass(0)
+								.src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::TRANSFER)
+								.dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+								.src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+								.dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+								.build(),
+							vk::SubpassDependency::builder()
+								.src_subpass(0)
+								.dst_subpass(vk::SUBPASS_EXTERNAL)
+								.src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+								.dst_stage_mask(vk::PipelineStageFlags::TRANSFER)
+								.src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+								.dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+								.build(),
+						]),
+					None,
+				)
+				.expect("Failed to create Render Pass!")
+		}
+	}
+}
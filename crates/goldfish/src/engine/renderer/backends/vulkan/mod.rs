@@ -4,22 +4,31 @@ mod descriptor;
 mod device;
 mod fence;
 mod framebuffer;
+mod headless;
 mod pipeline;
+mod pipeline_cache;
 mod render_pass;
+mod sampler;
 mod semaphore;
 mod shader;
 mod swapchain;
 mod texture;
 
 use crate::window::Window;
-use command_pool::VulkanCommandBuffer;
+use command_pool::{VulkanCommandBuffer, VulkanCommandPool};
+use headless::VulkanHeadlessTarget;
+use semaphore::VulkanSemaphore;
 use swapchain::{FrameInfo, VulkanSwapchain};
 
-use crate::renderer::{ClearValue, DepthCompareOp, DescriptorSetInfo, FaceCullMode, FrameId, ImageLayout, PolygonMode, VertexInputInfo};
+use crate::renderer::{
+	ClearValue, ColorBlendState, DepthCompareOp, DescriptorSetInfo, FaceCullMode, FrameId, ImageLayout, PolygonMode, SampleCount, StencilState, TextureAspect, TextureSubresource, VertexInputInfo,
+};
 use crate::types::{Color, Size};
 use ash::vk;
 use custom_error::custom_error;
 use std::cell::RefCell;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
 use tracy_client as tracy;
 
 custom_error! {pub SwapchainError
@@ -28,13 +37,16 @@ custom_error! {pub SwapchainError
 }
 
 pub use buffer::VulkanBuffer;
+pub use command_pool::QueueType;
 pub use descriptor::{VulkanDescriptorHandle, VulkanDescriptorHeap, VulkanDescriptorLayout, VulkanDescriptorLayoutCache};
 pub use device::{VulkanDevice, VulkanUploadContext};
 pub use framebuffer::VulkanFramebuffer;
 pub use pipeline::VulkanPipeline;
+pub use pipeline_cache::VulkanPipelineCache;
 pub use render_pass::VulkanRenderPass;
+pub use sampler::VulkanSampler;
 pub use shader::VulkanShader;
-pub use texture::VulkanTexture;
+pub use texture::{TextureError, VulkanTexture};
 
 pub enum VulkanRasterCmd {
 	BindPipeline {
@@ -105,6 +117,9 @@ pub enum VulkanRasterCmd {
 	None,
 }
 
+// NOTE: The barrier structs hold `p_next` pointers, but they're always null so the commands can safely be moved to the recording threads.
+unsafe impl Send for VulkanRasterCmd {}
+
 impl Default for VulkanRasterCmd {
 	fn default() -> Self {
 		Self::None
@@ -122,22 +137,234 @@ impl VulkanDevice {
 		let device = VulkanDevice::new(window);
 		let swapchain = VulkanSwapchain::new(window.get_size(), device.clone());
 
-		(
-			device,
-			VulkanGraphicsContext {
-				swapchain,
-				current_frame_info: None,
-				raster_cmds: Default::default(),
-				frame_id: FrameId(0),
-			},
-		)
+		(device, VulkanGraphicsContext::new(VulkanOutput::Swapchain(swapchain)))
+	}
+
+	pub fn new_headless_with_context(name: &'static str, size: Size) -> (Self, VulkanGraphicsContext) {
+		let device = VulkanDevice::new_headless(name);
+		let target = VulkanHeadlessTarget::new(size, device.clone());
+
+		(device, VulkanGraphicsContext::new(VulkanOutput::Headless(target)))
+	}
+}
+
+// Where the output render pass ends up. Everything outside of acquire/submit is identical between the two.
+enum VulkanOutput {
+	Swapchain(VulkanSwapchain),
+	Headless(VulkanHeadlessTarget),
+}
+
+impl VulkanOutput {
+	fn device(&self) -> &VulkanDevice {
+		match self {
+			Self::Swapchain(swapchain) => &swapchain.device,
+			Self::Headless(target) => &target.device,
+		}
+	}
+
+	fn extent(&self) -> vk::Extent2D {
+		match self {
+			Self::Swapchain(swapchain) => swapchain.extent,
+			Self::Headless(target) => target.extent,
+		}
+	}
+
+	fn render_pass(&self) -> vk::RenderPass {
+		match self {
+			Self::Swapchain(swapchain) => swapchain.render_pass,
+			Self::Headless(target) => target.render_pass,
+		}
+	}
+
+	fn acquire(&mut self) -> Result<FrameInfo, SwapchainError> {
+		match self {
+			Self::Swapchain(swapchain) => swapchain.acquire(),
+			Self::Headless(target) => target.acquire(),
+		}
+	}
+
+	fn submit(&mut self, image_index: u32, command_buffer: VulkanCommandBuffer, wait_semaphores: &[(vk::Semaphore, vk::PipelineStageFlags)]) -> Result<(), SwapchainError> {
+		match self {
+			Self::Swapchain(swapchain) => swapchain.submit(image_index, command_buffer, wait_semaphores),
+			Self::Headless(target) => target.submit(command_buffer, wait_semaphores),
+		}
+	}
+
+	fn invalidate(&mut self, size: Size) {
+		match self {
+			Self::Swapchain(swapchain) => swapchain.invalidate(size),
+			Self::Headless(target) => target.invalidate(size),
+		}
+	}
+
+	fn raw_device(&self) -> &ash::Device {
+		match self {
+			Self::Swapchain(swapchain) => swapchain.raw_device(),
+			Self::Headless(target) => target.raw_device(),
+		}
+	}
+
+	fn destroy(&mut self) {
+		match self {
+			Self::Swapchain(swapchain) => swapchain.destroy(),
+			Self::Headless(target) => target.destroy(),
+		}
+	}
+}
+
+// A run of commands that gets recorded into its own secondary command buffer. Lists inside of a render pass continue it.
+struct VulkanCmdList {
+	render_pass: Option<(vk::RenderPass, vk::Framebuffer)>,
+	cmds: Vec<VulkanRasterCmd>,
+}
+
+enum VulkanBatchCmd {
+	// Beginning and ending render passes has to happen in the primary command buffer.
+	Primary(VulkanRasterCmd),
+	Secondary(VulkanCmdList),
+}
+
+// Commands recorded for a single queue. A frame is split into multiple batches whenever work moves between the graphics and compute queues.
+struct VulkanQueueBatch {
+	queue: QueueType,
+	cmds: Vec<VulkanBatchCmd>,
+	// Earlier batches on the other queue which have to finish before the given stages of this batch can start.
+	waits: Vec<(usize, vk::PipelineStageFlags)>,
+}
+
+impl VulkanQueueBatch {
+	fn new(queue: QueueType) -> Self {
+		Self {
+			queue,
+			cmds: Default::default(),
+			waits: Default::default(),
+		}
+	}
+}
+
+enum VulkanRecordingJob {
+	// Command lists of a batch on the given queue along with their index in the batch, the worker answers with a secondary command buffer for each.
+	Record { queue: QueueType, lists: Vec<(usize, VulkanCmdList)> },
+	// The frame that the worker's command buffers were used in has finished.
+	Recycle,
+}
+
+// A thread which records secondary command buffers for a single frame in flight. It lives as long as the context and gets its work
+// over a channel, with its own pools since pools can't be used from multiple threads at once.
+struct VulkanRecordingWorker {
+	jobs: Sender<VulkanRecordingJob>,
+	secondaries: Receiver<Vec<(usize, VulkanCommandBuffer)>>,
+	thread: JoinHandle<()>,
+}
+
+impl VulkanRecordingWorker {
+	fn new(device: &VulkanDevice, name: String) -> Self {
+		let (jobs, job_receiver) = mpsc::channel();
+		let (secondary_sender, secondaries) = mpsc::channel();
+
+		let device = device.clone();
+		let thread = thread::Builder::new()
+			.name(name)
+			.spawn(move || Self::run(device, job_receiver, secondary_sender))
+			.expect("Failed to spawn command recording thread!");
+
+		Self { jobs, secondaries, thread }
+	}
+
+	fn run(device: VulkanDevice, jobs: Receiver<VulkanRecordingJob>, secondaries: Sender<Vec<(usize, VulkanCommandBuffer)>>) {
+		let mut graphics_pool = device.create_command_pool(QueueType::GRAPHICS);
+		let mut compute_pool = device.create_command_pool(QueueType::COMPUTE);
+
+		// The worker owns the other end of the channel, so this stops once it is destroyed.
+		while let Ok(job) = jobs.recv() {
+			match job {
+				VulkanRecordingJob::Record { queue, lists } => {
+					let pool = match queue {
+						QueueType::GRAPHICS => &mut graphics_pool,
+						QueueType::COMPUTE => &mut compute_pool,
+					};
+
+					let recorded = lists
+						.into_iter()
+						.map(|(i, list)| {
+							let secondary = pool.begin_secondary_command_buffer(&device, list.render_pass);
+							VulkanGraphicsContext::fill_raster_cmds(&device.raw, secondary, list.cmds);
+							pool.end_secondary_command_buffer(&device, secondary);
+							(i, secondary)
+						})
+						.collect::<Vec<_>>();
+
+					if secondaries.send(recorded).is_err() {
+						break;
+					}
+				}
+				VulkanRecordingJob::Recycle => {
+					graphics_pool.recycle(&device);
+					compute_pool.recycle(&device);
+				}
+			}
+		}
+
+		device.destroy_command_pool(graphics_pool);
+		device.destroy_command_pool(compute_pool);
+	}
+
+	fn destroy(self) {
+		drop(self.jobs);
+		if self.thread.join().is_err() {
+			println!("WARNING: Command recording thread panicked, its command pools are leaked!");
+		}
+	}
+}
+
+// Command pools and semaphores for every batch of a frame except the last one, which is submitted through the output,
+// and the workers that record the secondary command buffers of the frame.
+struct VulkanQueueFrame {
+	graphics_command_pool: VulkanCommandPool,
+	compute_command_pool: VulkanCommandPool,
+	workers: Vec<VulkanRecordingWorker>,
+	semaphores: Vec<VulkanSemaphore>,
+}
+
+impl VulkanQueueFrame {
+	fn new(device: &VulkanDevice, frame_index: usize, workers: usize) -> Self {
+		Self {
+			graphics_command_pool: device.create_command_pool(QueueType::GRAPHICS),
+			compute_command_pool: device.create_command_pool(QueueType::COMPUTE),
+			workers: (0..workers).map(|i| VulkanRecordingWorker::new(device, format!("Command Recording {}.{}", frame_index, i))).collect(),
+			semaphores: Default::default(),
+		}
+	}
+
+	fn recycle(&mut self, device: &VulkanDevice) {
+		self.graphics_command_pool.recycle(device);
+		self.compute_command_pool.recycle(device);
+
+		// Jobs are handled in order, so the pools are reset before anything of the new frame gets recorded into them.
+		for worker in self.workers.iter() {
+			worker.jobs.send(VulkanRecordingJob::Recycle).expect("Command recording thread panicked!");
+		}
+	}
+
+	fn destroy(self, device: &VulkanDevice) {
+		device.destroy_command_pool(self.graphics_command_pool);
+		device.destroy_command_pool(self.compute_command_pool);
+
+		for worker in self.workers.into_iter() {
+			worker.destroy();
+		}
+
+		for semaphore in self.semaphores.into_iter() {
+			device.destroy_semaphore(semaphore);
+		}
 	}
 }
 
 pub struct VulkanGraphicsContext {
-	swapchain: VulkanSwapchain,
+	output: VulkanOutput,
 	current_frame_info: Option<FrameInfo>,
-	raster_cmds: RefCell<Vec<VulkanRasterCmd>>,
+	batches: RefCell<Vec<VulkanQueueBatch>>,
+	queue_frames: Vec<VulkanQueueFrame>,
 	frame_id: FrameId,
 }
 
@@ -155,42 +382,259 @@ impl From<ClearValue> for vk::ClearValue {
 }
 
 impl VulkanGraphicsContext {
-	pub fn begin_frame(&mut self, window: &Window) -> Result<(), SwapchainError> {
+	const MAX_RECORDING_THREADS: usize = 8;
+
+	fn new(output: VulkanOutput) -> Self {
+		let workers = thread::available_parallelism().map_or(1, |n| n.get()).min(Self::MAX_RECORDING_THREADS);
+		let queue_frames = (0..VulkanSwapchain::MAX_FRAMES_IN_FLIGHT)
+			.map(|i| VulkanQueueFrame::new(output.device(), i, workers))
+			.collect::<Vec<_>>();
+
+		Self {
+			output,
+			current_frame_info: None,
+			batches: RefCell::new(vec![VulkanQueueBatch::new(QueueType::GRAPHICS)]),
+			queue_frames,
+			frame_id: FrameId(0),
+		}
+	}
+
+	pub fn begin_frame(&mut self, output_size: Size) -> Result<(), SwapchainError> {
 		assert!(self.current_frame_info.is_none(), "Did not call end_frame before starting another frame!");
 
 		self.frame_id.incr();
-		match self.swapchain.acquire() {
+		match self.output.acquire() {
 			Ok(res) => {
+				// The output waited for this frame to complete, which includes every batch that was submitted before it.
+				let device = self.output.device().clone();
+				self.queue_frames[res.frame_index].recycle(&device);
+
 				self.current_frame_info = Some(res);
 
 				Ok(())
 			}
 			Err(err) => {
-				self.swapchain.invalidate(window.get_size());
+				self.output.invalidate(output_size);
 				Err(err)
 			}
 		}
 	}
 
-	pub fn end_frame(&mut self, window: &Window) {
+	pub fn end_frame(&mut self, output_size: Size) {
 		if let Some(current_frame_info) = self.current_frame_info.take() {
-			self.fill_raster_cmds(current_frame_info.command_buffer);
-			if let Err(_) = self.swapchain.submit(current_frame_info.image_index, current_frame_info.command_buffer) {
-				self.swapchain.invalidate(window.get_size());
+			let wait_semaphores = self.submit_queue_batches(&current_frame_info);
+			if let Err(_) = self.output.submit(current_frame_info.image_index, current_frame_info.command_buffer, &wait_semaphores) {
+				self.output.invalidate(output_size);
 			}
 		} else {
 			panic!("Did not call begin_frame first!");
 		}
 	}
 
+	// Submits every batch of the frame except for the last one and records the last one into the frame's command buffer.
+	// Returns the semaphores that the last batch needs to wait on.
+	fn submit_queue_batches(&mut self, frame_info: &FrameInfo) -> Vec<(vk::Semaphore, vk::PipelineStageFlags)> {
+		tracy::span!();
+		let device = self.output.device().clone();
+
+		let mut batches = self.batches.replace(vec![VulkanQueueBatch::new(QueueType::GRAPHICS)]);
+		let output_batch = batches.pop().unwrap();
+		assert!(output_batch.queue == QueueType::GRAPHICS, "The last batch of a frame has to be on the graphics queue!");
+
+		let mut waits = batches.iter().chain(std::iter::once(&output_batch)).map(|batch| batch.waits.clone()).collect::<Vec<_>>();
+
+		// The frame fence only covers the graphics queue, so make sure that the last compute batch is done by the end of the frame too.
+		if let Some(last_compute) = batches.iter().rposition(|b| b.queue == QueueType::COMPUTE) {
+			if !waits.iter().flatten().any(|(b, _)| *b == last_compute) {
+				waits.last_mut().unwrap().push((last_compute, vk::PipelineStageFlags::ALL_COMMANDS));
+			}
+		}
+
+		let queue_frame = &mut self.queue_frames[frame_info.frame_index];
+		let wait_count = waits.iter().map(|w| w.len()).sum::<usize>();
+		while queue_frame.semaphores.len() < wait_count {
+			queue_frame.semaphores.push(device.create_semaphore());
+		}
+
+		// NOTE: Binary semaphores can only be waited on once, so every wait gets its own semaphore which the waited on batch signals.
+		let mut semaphores = queue_frame.semaphores.iter().map(|s| s.raw);
+		let mut signals = vec![Vec::new(); waits.len()];
+		let mut waits = waits
+			.into_iter()
+			.map(|batch_waits| {
+				batch_waits
+					.into_iter()
+					.map(|(b, stage)| {
+						let semaphore = semaphores.next().unwrap();
+						signals[b].push(semaphore);
+						(semaphore, stage)
+					})
+					.collect::<Vec<_>>()
+			})
+			.collect::<Vec<_>>();
+
+		for (i, batch) in batches.into_iter().enumerate() {
+			let command_pool = match batch.queue {
+				QueueType::GRAPHICS => &mut queue_frame.graphics_command_pool,
+				QueueType::COMPUTE => &mut queue_frame.compute_command_pool,
+			};
+
+			let command_buffer = command_pool.begin_command_buffer(&device);
+			Self::record_queue_batch(&device, command_buffer, batch.queue, batch.cmds, &queue_frame.workers);
+			command_pool.end_command_buffer(&device, command_buffer);
+
+			device.queue_submit(batch.queue, command_buffer, &waits[i], &signals[i], None);
+		}
+
+		Self::record_queue_batch(&device, frame_info.command_buffer, QueueType::GRAPHICS, output_batch.cmds, &queue_frame.workers);
+
+		waits.pop().unwrap()
+	}
+
+	// Records every command list of a batch into a secondary command buffer in parallel on the frame's workers,
+	// and then stitches them together with the render pass commands in the primary `command_buffer`. Only the Vulkan
+	// recording happens on the workers, the commands themselves were already queued on the thread that owns the context.
+	fn record_queue_batch(device: &VulkanDevice, command_buffer: VulkanCommandBuffer, queue: QueueType, cmds: Vec<VulkanBatchCmd>, workers: &[VulkanRecordingWorker]) {
+		tracy::span!();
+
+		let mut primary_cmds = Vec::with_capacity(cmds.len());
+		let mut jobs = (0..workers.len()).map(|_| Vec::new()).collect::<Vec<_>>();
+		let mut list_count = 0;
+		for (i, cmd) in cmds.into_iter().enumerate() {
+			match cmd {
+				VulkanBatchCmd::Primary(cmd) => primary_cmds.push(Some(cmd)),
+				VulkanBatchCmd::Secondary(list) => {
+					primary_cmds.push(None);
+					if !list.cmds.is_empty() {
+						jobs[list_count % workers.len()].push((i, list));
+						list_count += 1;
+					}
+				}
+			}
+		}
+
+		let busy_workers = jobs
+			.into_iter()
+			.zip(workers.iter())
+			.filter(|(lists, _)| !lists.is_empty())
+			.map(|(lists, worker)| {
+				worker.jobs.send(VulkanRecordingJob::Record { queue, lists }).expect("Command recording thread panicked!");
+				worker
+			})
+			.collect::<Vec<_>>();
+
+		let mut secondaries = vec![None; primary_cmds.len()];
+		for worker in busy_workers.into_iter() {
+			for (i, secondary) in worker.secondaries.recv().expect("Command recording thread panicked!").into_iter() {
+				secondaries[i] = Some(secondary);
+			}
+		}
+
+		// Consecutive secondaries are executed together, everything else is replayed directly into the primary.
+		let mut pending = Vec::new();
+		for (cmd, secondary) in primary_cmds.into_iter().zip(secondaries.into_iter()) {
+			if let Some(secondary) = secondary {
+				pending.push(secondary);
+			}
+
+			if let Some(cmd) = cmd {
+				Self::execute_secondaries(&device.raw, command_buffer, &mut pending);
+				Self::fill_raster_cmds(&device.raw, command_buffer, vec![cmd]);
+			}
+		}
+		Self::execute_secondaries(&device.raw, command_buffer, &mut pending);
+	}
+
+	fn execute_secondaries(raw: &ash::Device, command_buffer: VulkanCommandBuffer, secondaries: &mut Vec<VulkanCommandBuffer>) {
+		if !secondaries.is_empty() {
+			unsafe {
+				raw.cmd_execute_commands(command_buffer, secondaries);
+			}
+			secondaries.clear();
+		}
+	}
+
 	pub fn queue_raster_cmd(&self, cmd: VulkanRasterCmd) {
-		self.raster_cmds.borrow_mut().push(cmd);
+		let mut batches = self.batches.borrow_mut();
+		let cmds = &mut batches.last_mut().unwrap().cmds;
+
+		match cmd {
+			VulkanRasterCmd::BeginRenderPass { render_pass, framebuffer, .. } => {
+				cmds.push(VulkanBatchCmd::Primary(cmd));
+				cmds.push(VulkanBatchCmd::Secondary(VulkanCmdList {
+					render_pass: Some((render_pass, framebuffer)),
+					cmds: Default::default(),
+				}));
+			}
+			VulkanRasterCmd::EndRenderPass {} => {
+				cmds.push(VulkanBatchCmd::Primary(cmd));
+				cmds.push(VulkanBatchCmd::Secondary(VulkanCmdList {
+					render_pass: None,
+					cmds: Default::default(),
+				}));
+			}
+			_ => match cmds.last_mut() {
+				Some(VulkanBatchCmd::Secondary(list)) => list.cmds.push(cmd),
+				_ => cmds.push(VulkanBatchCmd::Secondary(VulkanCmdList { render_pass: None, cmds: vec![cmd] })),
+			},
+		}
+	}
+
+	// Commands queued from here on are recorded into a separate secondary command buffer, which can happen on a different thread
+	// than the commands before it. Should be called whenever independent work starts, e.g. at the beginning of every pass.
+	pub fn begin_cmd_list(&self) {
+		let mut batches = self.batches.borrow_mut();
+		let cmds = &mut batches.last_mut().unwrap().cmds;
+
+		let render_pass = match cmds.last() {
+			Some(VulkanBatchCmd::Secondary(list)) if list.cmds.is_empty() => return,
+			Some(VulkanBatchCmd::Secondary(list)) => list.render_pass,
+			_ => None,
+		};
+
+		cmds.push(VulkanBatchCmd::Secondary(VulkanCmdList {
+			render_pass,
+			cmds: Default::default(),
+		}));
+	}
+
+	// Whether work can be moved onto a compute queue which runs in parallel with the graphics queue.
+	pub fn has_async_compute(&self) -> bool {
+		self.output.device().has_async_compute()
+	}
+
+	// Index and queue of the batch that commands are currently recorded into.
+	pub fn current_queue_batch(&self) -> (usize, QueueType) {
+		let batches = self.batches.borrow();
+		(batches.len() - 1, batches.last().unwrap().queue)
+	}
+
+	// Starts recording commands for `queue`, commands recorded from here on are submitted separately from everything before them.
+	// Returns the index of the new batch.
+	pub fn begin_queue_batch(&self, queue: QueueType) -> usize {
+		assert!(queue == QueueType::GRAPHICS || self.has_async_compute(), "The device does not have a separate compute queue!");
+
+		let mut batches = self.batches.borrow_mut();
+		batches.push(VulkanQueueBatch::new(queue));
+		batches.len() - 1
+	}
+
+	// Makes the current batch wait for an earlier batch on the other queue before `stage` of the current batch starts.
+	pub fn wait_for_queue_batch(&self, batch: usize, stage: vk::PipelineStageFlags) {
+		let mut batches = self.batches.borrow_mut();
+		let current = batches.len() - 1;
+		assert!(batch < current, "Can only wait for earlier batches!");
+		assert!(batches[batch].queue != batches[current].queue, "Can only wait for batches on the other queue!");
+
+		match batches[current].waits.iter_mut().find(|(b, _)| *b == batch) {
+			Some((_, wait_stage)) => *wait_stage |= stage,
+			None => batches[current].waits.push((batch, stage)),
+		}
 	}
 
-	fn fill_raster_cmds(&self, cmd_buf: VulkanCommandBuffer) {
+	fn fill_raster_cmds(raw: &ash::Device, cmd_buf: VulkanCommandBuffer, cmds: Vec<VulkanRasterCmd>) {
 		tracy::span!();
-		let raw = self.raw_device();
-		self.raster_cmds.take().into_iter().for_each(|cmd| unsafe {
+		cmds.into_iter().for_each(|cmd| unsafe {
 			match cmd {
 				VulkanRasterCmd::BindPipeline { bind_point, pipeline } => raw.cmd_bind_pipeline(cmd_buf, bind_point, pipeline),
 				VulkanRasterCmd::BindVertexBuffer { first_binding, buffer, offset } => {
@@ -276,34 +720,51 @@ impl VulkanGraphicsContext {
 	pub fn begin_output_render_pass(&self, clear_values: &[ClearValue]) {
 		tracy::span!();
 
+		// NOTE: Dynamic state isn't inherited by secondary command buffers, so the render pass has to begin before the viewport and scissor are set.
+		self.queue_raster_cmd(VulkanRasterCmd::BeginRenderPass {
+			render_pass: self.output.render_pass(),
+			framebuffer: self.get_output_framebuffer(),
+			render_area: vk::Rect2D {
+				offset: vk::Offset2D { x: 0, y: 0 },
+				extent: self.output.extent(),
+			},
+			clear_values: clear_values.iter().map(|&c| c.into()).collect::<Vec<_>>(),
+			subpass_contents: vk::SubpassContents::SECONDARY_COMMAND_BUFFERS,
+		});
+
 		self.queue_raster_cmd(VulkanRasterCmd::SetViewport {
 			viewport: vk::Viewport::builder()
 				.x(0.0)
-				.y(self.swapchain.extent.height as f32)
-				.width(self.swapchain.extent.width as f32)
-				.height(-(self.swapchain.extent.height as f32))
+				.y(self.output.extent().height as f32)
+				.width(self.output.extent().width as f32)
+				.height(-(self.output.extent().height as f32))
 				.min_depth(0.0)
 				.max_depth(1.0)
 				.build(),
 		});
 
 		self.queue_raster_cmd(VulkanRasterCmd::SetScissor {
-			scissor: vk::Rect2D::builder().offset(vk::Offset2D { x: 0, y: 0 }).extent(self.swapchain.extent).build(),
+			scissor: vk::Rect2D::builder().offset(vk::Offset2D { x: 0, y: 0 }).extent(self.output.extent()).build(),
 		});
+	}
+
+	pub fn begin_render_pass(&self, render_pass: &VulkanRenderPass, framebuffer: &VulkanFramebuffer, clear_values: &[ClearValue]) {
+		let extent = vk::Extent2D {
+			width: framebuffer.width,
+			height: framebuffer.height,
+		};
 
 		self.queue_raster_cmd(VulkanRasterCmd::BeginRenderPass {
-			render_pass: self.swapchain.render_pass,
-			framebuffer: self.get_output_framebuffer(),
+			render_pass: render_pass.raw,
+			framebuffer: framebuffer.raw,
 			render_area: vk::Rect2D {
 				offset: vk::Offset2D { x: 0, y: 0 },
-				extent: self.swapchain.extent,
+				extent,
 			},
 			clear_values: clear_values.iter().map(|&c| c.into()).collect::<Vec<_>>(),
-			subpass_contents: vk::SubpassContents::INLINE,
+			subpass_contents: vk::SubpassContents::SECONDARY_COMMAND_BUFFERS,
 		});
-	}
 
-	pub fn begin_render_pass(&self, render_pass: &VulkanRenderPass, framebuffer: &VulkanFramebuffer, clear_values: &[ClearValue]) {
 		self.queue_raster_cmd(VulkanRasterCmd::SetViewport {
 			viewport: vk::Viewport::builder()
 				.x(0.0)
@@ -315,25 +776,9 @@ impl VulkanGraphicsContext {
 				.build(),
 		});
 
-		let extent = vk::Extent2D {
-			width: framebuffer.width,
-			height: framebuffer.height,
-		};
-
 		self.queue_raster_cmd(VulkanRasterCmd::SetScissor {
 			scissor: vk::Rect2D::builder().offset(vk::Offset2D { x: 0, y: 0 }).extent(extent).build(),
 		});
-
-		self.queue_raster_cmd(VulkanRasterCmd::BeginRenderPass {
-			render_pass: render_pass.raw,
-			framebuffer: framebuffer.raw,
-			render_area: vk::Rect2D {
-				offset: vk::Offset2D { x: 0, y: 0 },
-				extent,
-			},
-			clear_values: clear_values.iter().map(|&c| c.into()).collect::<Vec<_>>(),
-			subpass_contents: vk::SubpassContents::INLINE,
-		});
 	}
 
 	pub fn end_render_pass(&self) {
@@ -345,16 +790,41 @@ impl VulkanGraphicsContext {
 	}
 
 	pub fn raw_device(&self) -> &ash::Device {
-		self.swapchain.raw_device()
+		self.output.raw_device()
 	}
 
 	pub fn on_resize(&mut self, framebuffer_size: Size) {
 		tracy::span!();
-		self.swapchain.invalidate(framebuffer_size);
+		self.output.invalidate(framebuffer_size);
+	}
+
+	pub fn is_headless(&self) -> bool {
+		matches!(self.output, VulkanOutput::Headless(_))
+	}
+
+	pub fn output_size(&self) -> Size {
+		let extent = self.output.extent();
+		Size {
+			width: extent.width,
+			height: extent.height,
+		}
+	}
+
+	// Reads back the RGBA8 contents of the last submitted frame. Only headless contexts own their output image.
+	pub fn read_output_image(&mut self) -> Vec<u8> {
+		match &mut self.output {
+			VulkanOutput::Headless(target) => target.read_output(),
+			VulkanOutput::Swapchain(_) => panic!("Cannot read back the output image of a windowed graphics context!"),
+		}
 	}
 
 	pub fn destroy(&mut self) {
-		self.swapchain.destroy();
+		self.output.destroy();
+
+		let device = self.output.device().clone();
+		for queue_frame in std::mem::take(&mut self.queue_frames).into_iter() {
+			queue_frame.destroy(&device);
+		}
 	}
 	pub fn create_raster_pipeline(
 		&mut self,
@@ -367,12 +837,15 @@ impl VulkanGraphicsContext {
 		push_constant_bytes: usize,
 		vertex_input_info: VertexInputInfo,
 		polygon_mode: PolygonMode,
+		blend_states: &[ColorBlendState],
+		stencil: Option<StencilState>,
+		pipeline_cache: &mut VulkanPipelineCache,
 	) -> VulkanPipeline {
-		self.swapchain.device.create_raster_pipeline_impl(
+		self.output.device().create_raster_pipeline_impl(
 			vs,
 			ps,
 			descriptor_layouts,
-			self.swapchain.render_pass,
+			self.output.render_pass(),
 			1usize,
 			depth_compare_op,
 			depth_write,
@@ -380,6 +853,10 @@ impl VulkanGraphicsContext {
 			push_constant_bytes,
 			vertex_input_info,
 			polygon_mode,
+			blend_states,
+			stencil,
+			SampleCount::X1,
+			pipeline_cache,
 		)
 	}
 
@@ -429,7 +906,8 @@ impl VulkanGraphicsContext {
 	pub fn update_descriptor(
 		&mut self,
 		buffers: &[(u32, &VulkanBuffer)],
-		images: &[(u32, &VulkanTexture, ImageLayout)],
+		images: &[(u32, &VulkanTexture, TextureAspect, Option<TextureSubresource>, ImageLayout)],
+		samplers: &[(u32, VulkanSampler)],
 		descriptor_layout: &'static DescriptorSetInfo,
 		descriptor_heap: &VulkanDescriptorHeap,
 		descriptor_set: &VulkanDescriptorHandle,
@@ -444,15 +922,17 @@ impl VulkanGraphicsContext {
 
 		let image_infos = images
 			.iter()
-			.map(|(_, image, layout)| {
+			.map(|(_, image, aspect, subresource, layout)| {
 				vk::DescriptorImageInfo::builder()
-					.image_view(image.image_view)
+					.image_view(image.view(*aspect, *subresource))
 					.sampler(image.sampler)
 					.image_layout((*layout).into())
 					.build()
 			})
 			.collect::<Vec<_>>();
 
+		let sampler_infos = samplers.iter().map(|(_, sampler)| vk::DescriptorImageInfo::builder().sampler(sampler.raw).build()).collect::<Vec<_>>();
+
 		unsafe {
 			self.raw_device().update_descriptor_sets(
 				&buffers
@@ -466,7 +946,7 @@ impl VulkanGraphicsContext {
 							.buffer_info(&buffer_infos[i..=i])
 							.build()
 					})
-					.chain(images.iter().enumerate().map(|(i, (binding, _, _))| {
+					.chain(images.iter().enumerate().map(|(i, (binding, ..))| {
 						vk::WriteDescriptorSet::builder()
 							.dst_set(descriptor)
 							.dst_binding(*binding)
@@ -474,6 +954,14 @@ impl VulkanGraphicsContext {
 							.image_info(&image_infos[i..=i])
 							.build()
 					}))
+					.chain(samplers.iter().enumerate().map(|(i, (binding, _))| {
+						vk::WriteDescriptorSet::builder()
+							.dst_set(descriptor)
+							.dst_binding(*binding)
+							.descriptor_type((*descriptor_layout.bindings.get(&binding).unwrap()).into())
+							.image_info(&sampler_infos[i..=i])
+							.build()
+					}))
 					.collect::<Vec<_>>(),
 				&[],
 			)
@@ -559,7 +1047,16 @@ impl TextureFormat {
 			TextureFormat::RG32Float => vk::Format::R32G32_SFLOAT,
 			TextureFormat::RGB32Float => vk::Format::R32G32B32_SFLOAT,
 			TextureFormat::RGBA32Float => vk::Format::R32G32B32A32_SFLOAT,
+			TextureFormat::BC1RGBAUNorm => vk::Format::BC1_RGBA_UNORM_BLOCK,
+			TextureFormat::BC1SRGBA => vk::Format::BC1_RGBA_SRGB_BLOCK,
+			TextureFormat::BC3RGBAUNorm => vk::Format::BC3_UNORM_BLOCK,
+			TextureFormat::BC3SRGBA => vk::Format::BC3_SRGB_BLOCK,
+			TextureFormat::BC4RUNorm => vk::Format::BC4_UNORM_BLOCK,
+			TextureFormat::BC5RGUNorm => vk::Format::BC5_UNORM_BLOCK,
+			TextureFormat::BC7RGBAUNorm => vk::Format::BC7_UNORM_BLOCK,
+			TextureFormat::BC7SRGBA => vk::Format::BC7_SRGB_BLOCK,
 			TextureFormat::Depth => device.depth_format,
+			TextureFormat::DepthStencil => device.depth_stencil_format,
 		}
 	}
 }
@@ -3,15 +3,20 @@ use super::{
 	{
 		descriptor::VulkanDescriptorLayout,
 		device::{VulkanDestructor, VulkanDevice},
+		pipeline_cache::VulkanPipelineCache,
 		render_pass::VulkanRenderPass,
 		shader::VulkanShader,
 		swapchain::VulkanSwapchain,
 	},
 };
-use crate::renderer::{DepthCompareOp, FaceCullMode, PolygonMode, Vertex, VertexAttributeDescriptionBinding, VertexAttributeFormat, VertexInputInfo, CS_MAIN, PS_MAIN, VS_MAIN};
+use crate::renderer::{
+	BlendFactor, BlendMode, BlendOp, ColorBlendState, ColorWriteMask, DepthCompareOp, FaceCullMode, PolygonMode, SampleCount, StencilFaceState, StencilOp, StencilState, Vertex,
+	VertexAttributeDescriptionBinding, VertexAttributeFormat, VertexInputInfo, CS_MAIN, PS_MAIN, VS_MAIN,
+};
 use ash::vk;
 use std::collections::{hash_map::Entry, HashMap};
 use std::ffi::CString;
+use std::time::Instant;
 
 use tracy_client as tracy;
 
@@ -81,6 +86,128 @@ impl From<DepthCompareOp> for vk::CompareOp {
 	}
 }
 
+impl From<StencilOp> for vk::StencilOp {
+	fn from(o: StencilOp) -> Self {
+		match o {
+			StencilOp::Keep => vk::StencilOp::KEEP,
+			StencilOp::Zero => vk::StencilOp::ZERO,
+			StencilOp::Replace => vk::StencilOp::REPLACE,
+			StencilOp::IncrementAndClamp => vk::StencilOp::INCREMENT_AND_CLAMP,
+			StencilOp::DecrementAndClamp => vk::StencilOp::DECREMENT_AND_CLAMP,
+			StencilOp::Invert => vk::StencilOp::INVERT,
+			StencilOp::IncrementAndWrap => vk::StencilOp::INCREMENT_AND_WRAP,
+			StencilOp::DecrementAndWrap => vk::StencilOp::DECREMENT_AND_WRAP,
+		}
+	}
+}
+
+impl From<StencilFaceState> for vk::StencilOpState {
+	fn from(s: StencilFaceState) -> Self {
+		Self {
+			fail_op: s.fail_op.into(),
+			pass_op: s.pass_op.into(),
+			depth_fail_op: s.depth_fail_op.into(),
+			compare_op: s.compare_op.into(),
+			compare_mask: s.compare_mask,
+			write_mask: s.write_mask,
+			reference: s.reference,
+		}
+	}
+}
+
+impl From<BlendFactor> for vk::BlendFactor {
+	fn from(f: BlendFactor) -> Self {
+		match f {
+			BlendFactor::Zero => vk::BlendFactor::ZERO,
+			BlendFactor::One => vk::BlendFactor::ONE,
+			BlendFactor::SrcColor => vk::BlendFactor::SRC_COLOR,
+			BlendFactor::OneMinusSrcColor => vk::BlendFactor::ONE_MINUS_SRC_COLOR,
+			BlendFactor::DstColor => vk::BlendFactor::DST_COLOR,
+			BlendFactor::OneMinusDstColor => vk::BlendFactor::ONE_MINUS_DST_COLOR,
+			BlendFactor::SrcAlpha => vk::BlendFactor::SRC_ALPHA,
+			BlendFactor::OneMinusSrcAlpha => vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+			BlendFactor::DstAlpha => vk::BlendFactor::DST_ALPHA,
+			BlendFactor::OneMinusDstAlpha => vk::BlendFactor::ONE_MINUS_DST_ALPHA,
+			BlendFactor::SrcAlphaSaturate => vk::BlendFactor::SRC_ALPHA_SATURATE,
+		}
+	}
+}
+
+impl From<BlendOp> for vk::BlendOp {
+	fn from(o: BlendOp) -> Self {
+		match o {
+			BlendOp::Add => vk::BlendOp::ADD,
+			BlendOp::Subtract => vk::BlendOp::SUBTRACT,
+			BlendOp::ReverseSubtract => vk::BlendOp::REVERSE_SUBTRACT,
+			BlendOp::Min => vk::BlendOp::MIN,
+			BlendOp::Max => vk::BlendOp::MAX,
+		}
+	}
+}
+
+impl From<ColorWriteMask> for vk::ColorComponentFlags {
+	fn from(m: ColorWriteMask) -> Self {
+		let mut flags = vk::ColorComponentFlags::empty();
+		if m.contains(ColorWriteMask::R) {
+			flags |= vk::ColorComponentFlags::R;
+		}
+		if m.contains(ColorWriteMask::G) {
+			flags |= vk::ColorComponentFlags::G;
+		}
+		if m.contains(ColorWriteMask::B) {
+			flags |= vk::ColorComponentFlags::B;
+		}
+		if m.contains(ColorWriteMask::A) {
+			flags |= vk::ColorComponentFlags::A;
+		}
+		flags
+	}
+}
+
+impl From<ColorBlendState> for vk::PipelineColorBlendAttachmentState {
+	fn from(b: ColorBlendState) -> Self {
+		let (src_color, dst_color, color_op, src_alpha, dst_alpha, alpha_op) = match b.mode {
+			BlendMode::Opaque => (BlendFactor::One, BlendFactor::Zero, BlendOp::Add, BlendFactor::One, BlendFactor::Zero, BlendOp::Add),
+			BlendMode::Alpha => (
+				BlendFactor::SrcAlpha,
+				BlendFactor::OneMinusSrcAlpha,
+				BlendOp::Add,
+				BlendFactor::One,
+				BlendFactor::OneMinusSrcAlpha,
+				BlendOp::Add,
+			),
+			BlendMode::Premultiplied => (
+				BlendFactor::One,
+				BlendFactor::OneMinusSrcAlpha,
+				BlendOp::Add,
+				BlendFactor::One,
+				BlendFactor::OneMinusSrcAlpha,
+				BlendOp::Add,
+			),
+			BlendMode::Additive => (BlendFactor::SrcAlpha, BlendFactor::One, BlendOp::Add, BlendFactor::One, BlendFactor::One, BlendOp::Add),
+			BlendMode::Custom {
+				src_color,
+				dst_color,
+				color_op,
+				src_alpha,
+				dst_alpha,
+				alpha_op,
+			} => (src_color, dst_color, color_op, src_alpha, dst_alpha, alpha_op),
+		};
+
+		Self {
+			blend_enable: if b.mode != BlendMode::Opaque { 1 } else { 0 },
+			src_color_blend_factor: src_color.into(),
+			dst_color_blend_factor: dst_color.into(),
+			color_blend_op: color_op.into(),
+			src_alpha_blend_factor: src_alpha.into(),
+			dst_alpha_blend_factor: dst_alpha.into(),
+			alpha_blend_op: alpha_op.into(),
+			color_write_mask: b.write_mask.into(),
+		}
+	}
+}
+
 impl VulkanDevice {
 	pub fn create_raster_pipeline(
 		&self,
@@ -94,6 +221,10 @@ impl VulkanDevice {
 		push_constant_bytes: usize,
 		vertex_input_info: VertexInputInfo,
 		polygon_mode: PolygonMode,
+		blend_states: &[ColorBlendState],
+		stencil: Option<StencilState>,
+		samples: SampleCount,
+		pipeline_cache: &mut VulkanPipelineCache,
 	) -> VulkanPipeline {
 		self.create_raster_pipeline_impl(
 			vs,
@@ -107,6 +238,10 @@ impl VulkanDevice {
 			push_constant_bytes,
 			vertex_input_info,
 			polygon_mode,
+			blend_states,
+			stencil,
+			samples,
+			pipeline_cache,
 		)
 	}
 
@@ -123,6 +258,10 @@ impl VulkanDevice {
 		push_constant_bytes: usize,
 		vertex_input_info: VertexInputInfo,
 		polygon_mode: PolygonMode,
+		blend_states: &[ColorBlendState],
+		stencil: Option<StencilState>,
+		samples: SampleCount,
+		pipeline_cache: &mut VulkanPipelineCache,
 	) -> VulkanPipeline {
 		let mut layout_create_info = vk::PipelineLayoutCreateInfo::builder().set_layouts(descriptor_layouts);
 
@@ -179,15 +318,7 @@ impl VulkanDevice {
 		};
 
 		let multisample_state_info = vk::PipelineMultisampleStateCreateInfo {
-			rasterization_samples: vk::SampleCountFlags::TYPE_1,
-			..Default::default()
-		};
-
-		let noop_stencil_state = vk::StencilOpState {
-			fail_op: vk::StencilOp::KEEP,
-			pass_op: vk::StencilOp::KEEP,
-			depth_fail_op: vk::StencilOp::KEEP,
-			compare_op: vk::CompareOp::ALWAYS,
+			rasterization_samples: samples.into(),
 			..Default::default()
 		};
 
@@ -196,32 +327,28 @@ impl VulkanDevice {
 			depth_write_enable: if depth_write { 1 } else { 0 },
 			depth_compare_op: depth_compare_op.map_or(vk::CompareOp::default(), |c| c.into()),
 			depth_bounds_test_enable: 0,
-			stencil_test_enable: 0,
-			// front: noop_stencil_state,
-			// back: noop_stencil_state,
+			stencil_test_enable: if stencil.is_some() { 1 } else { 0 },
+			front: stencil.map_or(vk::StencilOpState::default(), |s| s.front.into()),
+			back: stencil.map_or(vk::StencilOpState::default(), |s| s.back.into()),
 			..Default::default()
 		};
 
-		let color_blend_attachment_states = vec![
-			vk::PipelineColorBlendAttachmentState {
-				blend_enable: 0,
-				src_color_blend_factor: vk::BlendFactor::SRC_COLOR,
-				dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_DST_COLOR,
-				color_blend_op: vk::BlendOp::ADD,
-				src_alpha_blend_factor: vk::BlendFactor::ZERO,
-				dst_alpha_blend_factor: vk::BlendFactor::ZERO,
-				alpha_blend_op: vk::BlendOp::ADD,
-				color_write_mask: vk::ColorComponentFlags::R | vk::ColorComponentFlags::G | vk::ColorComponentFlags::B | vk::ColorComponentFlags::A,
-			};
-			color_attachments_count
-		];
+		let color_blend_attachment_states = (0..color_attachments_count)
+			.map(|i| blend_states.get(i).copied().unwrap_or_default().into())
+			.collect::<Vec<vk::PipelineColorBlendAttachmentState>>();
 
 		let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder().attachments(&color_blend_attachment_states);
 
 		let dynamic_state = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
 		let dynamic_state_info = vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_state);
 
-		let graphics_pipeline_info = vk::GraphicsPipelineCreateInfo::builder()
+		let mut feedback = vk::PipelineCreationFeedback::default();
+		let mut stage_feedbacks = vec![vk::PipelineCreationFeedback::default(); shader_stage_infos.len()];
+		let mut feedback_info = vk::PipelineCreationFeedbackCreateInfo::builder()
+			.pipeline_creation_feedback(&mut feedback)
+			.pipeline_stage_creation_feedbacks(&mut stage_feedbacks);
+
+		let mut graphics_pipeline_info = vk::GraphicsPipelineCreateInfo::builder()
 			.stages(&shader_stage_infos)
 			.vertex_input_state(&vertex_input_state_info)
 			.input_assembly_state(&vertex_input_assembly_state_info)
@@ -234,16 +361,22 @@ impl VulkanDevice {
 			.layout(pipeline_layout)
 			.render_pass(render_pass);
 
+		if self.pipeline_creation_feedback {
+			graphics_pipeline_info = graphics_pipeline_info.push_next(&mut feedback_info);
+		}
+
+		let start = Instant::now();
 		let pipeline = unsafe {
 			self.raw
-				.create_graphics_pipelines(vk::PipelineCache::null(), &[graphics_pipeline_info.build()], None)
+				.create_graphics_pipelines(pipeline_cache.raw, &[graphics_pipeline_info.build()], None)
 				.expect("Failed to create graphics pipeline!")
 		}[0];
+		pipeline_cache.record_creation(start.elapsed(), self.pipeline_creation_feedback.then_some(feedback));
 
 		VulkanPipeline { pipeline, pipeline_layout }
 	}
 
-	pub fn create_compute_pipeline(&self, cs: &VulkanShader, descriptor_layouts: &[VulkanDescriptorLayout]) -> VulkanPipeline {
+	pub fn create_compute_pipeline(&self, cs: &VulkanShader, descriptor_layouts: &[VulkanDescriptorLayout], pipeline_cache: &mut VulkanPipelineCache) -> VulkanPipeline {
 		let layout_create_info = vk::PipelineLayoutCreateInfo::builder().set_layouts(descriptor_layouts);
 
 		let pipeline_layout = unsafe { self.raw.create_pipeline_layout(&layout_create_info, None).expect("Failed to create pipeline layout!") };
@@ -251,12 +384,24 @@ impl VulkanDevice {
 		let name = CString::new(CS_MAIN).unwrap();
 		let stage = vk::PipelineShaderStageCreateInfo::builder().module(cs.module).stage(vk::ShaderStageFlags::COMPUTE).name(&name);
 
-		let compute_pipeline_info = vk::ComputePipelineCreateInfo::builder().layout(pipeline_layout).stage(stage.build());
+		let mut feedback = vk::PipelineCreationFeedback::default();
+		let mut stage_feedbacks = [vk::PipelineCreationFeedback::default()];
+		let mut feedback_info = vk::PipelineCreationFeedbackCreateInfo::builder()
+			.pipeline_creation_feedback(&mut feedback)
+			.pipeline_stage_creation_feedbacks(&mut stage_feedbacks);
+
+		let mut compute_pipeline_info = vk::ComputePipelineCreateInfo::builder().layout(pipeline_layout).stage(stage.build());
+		if self.pipeline_creation_feedback {
+			compute_pipeline_info = compute_pipeline_info.push_next(&mut feedback_info);
+		}
+
+		let start = Instant::now();
 		let pipeline = unsafe {
 			self.raw
-				.create_compute_pipelines(vk::PipelineCache::null(), &[compute_pipeline_info.build()], None)
+				.create_compute_pipelines(pipeline_cache.raw, &[compute_pipeline_info.build()], None)
 				.expect("Failed to create compute pipeline!")
 		}[0];
+		pipeline_cache.record_creation(start.elapsed(), self.pipeline_creation_feedback.then_some(feedback));
 		VulkanPipeline { pipeline, pipeline_layout }
 	}
 
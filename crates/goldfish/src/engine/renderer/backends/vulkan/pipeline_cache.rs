@@ -0,0 +1,167 @@
+use super::device::VulkanDevice;
+use crate::renderer::PipelineCacheStats;
+use ash::vk;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+use tracy_client as tracy;
+
+#[derive(Error, Debug)]
+pub enum PipelineCacheError {
+	#[error("A filesystem error occurred {0}")]
+	Filesystem(std::io::Error),
+	#[error("Pipeline cache data is too small to contain a header")]
+	Truncated,
+	#[error("Unsupported pipeline cache header version {0}")]
+	UnsupportedVersion(u32),
+	#[error("Pipeline cache was written by a different device (vendor {vendor_id:#x}, device {device_id:#x})")]
+	DeviceMismatch { vendor_id: u32, device_id: u32 },
+	#[error("Pipeline cache was written by a different driver version")]
+	DriverMismatch,
+}
+
+// Backs pipeline creation with a VkPipelineCache. If `path` is set the cache is loaded from and saved to disk so that pipelines
+// don't have to be recompiled every launch. A default cache has a null handle and behaves exactly like not having a cache.
+#[derive(Default)]
+pub struct VulkanPipelineCache {
+	pub raw: vk::PipelineCache,
+	path: Option<PathBuf>,
+	stats: PipelineCacheStats,
+}
+
+impl VulkanPipelineCache {
+	// Size of VkPipelineCacheHeaderVersionOne, which every driver writes at the start of the cache data.
+	const HEADER_SIZE: usize = 16 + vk::UUID_SIZE;
+
+	pub fn get_stats(&self) -> PipelineCacheStats {
+		self.stats
+	}
+
+	pub(super) fn record_creation(&mut self, duration: Duration, feedback: Option<vk::PipelineCreationFeedback>) {
+		self.stats.pipelines_created += 1;
+		self.stats.creation_time += duration;
+
+		match feedback {
+			Some(feedback) if feedback.flags.contains(vk::PipelineCreationFeedbackFlags::VALID) => {
+				if feedback.flags.contains(vk::PipelineCreationFeedbackFlags::APPLICATION_PIPELINE_CACHE_HIT) {
+					self.stats.cache_hits += 1;
+				} else {
+					self.stats.cache_misses += 1;
+				}
+			}
+			_ => {}
+		}
+	}
+}
+
+impl VulkanDevice {
+	// Creates a pipeline cache that starts out with the contents of `path`. Data that is missing or was written by a different device or driver
+	// is discarded and the cache starts out empty instead, it gets written back to `path` when the cache is saved or destroyed.
+	pub fn load_pipeline_cache(&self, path: &Path) -> VulkanPipelineCache {
+		tracy::span!();
+		let data = match std::fs::read(path).map_err(|err| PipelineCacheError::Filesystem(err)).and_then(|data| {
+			self.validate_pipeline_cache_data(&data)?;
+			Ok(data)
+		}) {
+			Ok(data) => data,
+			Err(PipelineCacheError::Filesystem(err)) if err.kind() == std::io::ErrorKind::NotFound => vec![],
+			Err(err) => {
+				println!("Discarding pipeline cache {:?}: {}", path, err);
+				vec![]
+			}
+		};
+
+		let raw = unsafe {
+			self.raw
+				.create_pipeline_cache(&vk::PipelineCacheCreateInfo::builder().initial_data(&data), None)
+				.expect("Failed to create pipeline cache!")
+		};
+
+		VulkanPipelineCache {
+			raw,
+			path: Some(path.to_path_buf()),
+			stats: PipelineCacheStats {
+				loaded_bytes: data.len(),
+				..Default::default()
+			},
+		}
+	}
+
+	pub fn destroy_pipeline_cache(&self, cache: VulkanPipelineCache) {
+		tracy::span!();
+		if cache.raw == vk::PipelineCache::null() {
+			return;
+		}
+
+		self.save_pipeline_cache(&cache);
+
+		unsafe {
+			self.raw.destroy_pipeline_cache(cache.raw, None);
+		}
+	}
+
+	// Writes everything that the cache contains right now to its path, which lets pipelines survive a crash or a killed process.
+	// The stats are printed along with it, so that every save shows how much the cache loaded at startup actually helped.
+	pub fn save_pipeline_cache(&self, cache: &VulkanPipelineCache) {
+		tracy::span!();
+		if cache.raw == vk::PipelineCache::null() {
+			return;
+		}
+
+		if let Some(path) = &cache.path {
+			match self.write_pipeline_cache_data(cache.raw, path) {
+				Ok(()) => {
+					let stats = cache.stats;
+					println!(
+						"Saved pipeline cache {:?}: loaded {} bytes at startup, created {} pipelines in {:?} with {} cache hits and {} misses",
+						path, stats.loaded_bytes, stats.pipelines_created, stats.creation_time, stats.cache_hits, stats.cache_misses
+					);
+				}
+				Err(err) => println!("Failed to save pipeline cache {:?}: {}", path, err),
+			}
+		}
+	}
+
+	fn write_pipeline_cache_data(&self, cache: vk::PipelineCache, path: &Path) -> Result<(), PipelineCacheError> {
+		let data = unsafe { self.raw.get_pipeline_cache_data(cache).expect("Failed to get pipeline cache data!") };
+
+		if let Some(parent) = path.parent() {
+			std::fs::create_dir_all(parent).map_err(|err| PipelineCacheError::Filesystem(err))?;
+		}
+		std::fs::write(path, &data).map_err(|err| PipelineCacheError::Filesystem(err))
+	}
+
+	// Drivers are supposed to ignore incompatible data on their own, but not all of them do so check the header ourselves.
+	fn validate_pipeline_cache_data(&self, data: &[u8]) -> Result<(), PipelineCacheError> {
+		if data.len() < VulkanPipelineCache::HEADER_SIZE {
+			return Err(PipelineCacheError::Truncated);
+		}
+
+		let read_u32 = |offset: usize| u32::from_ne_bytes(data[offset..offset + 4].try_into().unwrap());
+
+		let header_size = read_u32(0) as usize;
+		let header_version = read_u32(4);
+		let vendor_id = read_u32(8);
+		let device_id = read_u32(12);
+		let uuid = &data[16..16 + vk::UUID_SIZE];
+
+		if header_size < VulkanPipelineCache::HEADER_SIZE || header_size > data.len() {
+			return Err(PipelineCacheError::Truncated);
+		}
+
+		if header_version != vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32 {
+			return Err(PipelineCacheError::UnsupportedVersion(header_version));
+		}
+
+		let properties = &self.physical_device_properties;
+		if vendor_id != properties.vendor_id || device_id != properties.device_id {
+			return Err(PipelineCacheError::DeviceMismatch { vendor_id, device_id });
+		}
+
+		if uuid != properties.pipeline_cache_uuid {
+			return Err(PipelineCacheError::DriverMismatch);
+		}
+
+		Ok(())
+	}
+}
@@ -9,6 +9,7 @@ pub struct VulkanRenderPass {
 	pub raw: vk::RenderPass,
 	pub color_attachments: Vec<AttachmentDescription>,
 	pub depth_attachment: Option<AttachmentDescription>,
+	pub resolve_attachments: Vec<AttachmentDescription>,
 }
 
 impl From<LoadOp> for vk::AttachmentLoadOp {
@@ -50,9 +51,12 @@ impl AttachmentDescription {
 	fn to_vk(&self, device: &VulkanDevice) -> vk::AttachmentDescription {
 		vk::AttachmentDescription {
 			format: self.format.to_vk(device),
-			samples: vk::SampleCountFlags::TYPE_1,
+			samples: self.samples.into(),
 			load_op: self.load_op.into(),
 			store_op: self.store_op.into(),
+			// Stencil is loaded and stored together with depth.
+			stencil_load_op: if self.format.has_stencil() { self.load_op.into() } else { vk::AttachmentLoadOp::DONT_CARE },
+			stencil_store_op: if self.format.has_stencil() { self.store_op.into() } else { vk::AttachmentStoreOp::DONT_CARE },
 			initial_layout: self.initial_layout.into(),
 			final_layout: self.final_layout.into(),
 			..Default::default()
@@ -61,11 +65,21 @@ impl AttachmentDescription {
 }
 
 impl VulkanDevice {
-	pub fn create_render_pass(&self, color_attachments: &[AttachmentDescription], depth_attachment: Option<AttachmentDescription>) -> VulkanRenderPass {
+	// `resolve_attachments` is either empty or has one single sample attachment for every multisampled color attachment, which the color
+	// attachment is resolved into at the end of the render pass. Framebuffers have to list their attachments as color, depth, then resolve.
+	pub fn create_render_pass(&self, color_attachments: &[AttachmentDescription], depth_attachment: Option<AttachmentDescription>, resolve_attachments: &[AttachmentDescription]) -> VulkanRenderPass {
+		assert!(
+			resolve_attachments.is_empty() || resolve_attachments.len() == color_attachments.len(),
+			"Render pass has {} resolve attachments but {} color attachments!",
+			resolve_attachments.len(),
+			color_attachments.len()
+		);
+
 		let render_pass_attachments = color_attachments
 			.iter()
 			.map(|desc| desc.to_vk(self))
 			.chain(depth_attachment.as_ref().map(|desc| desc.to_vk(self)))
+			.chain(resolve_attachments.iter().map(|desc| desc.to_vk(self)))
 			.collect::<Vec<_>>();
 
 		let color_attachment_refs = (0..color_attachments.len() as u32)
@@ -84,12 +98,23 @@ impl VulkanDevice {
 			},
 		};
 
+		let resolve_offset = (color_attachments.len() + depth_attachment.iter().count()) as u32;
+		let resolve_attachment_refs = (0..resolve_attachments.len() as u32)
+			.map(|i| vk::AttachmentReference {
+				attachment: resolve_offset + i,
+				layout: resolve_attachments[i as usize].final_layout.into(),
+			})
+			.collect::<Vec<_>>();
+
 		let mut subpass_description = vk::SubpassDescription::builder()
 			.color_attachments(&color_attachment_refs)
 			.pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS);
 		if depth_attachment.is_some() {
 			subpass_description = subpass_description.depth_stencil_attachment(&depth_attachment_ref);
 		}
+		if !resolve_attachments.is_empty() {
+			subpass_description = subpass_description.resolve_attachments(&resolve_attachment_refs);
+		}
 
 		let subpass_description = subpass_description.build();
 
@@ -105,6 +130,7 @@ impl VulkanDevice {
 			raw,
 			color_attachments: color_attachments.to_vec(),
 			depth_attachment,
+			resolve_attachments: resolve_attachments.to_vec(),
 		}
 	}
 
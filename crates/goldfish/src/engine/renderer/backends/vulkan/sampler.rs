@@ -0,0 +1,92 @@
+use super::device::VulkanDevice;
+use crate::renderer::{AddressMode, BorderColor, Filter, MipmapMode, SamplerDesc};
+use ash::vk;
+use tracy_client as tracy;
+
+// Samplers are owned by the device's sampler cache and live until the device is destroyed, so these can be freely copied around.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub struct VulkanSampler {
+	pub raw: vk::Sampler,
+}
+
+impl From<Filter> for vk::Filter {
+	fn from(filter: Filter) -> Self {
+		match filter {
+			Filter::Nearest => vk::Filter::NEAREST,
+			Filter::Linear => vk::Filter::LINEAR,
+		}
+	}
+}
+
+impl From<MipmapMode> for vk::SamplerMipmapMode {
+	fn from(mode: MipmapMode) -> Self {
+		match mode {
+			MipmapMode::Nearest => vk::SamplerMipmapMode::NEAREST,
+			MipmapMode::Linear => vk::SamplerMipmapMode::LINEAR,
+		}
+	}
+}
+
+impl From<AddressMode> for vk::SamplerAddressMode {
+	fn from(mode: AddressMode) -> Self {
+		match mode {
+			AddressMode::Repeat => vk::SamplerAddressMode::REPEAT,
+			AddressMode::MirroredRepeat => vk::SamplerAddressMode::MIRRORED_REPEAT,
+			AddressMode::ClampToEdge => vk::SamplerAddressMode::CLAMP_TO_EDGE,
+			AddressMode::ClampToBorder => vk::SamplerAddressMode::CLAMP_TO_BORDER,
+		}
+	}
+}
+
+impl From<BorderColor> for vk::BorderColor {
+	fn from(color: BorderColor) -> Self {
+		match color {
+			BorderColor::TransparentBlack => vk::BorderColor::FLOAT_TRANSPARENT_BLACK,
+			BorderColor::OpaqueBlack => vk::BorderColor::FLOAT_OPAQUE_BLACK,
+			BorderColor::OpaqueWhite => vk::BorderColor::FLOAT_OPAQUE_WHITE,
+		}
+	}
+}
+
+impl VulkanDevice {
+	// Returns the sampler for `desc`, creating it the first time it is asked for.
+	pub fn get_sampler(&self, desc: &SamplerDesc) -> VulkanSampler {
+		let mut samplers = self.samplers.lock().unwrap();
+		if let Some(&raw) = samplers.get(desc) {
+			return VulkanSampler { raw };
+		}
+
+		tracy::span!();
+		// NOTE: An anisotropy of 1 samples exactly like anisotropic filtering being off, and anything below it is invalid.
+		let max_anisotropy = desc
+			.max_anisotropy
+			.filter(|&anisotropy| self.sampler_anisotropy && anisotropy > 1)
+			.map(|anisotropy| (anisotropy as f32).min(self.physical_device_properties.limits.max_sampler_anisotropy));
+
+		let raw = unsafe {
+			self.raw
+				.create_sampler(
+					&vk::SamplerCreateInfo::builder()
+						.mag_filter(desc.mag_filter.into())
+						.min_filter(desc.min_filter.into())
+						.mipmap_mode(desc.mip_mode.into())
+						.address_mode_u(desc.address_u.into())
+						.address_mode_v(desc.address_v.into())
+						.address_mode_w(desc.address_w.into())
+						.mip_lod_bias(0.0)
+						.anisotropy_enable(max_anisotropy.is_some())
+						.max_anisotropy(max_anisotropy.unwrap_or(1.0))
+						.compare_enable(desc.compare_op.is_some())
+						.compare_op(desc.compare_op.map_or(vk::CompareOp::ALWAYS, |op| op.into()))
+						.min_lod(0.0)
+						.max_lod(vk::LOD_CLAMP_NONE)
+						.border_color(desc.border_color.into()),
+					None,
+				)
+				.expect("Failed to create sampler!")
+		};
+
+		samplers.insert(*desc, raw);
+		VulkanSampler { raw }
+	}
+}
@@ -25,6 +25,7 @@ pub struct VulkanSwapchain {
 	images: Vec<SwapchainImage>,
 
 	pub frames: Vec<VulkanFrame>,
+	frame_semaphores: Vec<SwapchainSemaphores>,
 
 	pub pipelines: Vec<Option<VulkanPipeline>>,
 }
@@ -35,11 +36,11 @@ impl VulkanSwapchain {
 	pub fn new(framebuffer_size: Size, device: VulkanDevice) -> Self {
 		let (image_format, extent, swapchain_loader, swapchain, render_pass, images) = Self::init_swapchain(framebuffer_size, &device);
 		let mut frames = Vec::with_capacity(Self::MAX_FRAMES_IN_FLIGHT);
+		let mut frame_semaphores = Vec::with_capacity(Self::MAX_FRAMES_IN_FLIGHT);
 
 		for _ in 0..Self::MAX_FRAMES_IN_FLIGHT {
-			frames.push(VulkanFrame {
-				command_pool: device.create_command_pool(QueueType::GRAPHICS),
-				completed_fence: Rc::new(device.create_fence(true)),
+			frames.push(VulkanFrame::new(&device));
+			frame_semaphores.push(SwapchainSemaphores {
 				acquired_sem: device.create_semaphore(),
 				present_sem: device.create_semaphore(),
 			});
@@ -56,6 +57,7 @@ impl VulkanSwapchain {
 			images,
 
 			frames,
+			frame_semaphores,
 			pipelines: Default::default(),
 		}
 	}
@@ -68,6 +70,7 @@ impl VulkanSwapchain {
 
 		// Get the current frame that we are processing
 		let frame = &self.frames[current_frame];
+		let semaphores = &self.frame_semaphores[current_frame];
 
 		// Wait for the frame to have fully finished rendering before acquiring.
 		frame.completed_fence.wait(&self.device);
@@ -79,7 +82,7 @@ impl VulkanSwapchain {
 
 		guard.destructors[current_frame].clear();
 
-		match unsafe { self.swapchain_loader.acquire_next_image(self.swapchain, u64::MAX, frame.acquired_sem.raw, vk::Fence::null()) } {
+		match unsafe { self.swapchain_loader.acquire_next_image(self.swapchain, u64::MAX, semaphores.acquired_sem.raw, vk::Fence::null()) } {
 			Ok((image_index, false)) => {
 				assert!(image_index < self.images.len() as u32, "Invalid image index received!");
 
@@ -106,7 +109,7 @@ impl VulkanSwapchain {
 		}
 	}
 
-	pub fn submit(&mut self, image_index: u32, command_buffer: VulkanCommandBuffer) -> Result<(), SwapchainError> {
+	pub fn submit(&mut self, image_index: u32, command_buffer: VulkanCommandBuffer, wait_semaphores: &[(vk::Semaphore, vk::PipelineStageFlags)]) -> Result<(), SwapchainError> {
 		tracy::span!();
 		let mut guard = self.device.frame.lock().unwrap();
 		let current_frame = guard.frame as usize;
@@ -114,27 +117,15 @@ impl VulkanSwapchain {
 		self.frames[current_frame].command_pool.end_command_buffer(&self.device, command_buffer);
 		let frame = &self.frames[current_frame];
 
-		let acquired_sem = &frame.acquired_sem;
-		let present_sem = &frame.present_sem;
+		let acquired_sem = &self.frame_semaphores[current_frame].acquired_sem;
+		let present_sem = &self.frame_semaphores[current_frame].present_sem;
 
-		unsafe {
-			frame.completed_fence.reset(&self.device);
+		let wait_semaphores = std::iter::once((acquired_sem.raw, vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT))
+			.chain(wait_semaphores.iter().copied())
+			.collect::<Vec<_>>();
 
-			let graphics_queue = self.device.graphics_queue.lock().unwrap();
-			self.device
-				.raw
-				.queue_submit(
-					*graphics_queue,
-					&[vk::SubmitInfo::builder()
-						.wait_semaphores(&[acquired_sem.raw])
-						.wait_dst_stage_mask(&[vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT])
-						.command_buffers(&[command_buffer])
-						.signal_semaphores(&[present_sem.raw])
-						.build()],
-					frame.completed_fence.raw,
-				)
-				.unwrap();
-		}
+		self.device
+			.queue_submit(QueueType::GRAPHICS, command_buffer, &wait_semaphores, &[present_sem.raw], Some(&frame.completed_fence));
 
 		guard.frame = ((current_frame + 1) % Self::MAX_FRAMES_IN_FLIGHT) as u32;
 
@@ -223,7 +214,7 @@ impl VulkanSwapchain {
 
 		let swapchain_loader = Swapchain::new(&device.instance, &device.raw);
 		let mut create_info = vk::SwapchainCreateInfoKHR::builder()
-			.surface(device.surface)
+			.surface(device.surface.expect("Cannot create a swapchain on a headless device!"))
 			.min_image_count(image_count)
 			.image_format(surface_format.format)
 			.image_color_space(surface_format.color_space)
@@ -350,14 +341,12 @@ impl VulkanSwapchain {
 		self.destroy_swapchain();
 
 		for frame in std::mem::take(&mut self.frames).into_iter() {
-			self.device.destroy_command_pool(frame.command_pool);
-
-			if let Ok(completed_fence) = Rc::try_unwrap(frame.completed_fence) {
-				self.device.destroy_fence(completed_fence);
-			}
+			frame.destroy(&self.device);
+		}
 
-			self.device.destroy_semaphore(frame.acquired_sem);
-			self.device.destroy_semaphore(frame.present_sem);
+		for semaphores in std::mem::take(&mut self.frame_semaphores).into_iter() {
+			self.device.destroy_semaphore(semaphores.acquired_sem);
+			self.device.destroy_semaphore(semaphores.present_sem);
 		}
 	}
 }
@@ -369,13 +358,35 @@ struct SwapchainImage {
 	available_fence: Option<Rc<VulkanFence>>,
 }
 
-pub struct VulkanFrame {
-	command_pool: VulkanCommandPool,
-	completed_fence: Rc<VulkanFence>,
+// NOTE: Only frames that present to a surface need these, so they're kept out of `VulkanFrame`
+// which is shared with the headless target.
+struct SwapchainSemaphores {
 	acquired_sem: VulkanSemaphore,
 	present_sem: VulkanSemaphore,
 }
 
+pub struct VulkanFrame {
+	pub(super) command_pool: VulkanCommandPool,
+	pub(super) completed_fence: Rc<VulkanFence>,
+}
+
+impl VulkanFrame {
+	pub(super) fn new(device: &VulkanDevice) -> Self {
+		Self {
+			command_pool: device.create_command_pool(QueueType::GRAPHICS),
+			completed_fence: Rc::new(device.create_fence(true)),
+		}
+	}
+
+	pub(super) fn destroy(self, device: &VulkanDevice) {
+		device.destroy_command_pool(self.command_pool);
+
+		if let Ok(completed_fence) = Rc::try_unwrap(self.completed_fence) {
+			device.destroy_fence(completed_fence);
+		}
+	}
+}
+
 pub struct FrameInfo {
 	pub output_framebuffer: vk::Framebuffer,
 	pub image_index: u32,
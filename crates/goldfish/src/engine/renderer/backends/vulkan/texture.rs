@@ -1,22 +1,34 @@
-use super::device::{VulkanDestructor, VulkanDevice};
-use crate::renderer::{TextureFormat, TextureUsage};
+use super::device::{VulkanDestructor, VulkanDevice, VulkanUploadContext};
+use crate::renderer::{BufferUsage, ImageLayout, SampleCount, SamplerDesc, TextureAspect, TextureDesc, TextureDimension, TextureFormat, TextureSubresource, TextureUsage};
 use ash::vk;
 use gpu_allocator::vulkan as vma;
 use gpu_allocator::MemoryLocation;
 use std::hash::{Hash, Hasher};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum TextureError {
+	#[error("Block compressed texture format {0:?} is not supported by this device")]
+	UnsupportedCompressedFormat(TextureFormat),
+}
 
 pub struct VulkanTexture {
-	pub width: u32,
-	pub height: u32,
+	pub desc: TextureDesc,
 
 	pub image: vk::Image,
+	// Default sampler for combined image samplers, owned by the device sampler cache.
 	pub sampler: vk::Sampler,
 	pub image_view: vk::ImageView,
+	// Depth stencil textures can't be sampled through `image_view`, so they get a separate view for each aspect.
+	pub depth_view: Option<vk::ImageView>,
+	pub stencil_view: Option<vk::ImageView>,
+	// Views of a single mip across every layer, and of a single mip of a single layer indexed by `mip * layer_count + layer`.
+	// Both are empty for textures that only have a single mip and layer, `image_view` already covers that subresource.
+	pub mip_views: Vec<vk::ImageView>,
+	pub layer_views: Vec<vk::ImageView>,
 	pub subresource_range: vk::ImageSubresourceRange,
 
 	pub allocation: vma::Allocation,
-	pub format: TextureFormat,
-	pub usage: TextureUsage,
 }
 
 impl Hash for VulkanTexture {
@@ -35,12 +47,124 @@ impl PartialEq for VulkanTexture {
 
 impl Eq for VulkanTexture {}
 
+impl From<SampleCount> for vk::SampleCountFlags {
+	fn from(samples: SampleCount) -> Self {
+		match samples {
+			SampleCount::X1 => vk::SampleCountFlags::TYPE_1,
+			SampleCount::X2 => vk::SampleCountFlags::TYPE_2,
+			SampleCount::X4 => vk::SampleCountFlags::TYPE_4,
+			SampleCount::X8 => vk::SampleCountFlags::TYPE_8,
+		}
+	}
+}
+
+impl VulkanTexture {
+	pub fn view(&self, aspect: TextureAspect, subresource: Option<TextureSubresource>) -> vk::ImageView {
+		let format = self.desc.format;
+		assert!(format.supports_aspect(aspect), "Texture format {:?} has no {:?} aspect to view!", format, aspect);
+
+		let Some(subresource) = subresource else {
+			return match aspect {
+				TextureAspect::All => self.image_view,
+				TextureAspect::Depth => self.depth_view.unwrap_or(self.image_view),
+				TextureAspect::Stencil => self.stencil_view.unwrap(),
+			};
+		};
+
+		// NOTE: Subresource views are only created for the default aspect of the texture, which for depth stencil textures can only be used as an attachment.
+		assert!(
+			!format.has_stencil() || aspect == TextureAspect::All,
+			"Subresources of depth stencil textures cannot be viewed through a single aspect!"
+		);
+		assert!(
+			subresource.mip < self.desc.mip_levels,
+			"Texture only has {} mips but mip {} was viewed!",
+			self.desc.mip_levels,
+			subresource.mip
+		);
+
+		let layer_count = self.desc.layer_count();
+		if let Some(layer) = subresource.layer {
+			assert!(layer < layer_count, "Texture only has {} layers but layer {} was viewed!", layer_count, layer);
+			assert!(self.desc.dimension != TextureDimension::D3, "Layers of 3D textures cannot be viewed individually!");
+		}
+
+		if self.mip_views.is_empty() {
+			return self.image_view;
+		}
+
+		match subresource.layer {
+			Some(layer) if !self.layer_views.is_empty() => self.layer_views[(subresource.mip * layer_count + layer) as usize],
+			_ => self.mip_views[subresource.mip as usize],
+		}
+	}
+}
+
 impl VulkanDevice {
-	pub fn create_texture(&self, width: u32, height: u32, format: TextureFormat, usage: TextureUsage) -> VulkanTexture {
+	// Whether attachments of `format` can be rendered to with `samples`, devices have separate limits for color, depth and stencil.
+	pub fn supports_attachment_samples(&self, format: TextureFormat, samples: SampleCount) -> bool {
+		let limits = &self.physical_device_properties.limits;
+		let supported = if format.has_stencil() {
+			limits.framebuffer_depth_sample_counts & limits.framebuffer_stencil_sample_counts
+		} else if format.is_depth() {
+			limits.framebuffer_depth_sample_counts
+		} else {
+			limits.framebuffer_color_sample_counts
+		};
+
+		supported.contains(samples.into())
+	}
+
+	pub fn create_texture(&self, desc: &TextureDesc) -> Result<VulkanTexture, TextureError> {
+		let &TextureDesc {
+			width,
+			height,
+			depth,
+			dimension,
+			array_layers,
+			mip_levels,
+			format,
+			usage,
+			samples,
+		} = desc;
+
+		let is_cube = desc.is_cube();
+		let layer_count = desc.layer_count();
+
+		assert!(width > 0 && height > 0 && depth > 0 && array_layers > 0, "Texture dimensions cannot be 0!");
+		assert!(
+			mip_levels > 0 && mip_levels <= desc.full_mip_levels(),
+			"Texture of size {}x{}x{} cannot have {} mips!",
+			width,
+			height,
+			depth,
+			mip_levels
+		);
+		assert!(depth == 1 || dimension == TextureDimension::D3, "Only 3D textures can have a depth!");
+		assert!(
+			array_layers == 1 || matches!(dimension, TextureDimension::D2Array | TextureDimension::CubeArray),
+			"Only array textures can have multiple layers!"
+		);
+		assert!(!is_cube || (width == height && dimension != TextureDimension::D3), "Cube textures have to be square and 2D!");
+		// Multisampled textures can only be rendered to and resolved, they have to be resolved into a single sample texture to be read.
+		assert!(
+			!samples.is_multisampled() || (mip_levels == 1 && !is_cube && dimension != TextureDimension::D3),
+			"Multisampled textures can only have a single mip and be 2D!"
+		);
+		assert!(
+			!format.is_compressed() || !usage.intersects(TextureUsage::ATTACHMENT | TextureUsage::STORAGE),
+			"Block compressed texture format {:?} cannot be used as an attachment or storage texture!",
+			format
+		);
+
+		if format.is_compressed() && !self.texture_compression_bc {
+			return Err(TextureError::UnsupportedCompressedFormat(format));
+		}
+
 		let mut usage_flags = vk::ImageUsageFlags::default();
 
 		if usage.contains(TextureUsage::ATTACHMENT) {
-			if format == TextureFormat::Depth {
+			if format.is_depth() {
 				usage_flags |= vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT;
 			} else {
 				usage_flags |= vk::ImageUsageFlags::COLOR_ATTACHMENT;
@@ -72,17 +196,13 @@ impl VulkanDevice {
 			self.raw
 				.create_image(
 					&vk::ImageCreateInfo::builder()
-						.flags(if format.is_cubemap() {
-							vk::ImageCreateFlags::CUBE_COMPATIBLE
-						} else {
-							vk::ImageCreateFlags::default()
-						})
-						.image_type(vk::ImageType::TYPE_2D)
+						.flags(if is_cube { vk::ImageCreateFlags::CUBE_COMPATIBLE } else { vk::ImageCreateFlags::default() })
+						.image_type(if dimension == TextureDimension::D3 { vk::ImageType::TYPE_3D } else { vk::ImageType::TYPE_2D })
 						.format(vk_format)
-						.extent(vk::Extent3D { width, height, depth: 1 })
-						.mip_levels(1)
-						.array_layers(if format.is_cubemap() { 6 } else { 1 })
-						.samples(vk::SampleCountFlags::TYPE_1)
+						.extent(vk::Extent3D { width, height, depth })
+						.mip_levels(mip_levels)
+						.array_layers(layer_count)
+						.samples(samples.into())
 						.tiling(vk::ImageTiling::OPTIMAL)
 						.usage(usage_flags)
 						.sharing_mode(vk::SharingMode::EXCLUSIVE)
@@ -107,71 +227,354 @@ impl VulkanDevice {
 			self.raw.bind_image_memory(image, allocation.memory(), allocation.offset()).expect("Failed to bind image memory!");
 		}
 
-		let sampler = unsafe {
-			self.raw
-				.create_sampler(
-					&vk::SamplerCreateInfo::builder()
-						.mag_filter(vk::Filter::LINEAR)
-						.min_filter(vk::Filter::LINEAR)
-						.mipmap_mode(vk::SamplerMipmapMode::LINEAR)
-						.address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
-						.address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
-						.address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
-						.mip_lod_bias(0.0)
-						.max_anisotropy(1.0)
-						.min_lod(0.0)
-						.max_lod(0.0)
-						.border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE),
-					None,
-				)
-				.expect("Failed to create sampler!")
-		};
+		// NOTE: Linear filtering isn't supported for stencil and most depth formats.
+		let sampler = self.get_sampler(if format.is_depth() { &SamplerDesc::NEAREST_CLAMP } else { &SamplerDesc::LINEAR_CLAMP }).raw;
 
 		let subresource_range = vk::ImageSubresourceRange::builder()
 			.aspect_mask(match format {
-				TextureFormat::Depth => vk::ImageAspectFlags::DEPTH, // TODO(Brandon): We need to figure out a good way to handle multiple image views + samplers for depth + stencil attachments
+				TextureFormat::Depth => vk::ImageAspectFlags::DEPTH,
+				TextureFormat::DepthStencil => vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL,
 				_ => vk::ImageAspectFlags::COLOR,
 			})
 			.base_mip_level(0)
-			.level_count(1)
+			.level_count(mip_levels)
 			.base_array_layer(0)
-			.layer_count(if format.is_cubemap() { 6 } else { 1 })
+			.layer_count(layer_count)
 			.build();
 
-		let image_view = unsafe {
+		let view_type = match (dimension, is_cube) {
+			(TextureDimension::D3, _) => vk::ImageViewType::TYPE_3D,
+			(TextureDimension::D2Array | TextureDimension::CubeArray, true) => vk::ImageViewType::CUBE_ARRAY,
+			(_, true) => vk::ImageViewType::CUBE,
+			(TextureDimension::D2Array, false) => vk::ImageViewType::TYPE_2D_ARRAY,
+			_ => vk::ImageViewType::TYPE_2D,
+		};
+
+		let create_view = |view_type: vk::ImageViewType, range: vk::ImageSubresourceRange| unsafe {
 			self.raw
-				.create_image_view(
-					&vk::ImageViewCreateInfo::builder()
-						.image(image)
-						.view_type(if format.is_cubemap() { vk::ImageViewType::CUBE } else { vk::ImageViewType::TYPE_2D })
-						.format(vk_format)
-						.subresource_range(subresource_range),
-					None,
-				)
+				.create_image_view(&vk::ImageViewCreateInfo::builder().image(image).view_type(view_type).format(vk_format).subresource_range(range), None)
 				.expect("Failed to create image view!")
 		};
 
-		VulkanTexture {
-			width,
-			height,
+		let image_view = create_view(view_type, subresource_range);
+
+		let (depth_view, stencil_view) = if format.has_stencil() {
+			(
+				Some(create_view(
+					view_type,
+					vk::ImageSubresourceRange {
+						aspect_mask: vk::ImageAspectFlags::DEPTH,
+						..subresource_range
+					},
+				)),
+				Some(create_view(
+					view_type,
+					vk::ImageSubresourceRange {
+						aspect_mask: vk::ImageAspectFlags::STENCIL,
+						..subresource_range
+					},
+				)),
+			)
+		} else {
+			(None, None)
+		};
+
+		let (mip_views, layer_views) = if mip_levels > 1 || layer_count > 1 {
+			let mip_views = (0..mip_levels)
+				.map(|mip| {
+					create_view(
+						view_type,
+						vk::ImageSubresourceRange {
+							base_mip_level: mip,
+							level_count: 1,
+							..subresource_range
+						},
+					)
+				})
+				.collect::<Vec<_>>();
+
+			// Cube faces and array layers get rendered to one at a time, so a single layer is always viewed as a plain 2D texture.
+			let layer_views = if dimension != TextureDimension::D3 && layer_count > 1 {
+				(0..mip_levels)
+					.flat_map(|mip| (0..layer_count).map(move |layer| (mip, layer)))
+					.map(|(mip, layer)| {
+						create_view(
+							vk::ImageViewType::TYPE_2D,
+							vk::ImageSubresourceRange {
+								base_mip_level: mip,
+								level_count: 1,
+								base_array_layer: layer,
+								layer_count: 1,
+								..subresource_range
+							},
+						)
+					})
+					.collect::<Vec<_>>()
+			} else {
+				vec![]
+			};
+
+			(mip_views, layer_views)
+		} else {
+			(vec![], vec![])
+		};
+
+		Ok(VulkanTexture {
+			desc: *desc,
 
 			image,
 			sampler,
 			image_view,
+			depth_view,
+			stencil_view,
+			mip_views,
+			layer_views,
 			subresource_range,
 
 			allocation,
-			format,
-			usage,
-		}
+		})
 	}
 
 	pub fn destroy_texture(&mut self, texture: VulkanTexture) {
-		self.queue_destruction(&mut [
-			VulkanDestructor::Image(texture.image),
-			VulkanDestructor::ImageView(texture.image_view),
-			VulkanDestructor::Sampler(texture.sampler),
-			VulkanDestructor::Allocation(texture.allocation),
-		])
+		self.queue_destruction(
+			&mut [
+				VulkanDestructor::Image(texture.image),
+				VulkanDestructor::ImageView(texture.image_view),
+				VulkanDestructor::Allocation(texture.allocation),
+			]
+			.into_iter()
+			.chain(
+				texture
+					.depth_view
+					.into_iter()
+					.chain(texture.stencil_view)
+					.chain(texture.mip_views)
+					.chain(texture.layer_views)
+					.map(|view| VulkanDestructor::ImageView(view)),
+			)
+			.collect::<Vec<_>>(),
+		)
+	}
+}
+
+impl VulkanUploadContext {
+	// Fills in every mip after the first by blitting each mip down from the one before it. Mip 0 of every layer has to be in `layout`
+	// and the contents of the other mips are discarded. Every mip is left in SHADER_READ_ONLY_OPTIMAL afterwards.
+	pub fn generate_mips(&mut self, texture: &VulkanTexture, layout: ImageLayout) {
+		let desc = texture.desc;
+		assert!(
+			desc.usage.contains(TextureUsage::TRANSFER_SRC | TextureUsage::TRANSFER_DST),
+			"Generating mips requires a texture with TRANSFER_SRC and TRANSFER_DST usage!"
+		);
+		assert!(!desc.samples.is_multisampled(), "Cannot generate mips of a multisampled texture!");
+		assert!(!desc.format.is_depth(), "Cannot generate mips of a depth texture!");
+		assert!(
+			!desc.format.is_compressed(),
+			"Cannot generate mips of a block compressed texture, they have to be compressed ahead of time!"
+		);
+
+		let format_properties = unsafe { self.device.instance.get_physical_device_format_properties(self.device.physical_device, desc.format.to_vk(&self.device)) };
+		// Not every format can be blitted with linear filtering, nearest filtering still produces a usable (if aliased) mip chain.
+		let filter = if format_properties.optimal_tiling_features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR) {
+			vk::Filter::LINEAR
+		} else {
+			vk::Filter::NEAREST
+		};
+
+		let image = texture.image;
+		let mip_range = |mip: u32, level_count: u32| vk::ImageSubresourceRange {
+			base_mip_level: mip,
+			level_count,
+			..texture.subresource_range
+		};
+		let mip_layers = |mip: u32| vk::ImageSubresourceLayers {
+			aspect_mask: texture.subresource_range.aspect_mask,
+			mip_level: mip,
+			base_array_layer: 0,
+			layer_count: texture.subresource_range.layer_count,
+		};
+		let mip_offset = |mip: u32| {
+			let (width, height, depth) = desc.mip_size(mip);
+			vk::Offset3D {
+				x: width as i32,
+				y: height as i32,
+				z: depth as i32,
+			}
+		};
+
+		self.wait_submit(|device, cmd| unsafe {
+			let barrier = |range: vk::ImageSubresourceRange, old_layout: vk::ImageLayout, new_layout: vk::ImageLayout, src_access: vk::AccessFlags, dst_access: vk::AccessFlags| {
+				vk::ImageMemoryBarrier::builder()
+					.image(image)
+					.subresource_range(range)
+					.old_layout(old_layout)
+					.new_layout(new_layout)
+					.src_access_mask(src_access)
+					.dst_access_mask(dst_access)
+					.src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+					.dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+					.build()
+			};
+
+			let mut initial_barriers = vec![barrier(
+				mip_range(0, 1),
+				layout.into(),
+				vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+				vk::AccessFlags::MEMORY_WRITE,
+				vk::AccessFlags::TRANSFER_READ,
+			)];
+			if desc.mip_levels > 1 {
+				initial_barriers.push(barrier(
+					mip_range(1, desc.mip_levels - 1),
+					vk::ImageLayout::UNDEFINED,
+					vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+					vk::AccessFlags::empty(),
+					vk::AccessFlags::TRANSFER_WRITE,
+				));
+			}
+
+			device.cmd_pipeline_barrier(
+				cmd,
+				vk::PipelineStageFlags::ALL_COMMANDS,
+				vk::PipelineStageFlags::TRANSFER,
+				vk::DependencyFlags::empty(),
+				&[],
+				&[],
+				&initial_barriers,
+			);
+
+			for mip in 1..desc.mip_levels {
+				device.cmd_blit_image(
+					cmd,
+					image,
+					vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+					image,
+					vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+					&[vk::ImageBlit {
+						src_subresource: mip_layers(mip - 1),
+						src_offsets: [vk::Offset3D::default(), mip_offset(mip - 1)],
+						dst_subresource: mip_layers(mip),
+						dst_offsets: [vk::Offset3D::default(), mip_offset(mip)],
+					}],
+					filter,
+				);
+
+				// The mip that was just written is the source of the next blit.
+				device.cmd_pipeline_barrier(
+					cmd,
+					vk::PipelineStageFlags::TRANSFER,
+					vk::PipelineStageFlags::TRANSFER,
+					vk::DependencyFlags::empty(),
+					&[],
+					&[],
+					&[barrier(
+						mip_range(mip, 1),
+						vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+						vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+						vk::AccessFlags::TRANSFER_WRITE,
+						vk::AccessFlags::TRANSFER_READ,
+					)],
+				);
+			}
+
+			device.cmd_pipeline_barrier(
+				cmd,
+				vk::PipelineStageFlags::TRANSFER,
+				vk::PipelineStageFlags::ALL_COMMANDS,
+				vk::DependencyFlags::empty(),
+				&[],
+				&[],
+				&[barrier(
+					mip_range(0, desc.mip_levels),
+					vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+					vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+					vk::AccessFlags::TRANSFER_READ,
+					vk::AccessFlags::SHADER_READ,
+				)],
+			);
+		});
+	}
+
+	// Creates a texture and fills every mip of every layer with `data`. Mips are tightly packed one after the other starting at mip 0,
+	// each containing every layer in order. The texture is left in SHADER_READ_ONLY_OPTIMAL.
+	pub fn create_texture_with_data(&mut self, desc: &TextureDesc, data: &[u8]) -> Result<VulkanTexture, TextureError> {
+		assert!(desc.usage.contains(TextureUsage::TRANSFER_DST), "Uploading texture data requires a texture with TRANSFER_DST usage!");
+		assert!(!desc.samples.is_multisampled(), "Cannot upload data to a multisampled texture!");
+		assert!(!desc.format.is_depth(), "Cannot upload data to a depth texture!");
+		assert_eq!(data.len() as u64, desc.byte_size(), "Texture data does not match the size of the texture!");
+
+		let texture = self.device.create_texture(desc)?;
+
+		let mut staging = self.device.create_empty_buffer(data.len(), MemoryLocation::CpuToGpu, BufferUsage::TransferSrc, None);
+		staging.allocation.mapped_slice_mut().expect("Failed to map staging buffer!")[0..data.len()].copy_from_slice(data);
+
+		let layer_count = desc.layer_count();
+		let mut offset = 0;
+		let regions = (0..desc.mip_levels)
+			.map(|mip| {
+				let (width, height, depth) = desc.mip_size(mip);
+				let region = vk::BufferImageCopy::builder()
+					.buffer_offset(offset)
+					.image_subresource(vk::ImageSubresourceLayers {
+						aspect_mask: texture.subresource_range.aspect_mask,
+						mip_level: mip,
+						base_array_layer: 0,
+						layer_count,
+					})
+					.image_extent(vk::Extent3D { width, height, depth })
+					.build();
+
+				offset += desc.format.surface_size(width, height) * depth as u64 * layer_count as u64;
+				region
+			})
+			.collect::<Vec<_>>();
+
+		let image = texture.image;
+		let subresource_range = texture.subresource_range;
+		self.wait_submit(|device, cmd| unsafe {
+			device.cmd_pipeline_barrier(
+				cmd,
+				vk::PipelineStageFlags::TOP_OF_PIPE,
+				vk::PipelineStageFlags::TRANSFER,
+				vk::DependencyFlags::empty(),
+				&[],
+				&[],
+				&[vk::ImageMemoryBarrier::builder()
+					.image(image)
+					.subresource_range(subresource_range)
+					.old_layout(vk::ImageLayout::UNDEFINED)
+					.new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+					.src_access_mask(vk::AccessFlags::empty())
+					.dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+					.src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+					.dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+					.build()],
+			);
+
+			device.cmd_copy_buffer_to_image(cmd, staging.raw, image, vk::ImageLayout::TRANSFER_DST_OPTIMAL, &regions);
+
+			device.cmd_pipeline_barrier(
+				cmd,
+				vk::PipelineStageFlags::TRANSFER,
+				vk::PipelineStageFlags::ALL_COMMANDS,
+				vk::DependencyFlags::empty(),
+				&[],
+				&[],
+				&[vk::ImageMemoryBarrier::builder()
+					.image(image)
+					.subresource_range(subresource_range)
+					.old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+					.new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+					.src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+					.dst_access_mask(vk::AccessFlags::SHADER_READ)
+					.src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+					.dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+					.build()],
+			);
+		});
+
+		self.destroy_buffer(staging);
+
+		Ok(texture)
 	}
 }
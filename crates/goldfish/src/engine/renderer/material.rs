@@ -0,0 +1,144 @@
+use super::*;
+use crate::package::{MaterialBinding, MaterialPackage};
+use crate::{GoldfishError, GoldfishResult};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Error, Debug)]
+pub enum MaterialError {
+	#[error("Binding {0} of the material is not part of the descriptor layout")]
+	UnknownBinding(u32),
+	#[error("Binding {binding} of the material is a {provided} but the descriptor layout expects a {expected:?}")]
+	MismatchedBinding { binding: u32, provided: &'static str, expected: DescriptorBindingType },
+	#[error("Binding {0} of the descriptor layout is not provided by the material")]
+	MissingBinding(u32),
+	#[error("Failed to load texture {0}: {1}")]
+	Texture(Uuid, GoldfishError),
+	#[error("Failed to upload texture {0}: {1}")]
+	TextureUpload(Uuid, TextureError),
+}
+
+// A material package whose cbuffers and textures were uploaded, ready to be bound to the material's set of its shader.
+pub struct Material {
+	pub shader: Uuid,
+	pub set: u32,
+	pub descriptor_layout: &'static DescriptorSetInfo,
+	cbuffers: Vec<(u32, GpuBuffer)>,
+	textures: Vec<(u32, Texture)>,
+	samplers: Vec<(u32, SamplerDesc)>,
+}
+
+impl Material {
+	// The bindings of a `DescriptorDesc` with `descriptor_layout`.
+	pub fn descriptor_bindings<'a, 'b>(&'a self) -> Vec<(u32, DescriptorBindingDesc<'a, 'b>)> {
+		self.cbuffers
+			.iter()
+			.map(|(binding, cbuffer)| (*binding, DescriptorBindingDesc::ImportedBuffer(cbuffer)))
+			.chain(self.textures.iter().map(|(binding, texture)| (*binding, DescriptorBindingDesc::ImportedTexture(texture))))
+			.chain(self.samplers.iter().map(|(binding, sampler)| (*binding, DescriptorBindingDesc::Sampler(*sampler))))
+			.collect()
+	}
+}
+
+impl UploadContext {
+	// `descriptor_layout` has to be the layout of the material's set in its shader, which the game declares alongside the shader.
+	// NOTE: Textures are loaded for every material that uses them, materials that share a texture don't share its memory.
+	pub fn create_material<F>(&mut self, package: &MaterialPackage, descriptor_layout: &'static DescriptorSetInfo, read_package: F) -> Result<Material, MaterialError>
+	where
+		F: Fn(Uuid, AssetType) -> GoldfishResult<Package>,
+	{
+		tracy::span!();
+		for (binding, value) in package.bindings.iter() {
+			let expected = *descriptor_layout.bindings.get(binding).ok_or(MaterialError::UnknownBinding(*binding))?;
+			let (provided, matches) = match value {
+				MaterialBinding::CBuffer(_) => ("cbuffer", expected == DescriptorBindingType::CBuffer),
+				MaterialBinding::Texture(_) => ("texture", expected == DescriptorBindingType::Texture2D),
+				MaterialBinding::Sampler(_) => ("sampler", expected == DescriptorBindingType::SamplerState),
+			};
+
+			if !matches {
+				return Err(MaterialError::MismatchedBinding {
+					binding: *binding,
+					provided,
+					expected,
+				});
+			}
+		}
+
+		if let Some(&binding) = descriptor_layout.bindings.keys().find(|&&binding| package.bindings.iter().all(|(provided, _)| *provided != binding)) {
+			return Err(MaterialError::MissingBinding(binding));
+		}
+
+		// Textures are all read before anything is uploaded, so that nothing has to be cleaned up if one of them fails to load.
+		let texture_packages = package
+			.bindings
+			.iter()
+			.filter_map(|(binding, value)| match value {
+				MaterialBinding::Texture(uuid) => Some((*binding, *uuid)),
+				_ => None,
+			})
+			.map(|(binding, uuid)| match read_package(uuid, AssetType::Texture) {
+				Ok(Package::Texture(texture_package)) => Ok((binding, uuid, texture_package)),
+				Ok(_) => Err(MaterialError::Texture(uuid, GoldfishError::Unknown("Incorrect package type loaded".to_string()))),
+				Err(err) => Err(MaterialError::Texture(uuid, err)),
+			})
+			.collect::<Result<Vec<_>, _>>()?;
+
+		// Textures are uploaded before the cbuffers, so that only they have to be cleaned up if the device doesn't support one of them.
+		let mut textures = Vec::with_capacity(texture_packages.len());
+		for (binding, uuid, texture_package) in texture_packages.iter() {
+			match self.create_texture_from_package(texture_package) {
+				Ok(texture) => textures.push((*binding, texture)),
+				Err(err) => {
+					for (_, texture) in textures {
+						self.device.destroy_texture(texture);
+					}
+					return Err(MaterialError::TextureUpload(*uuid, err));
+				}
+			}
+		}
+
+		let cbuffers = package
+			.bindings
+			.iter()
+			.filter_map(|(binding, value)| match value {
+				MaterialBinding::CBuffer(data) => Some((
+					*binding,
+					self.create_buffer(data.len(), MemoryLocation::GpuOnly, BufferUsage::UniformBuffer, None, Some(data.as_slice())),
+				)),
+				_ => None,
+			})
+			.collect();
+
+		let samplers = package
+			.bindings
+			.iter()
+			.filter_map(|(binding, value)| match value {
+				MaterialBinding::Sampler(sampler) => Some((*binding, *sampler)),
+				_ => None,
+			})
+			.collect();
+
+		Ok(Material {
+			shader: package.shader,
+			set: package.set,
+			descriptor_layout,
+			cbuffers,
+			textures,
+			samplers,
+		})
+	}
+}
+
+impl GraphicsDevice {
+	pub fn destroy_material(&mut self, material: Material) {
+		tracy::span!();
+		for (_, cbuffer) in material.cbuffers {
+			self.destroy_buffer(cbuffer);
+		}
+
+		for (_, texture) in material.textures {
+			self.destroy_texture(texture);
+		}
+	}
+}
@@ -3,7 +3,7 @@ use bitflags::bitflags;
 use serde::{Deserialize, Serialize};
 use uuid::uuid;
 
-use super::package::{AssetType, Package};
+use super::package::{AssetType, Package, TexturePackage};
 use super::GoldfishEngine;
 use crate::types::Color;
 use backends::vulkan::*;
@@ -11,8 +11,10 @@ use glam::{Vec2, Vec3};
 use std::collections::HashMap;
 use tracy_client as tracy;
 pub mod backends;
+pub mod material;
 pub mod render_graph;
 
+pub use material::*;
 pub use render_graph::*;
 
 pub const VS_MAIN: &'static str = "vs_main";
@@ -24,9 +26,11 @@ pub type GraphicsContext = VulkanGraphicsContext;
 pub type UploadContext = VulkanUploadContext;
 pub type GpuBuffer = VulkanBuffer;
 pub type Pipeline = VulkanPipeline;
+pub type PipelineCache = VulkanPipelineCache;
 pub type RenderPass = VulkanRenderPass;
 pub type Shader = VulkanShader;
 pub type Texture = VulkanTexture;
+pub type Sampler = VulkanSampler;
 pub type Framebuffer = VulkanFramebuffer;
 pub type DescriptorHeap = VulkanDescriptorHeap;
 pub type DescriptorLayoutCache = VulkanDescriptorLayoutCache;
@@ -127,9 +131,222 @@ pub enum TextureFormat {
 	RGB32Float,
 	RGBA32Float,
 
+	// Block compressed, every 4x4 block of texels is encoded together
+	// BC1 and BC4 use 8 bytes per block, the others use 16 bytes
+	BC1RGBAUNorm,
+	BC1SRGBA,
+	BC3RGBAUNorm,
+	BC3SRGBA,
+	BC4RUNorm,
+	BC5RGUNorm,
+	BC7RGBAUNorm,
+	BC7SRGBA,
+
 	// Depth formats
-	// TODO(Brandon): Add depth stencil format here.
 	Depth,
+	DepthStencil,
+}
+
+// Which part of a texture is viewed. Depth stencil textures have to be sampled through either their depth or their stencil aspect.
+#[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum TextureAspect {
+	All,
+	Depth,
+	Stencil,
+}
+
+// Number of samples per pixel of an attachment. Multisampled attachments can't be sampled directly and have to be resolved first.
+#[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum SampleCount {
+	X1,
+	X2,
+	X4,
+	X8,
+}
+
+impl SampleCount {
+	pub fn count(self) -> u32 {
+		match self {
+			SampleCount::X1 => 1,
+			SampleCount::X2 => 2,
+			SampleCount::X4 => 4,
+			SampleCount::X8 => 8,
+		}
+	}
+
+	pub fn is_multisampled(self) -> bool {
+		self != SampleCount::X1
+	}
+}
+
+#[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum TextureDimension {
+	D2,
+	D2Array,
+	Cube,
+	CubeArray,
+	D3,
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct TextureDesc {
+	pub width: u32,
+	pub height: u32,
+	// Only 3D textures can have a depth other than 1.
+	pub depth: u32,
+	pub dimension: TextureDimension,
+	// Number of array elements, every element of a cube array has 6 faces.
+	pub array_layers: u32,
+	pub mip_levels: u32,
+	pub format: TextureFormat,
+	pub usage: TextureUsage,
+	pub samples: SampleCount,
+}
+
+impl TextureDesc {
+	// A single mip, single sample 2D texture.
+	pub const fn new_2d(width: u32, height: u32, format: TextureFormat, usage: TextureUsage) -> Self {
+		Self {
+			width,
+			height,
+			depth: 1,
+			dimension: TextureDimension::D2,
+			array_layers: 1,
+			mip_levels: 1,
+			format,
+			usage,
+			samples: SampleCount::X1,
+		}
+	}
+
+	// The cubemap formats predate `TextureDimension`, so they still imply a cube.
+	pub fn is_cube(&self) -> bool {
+		matches!(self.dimension, TextureDimension::Cube | TextureDimension::CubeArray) || self.format.is_cubemap()
+	}
+
+	// Number of layers of the image, counting every face of a cube separately.
+	pub fn layer_count(&self) -> u32 {
+		if self.is_cube() {
+			self.array_layers * 6
+		} else {
+			self.array_layers
+		}
+	}
+
+	// Number of mips it takes to get down to a single texel.
+	pub fn full_mip_levels(&self) -> u32 {
+		32 - self.width.max(self.height).max(self.depth).max(1).leading_zeros()
+	}
+
+	pub fn mip_size(&self, mip: u32) -> (u32, u32, u32) {
+		((self.width >> mip).max(1), (self.height >> mip).max(1), (self.depth >> mip).max(1))
+	}
+
+	// Size of every mip of every layer and sample combined, see `TextureFormat::bytes_per_pixel` for the precision of this.
+	pub fn byte_size(&self) -> u64 {
+		let mips = (0..self.mip_levels)
+			.map(|mip| {
+				let (width, height, depth) = self.mip_size(mip);
+				self.format.surface_size(width, height) * depth as u64
+			})
+			.sum::<u64>();
+
+		mips * self.layer_count() as u64 * self.samples.count() as u64
+	}
+}
+
+// A single mip of a texture, optionally narrowed down to a single layer. Layers index cube faces individually.
+#[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct TextureSubresource {
+	pub mip: u32,
+	pub layer: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Filter {
+	Nearest,
+	Linear,
+}
+
+#[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum MipmapMode {
+	Nearest,
+	Linear,
+}
+
+#[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum AddressMode {
+	Repeat,
+	MirroredRepeat,
+	ClampToEdge,
+	ClampToBorder,
+}
+
+// Only used by AddressMode::ClampToBorder.
+#[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum BorderColor {
+	TransparentBlack,
+	OpaqueBlack,
+	OpaqueWhite,
+}
+
+// Samplers are deduplicated by the device, so two equal descs always end up with the same sampler object.
+// Setting `compare_op` makes this a comparison sampler which is what shadow maps are sampled with.
+#[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct SamplerDesc {
+	pub min_filter: Filter,
+	pub mag_filter: Filter,
+	pub mip_mode: MipmapMode,
+	pub address_u: AddressMode,
+	pub address_v: AddressMode,
+	pub address_w: AddressMode,
+	pub max_anisotropy: Option<u8>,
+	pub compare_op: Option<DepthCompareOp>,
+	pub border_color: BorderColor,
+}
+
+impl SamplerDesc {
+	pub const LINEAR_CLAMP: Self = Self::new(Filter::Linear, MipmapMode::Linear, AddressMode::ClampToEdge);
+	pub const LINEAR_REPEAT: Self = Self::new(Filter::Linear, MipmapMode::Linear, AddressMode::Repeat);
+	pub const NEAREST_CLAMP: Self = Self::new(Filter::Nearest, MipmapMode::Nearest, AddressMode::ClampToEdge);
+	pub const NEAREST_REPEAT: Self = Self::new(Filter::Nearest, MipmapMode::Nearest, AddressMode::Repeat);
+	// Everything outside of the shadow map is treated as lit.
+	pub const SHADOW: Self = Self {
+		address_u: AddressMode::ClampToBorder,
+		address_v: AddressMode::ClampToBorder,
+		address_w: AddressMode::ClampToBorder,
+		compare_op: Some(DepthCompareOp::LessOrEqual),
+		border_color: BorderColor::OpaqueWhite,
+		..Self::new(Filter::Linear, MipmapMode::Nearest, AddressMode::ClampToEdge)
+	};
+
+	pub const fn new(filter: Filter, mip_mode: MipmapMode, address_mode: AddressMode) -> Self {
+		Self {
+			min_filter: filter,
+			mag_filter: filter,
+			mip_mode,
+			address_u: address_mode,
+			address_v: address_mode,
+			address_w: address_mode,
+			max_anisotropy: None,
+			compare_op: None,
+			border_color: BorderColor::OpaqueBlack,
+		}
+	}
+
+	// A maximum anisotropy of 0 or 1 disables anisotropic filtering, anything above the device limit is clamped to it.
+	pub const fn with_anisotropy(self, max_anisotropy: u8) -> Self {
+		Self {
+			max_anisotropy: if max_anisotropy > 1 { Some(max_anisotropy) } else { None },
+			..self
+		}
+	}
+}
+
+impl Default for SamplerDesc {
+	fn default() -> Self {
+		Self::LINEAR_CLAMP
+	}
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
@@ -178,6 +395,124 @@ pub enum DepthCompareOp {
 	Always,
 }
 
+#[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum StencilOp {
+	Keep,
+	Zero,
+	Replace,
+	IncrementAndClamp,
+	DecrementAndClamp,
+	Invert,
+	IncrementAndWrap,
+	DecrementAndWrap,
+}
+
+#[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct StencilFaceState {
+	pub fail_op: StencilOp,
+	pub pass_op: StencilOp,
+	pub depth_fail_op: StencilOp,
+	pub compare_op: DepthCompareOp,
+	pub compare_mask: u32,
+	pub write_mask: u32,
+	pub reference: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct StencilState {
+	pub front: StencilFaceState,
+	pub back: StencilFaceState,
+}
+
+impl StencilState {
+	// Same test and ops for front and back facing triangles.
+	pub fn both(face: StencilFaceState) -> Self {
+		Self { front: face, back: face }
+	}
+}
+
+#[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum BlendFactor {
+	Zero,
+	One,
+	SrcColor,
+	OneMinusSrcColor,
+	DstColor,
+	OneMinusDstColor,
+	SrcAlpha,
+	OneMinusSrcAlpha,
+	DstAlpha,
+	OneMinusDstAlpha,
+	SrcAlphaSaturate,
+}
+
+#[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum BlendOp {
+	Add,
+	Subtract,
+	ReverseSubtract,
+	Min,
+	Max,
+}
+
+// How the output of the pixel shader is combined with what is already in a color attachment.
+#[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum BlendMode {
+	// Overwrites the attachment, blending is disabled.
+	Opaque,
+	// src * src_alpha + dst * (1 - src_alpha)
+	Alpha,
+	// src + dst * (1 - src_alpha), for colors that were already multiplied by their alpha.
+	Premultiplied,
+	// src * src_alpha + dst
+	Additive,
+	Custom {
+		src_color: BlendFactor,
+		dst_color: BlendFactor,
+		color_op: BlendOp,
+		src_alpha: BlendFactor,
+		dst_alpha: BlendFactor,
+		alpha_op: BlendOp,
+	},
+}
+
+bitflags! {
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+	pub struct ColorWriteMask: u8
+	{
+		const R = 0x1;
+		const G = 0x2;
+		const B = 0x4;
+		const A = 0x8;
+	}
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct ColorBlendState {
+	pub mode: BlendMode,
+	pub write_mask: ColorWriteMask,
+}
+
+impl ColorBlendState {
+	pub const OPAQUE: Self = Self::new(BlendMode::Opaque);
+	pub const ALPHA: Self = Self::new(BlendMode::Alpha);
+	pub const PREMULTIPLIED: Self = Self::new(BlendMode::Premultiplied);
+	pub const ADDITIVE: Self = Self::new(BlendMode::Additive);
+
+	pub const fn new(mode: BlendMode) -> Self {
+		Self {
+			mode,
+			write_mask: ColorWriteMask::all(),
+		}
+	}
+}
+
+impl Default for ColorBlendState {
+	fn default() -> Self {
+		Self::OPAQUE
+	}
+}
+
 #[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub enum VertexAttributeFormat {
 	F32,
@@ -235,6 +570,23 @@ impl Vertex {
 pub const EMPTY_VERTEX_INFO: VertexInputInfo = VertexInputInfo { bindings: &[], stride: 0 };
 
 impl TextureFormat {
+	pub fn is_depth(&self) -> bool {
+		matches!(self, TextureFormat::Depth | TextureFormat::DepthStencil)
+	}
+
+	pub fn has_stencil(&self) -> bool {
+		matches!(self, TextureFormat::DepthStencil)
+	}
+
+	pub fn supports_aspect(&self, aspect: TextureAspect) -> bool {
+		match aspect {
+			// The combined view of a depth stencil texture can only be used as an attachment.
+			TextureAspect::All => !self.has_stencil(),
+			TextureAspect::Depth => self.is_depth(),
+			TextureAspect::Stencil => self.has_stencil(),
+		}
+	}
+
 	pub fn is_cubemap(&self) -> bool {
 		return (*self == TextureFormat::CubemapRGB8UNorm)
 			| (*self == TextureFormat::CubemapRGB16UNorm)
@@ -243,6 +595,58 @@ impl TextureFormat {
 			| (*self == TextureFormat::CubemapSRGB8)
 			| (*self == TextureFormat::CubemapSRGBA8);
 	}
+
+	pub fn is_compressed(&self) -> bool {
+		self.bytes_per_block().is_some()
+	}
+
+	// Size of a single 4x4 block of the block compressed formats.
+	pub fn bytes_per_block(&self) -> Option<u32> {
+		match self {
+			TextureFormat::BC1RGBAUNorm | TextureFormat::BC1SRGBA | TextureFormat::BC4RUNorm => Some(8),
+			TextureFormat::BC3RGBAUNorm | TextureFormat::BC3SRGBA | TextureFormat::BC5RGUNorm | TextureFormat::BC7RGBAUNorm | TextureFormat::BC7SRGBA => Some(16),
+			_ => None,
+		}
+	}
+
+	// Size of a single 2D slice of one face, block compressed formats are padded out to whole blocks.
+	pub fn surface_size(&self, width: u32, height: u32) -> u64 {
+		match self.bytes_per_block() {
+			Some(bytes_per_block) => ((width + 3) / 4) as u64 * ((height + 3) / 4) as u64 * bytes_per_block as u64,
+			// NOTE: Every format that isn't block compressed has a size per pixel.
+			None => width as u64 * height as u64 * self.bytes_per_pixel().unwrap_or_default() as u64,
+		}
+	}
+
+	// Size of a single texel of one face, block compressed formats don't have one so use `surface_size` for those. The 3 channel
+	// formats are assumed to be tightly packed, so this can under-report on drivers which pad them out to 4 channels.
+	pub fn bytes_per_pixel(&self) -> Option<u32> {
+		match self {
+			TextureFormat::R8UNorm | TextureFormat::R8SNorm | TextureFormat::R8UInt | TextureFormat::R8SInt => Some(1),
+			TextureFormat::R16UNorm | TextureFormat::R16SNorm | TextureFormat::R16UInt | TextureFormat::R16SInt => Some(2),
+			TextureFormat::RG8UNorm | TextureFormat::RG8SNorm | TextureFormat::RG8UInt | TextureFormat::RG8SInt => Some(2),
+			TextureFormat::RGB8UNorm | TextureFormat::CubemapRGB8UNorm | TextureFormat::SRGB8 | TextureFormat::CubemapSRGB8 | TextureFormat::RGB8SNorm | TextureFormat::RGB8UInt | TextureFormat::RGB8SInt => Some(3),
+			TextureFormat::R32UInt | TextureFormat::R32SInt | TextureFormat::R32Float => Some(4),
+			TextureFormat::RG16UNorm | TextureFormat::RG16SNorm | TextureFormat::RG16UInt | TextureFormat::RG16SInt => Some(4),
+			TextureFormat::RGBA8UNorm | TextureFormat::CubemapRGBA8UNorm | TextureFormat::SRGBA8 | TextureFormat::CubemapSRGBA8 | TextureFormat::RGBA8SNorm | TextureFormat::RGBA8UInt | TextureFormat::RGBA8SInt => Some(4),
+			TextureFormat::RGB16UNorm | TextureFormat::CubemapRGB16UNorm | TextureFormat::RGB16SNorm | TextureFormat::RGB16UInt | TextureFormat::RGB16SInt => Some(6),
+			TextureFormat::RG32UInt | TextureFormat::RG32SInt | TextureFormat::RG32Float => Some(8),
+			TextureFormat::RGBA16UNorm | TextureFormat::CubemapRGBA16UNorm | TextureFormat::RGBA16SNorm | TextureFormat::RGBA16UInt | TextureFormat::RGBA16SInt => Some(8),
+			TextureFormat::RGB32UInt | TextureFormat::RGB32SInt | TextureFormat::RGB32Float => Some(12),
+			TextureFormat::RGBA32UInt | TextureFormat::RGBA32SInt | TextureFormat::RGBA32Float => Some(16),
+			// NOTE: Every depth format we pick from in the device is at most 32 bits, and at most 64 bits with stencil.
+			TextureFormat::Depth => Some(4),
+			TextureFormat::DepthStencil => Some(8),
+			TextureFormat::BC1RGBAUNorm
+			| TextureFormat::BC1SRGBA
+			| TextureFormat::BC3RGBAUNorm
+			| TextureFormat::BC3SRGBA
+			| TextureFormat::BC4RUNorm
+			| TextureFormat::BC5RGUNorm
+			| TextureFormat::BC7RGBAUNorm
+			| TextureFormat::BC7SRGBA => None,
+		}
+	}
 }
 
 bitflags! {
@@ -290,6 +694,7 @@ pub enum StoreOp {
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub struct AttachmentDescription {
 	pub format: TextureFormat,
+	pub samples: SampleCount,
 	pub usage: TextureUsage,
 	pub load_op: LoadOp,
 	pub store_op: StoreOp,
@@ -309,6 +714,17 @@ pub enum DescriptorBindingType {
 	RWStructuredBuffer,
 }
 
+// Cache hits and misses are only known if the driver supports pipeline creation feedback,
+// so they don't necessarily add up to the number of created pipelines.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PipelineCacheStats {
+	pub loaded_bytes: usize,
+	pub pipelines_created: u32,
+	pub cache_hits: u32,
+	pub cache_misses: u32,
+	pub creation_time: std::time::Duration,
+}
+
 #[derive(Debug)]
 pub struct DescriptorSetInfo {
 	pub bindings: phf::Map<u32, DescriptorBindingType>,
@@ -333,15 +749,73 @@ pub struct Vertex {
 unsafe impl bytemuck::Pod for Vertex {}
 unsafe impl bytemuck::Zeroable for Vertex {}
 
+#[derive(Debug, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum IndexType {
+	U16,
+	U32,
+}
+
+impl IndexType {
+	pub fn size(self) -> usize {
+		match self {
+			IndexType::U16 => std::mem::size_of::<u16>(),
+			IndexType::U32 => std::mem::size_of::<u32>(),
+		}
+	}
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum MeshIndices {
+	U16(Vec<u16>),
+	U32(Vec<u32>),
+}
+
+impl MeshIndices {
+	// Uses 16 bit indices whenever every vertex can be addressed with one.
+	pub fn new(indices: Vec<u32>, vertex_count: usize) -> Self {
+		if vertex_count <= u16::MAX as usize + 1 {
+			Self::U16(indices.into_iter().map(|index| index as u16).collect())
+		} else {
+			Self::U32(indices)
+		}
+	}
+
+	pub fn index_type(&self) -> IndexType {
+		match self {
+			MeshIndices::U16(..) => IndexType::U16,
+			MeshIndices::U32(..) => IndexType::U32,
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		match self {
+			MeshIndices::U16(indices) => indices.len(),
+			MeshIndices::U32(indices) => indices.len(),
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	pub fn as_bytes(&self) -> &[u8] {
+		match self {
+			MeshIndices::U16(indices) => bytemuck::cast_slice(indices),
+			MeshIndices::U32(indices) => bytemuck::cast_slice(indices),
+		}
+	}
+}
+
 #[derive(Hash, PartialEq, Eq)]
 pub struct Mesh {
 	pub vertex_buffer: GpuBuffer,
 	pub index_buffer: GpuBuffer,
 	pub index_count: u32,
+	pub index_type: IndexType,
 }
 
 impl UploadContext {
-	pub fn create_mesh(&mut self, vertices: &[Vertex], indices: &[u16]) -> Mesh {
+	pub fn create_mesh(&mut self, vertices: &[Vertex], indices: &MeshIndices) -> Mesh {
 		tracy::span!();
 		let vertex_buffer = self.create_buffer(
 			std::mem::size_of::<Vertex>() * vertices.len(),
@@ -352,20 +826,26 @@ impl UploadContext {
 		);
 
 		let index_count = indices.len() as u32;
-		let index_buffer = self.create_buffer(
-			std::mem::size_of::<u16>() * indices.len(),
-			MemoryLocation::GpuOnly,
-			BufferUsage::IndexBuffer,
-			None,
-			Some(bytemuck::cast_slice(indices)),
-		);
+		let index_type = indices.index_type();
+		let index_buffer = self.create_buffer(index_type.size() * indices.len(), MemoryLocation::GpuOnly, BufferUsage::IndexBuffer, None, Some(indices.as_bytes()));
 
 		Mesh {
 			vertex_buffer,
 			index_buffer,
 			index_count,
+			index_type,
 		}
 	}
+
+	pub fn create_texture_from_package(&mut self, package: &TexturePackage) -> Result<Texture, TextureError> {
+		tracy::span!();
+		let desc = TextureDesc {
+			mip_levels: package.mip_levels,
+			..TextureDesc::new_2d(package.width, package.height, package.format, TextureUsage::SAMPLED | TextureUsage::TRANSFER_DST)
+		};
+
+		self.create_texture_with_data(&desc, &package.data)
+	}
 }
 
 impl GraphicsDevice {
@@ -379,7 +859,88 @@ impl GraphicsDevice {
 impl GraphicsContext {
 	pub fn draw_mesh(&self, mesh: &Mesh) {
 		self.bind_vertex_buffer(&mesh.vertex_buffer);
-		self.bind_index_buffer(&mesh.index_buffer);
+		self.bind_index_buffer(&mesh.index_buffer, mesh.index_type);
 		self.draw_indexed(mesh.index_count);
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn non_power_of_two_mips_round_down() {
+		let desc = TextureDesc::new_2d(300, 100, TextureFormat::R8UNorm, TextureUsage::SAMPLED);
+
+		assert_eq!(desc.full_mip_levels(), 9);
+		assert_eq!(desc.mip_size(1), (150, 50, 1));
+		assert_eq!(desc.mip_size(3), (37, 12, 1));
+		assert_eq!(desc.mip_size(6), (4, 1, 1));
+		assert_eq!(desc.mip_size(8), (1, 1, 1));
+
+		let desc = TextureDesc {
+			mip_levels: desc.full_mip_levels(),
+			..desc
+		};
+		assert_eq!(desc.byte_size(), 30000 + 7500 + 1875 + 444 + 108 + 27 + 4 + 2 + 1);
+	}
+
+	#[test]
+	fn depth_of_3d_textures_halves_with_every_mip() {
+		let desc = TextureDesc {
+			depth: 4,
+			dimension: TextureDimension::D3,
+			..TextureDesc::new_2d(16, 8, TextureFormat::RGBA8UNorm, TextureUsage::SAMPLED)
+		};
+
+		assert_eq!(desc.full_mip_levels(), 5);
+		assert_eq!(desc.mip_size(1), (8, 4, 2));
+		assert_eq!(desc.mip_size(2), (4, 2, 1));
+		assert_eq!(desc.mip_size(4), (1, 1, 1));
+
+		let desc = TextureDesc { mip_levels: 3, ..desc };
+		assert_eq!(desc.byte_size(), (16 * 8 * 4 + 8 * 4 * 2 + 4 * 2 * 1) * 4);
+	}
+
+	#[test]
+	fn depth_alone_decides_the_mip_count() {
+		let desc = TextureDesc {
+			depth: 64,
+			dimension: TextureDimension::D3,
+			..TextureDesc::new_2d(4, 4, TextureFormat::R8UNorm, TextureUsage::SAMPLED)
+		};
+
+		assert_eq!(desc.full_mip_levels(), 7);
+		assert_eq!(desc.mip_size(3), (1, 1, 8));
+	}
+
+	#[test]
+	fn byte_size_counts_layers_faces_samples_and_blocks() {
+		let cube = TextureDesc {
+			dimension: TextureDimension::Cube,
+			mip_levels: 3,
+			..TextureDesc::new_2d(4, 4, TextureFormat::RGBA8UNorm, TextureUsage::SAMPLED)
+		};
+		assert_eq!(cube.byte_size(), (64 + 16 + 4) * 6);
+
+		let array = TextureDesc {
+			dimension: TextureDimension::D2Array,
+			array_layers: 3,
+			..TextureDesc::new_2d(4, 4, TextureFormat::RGBA8UNorm, TextureUsage::SAMPLED)
+		};
+		assert_eq!(array.byte_size(), 64 * 3);
+
+		let multisampled = TextureDesc {
+			samples: SampleCount::X4,
+			..TextureDesc::new_2d(4, 4, TextureFormat::RGBA8UNorm, TextureUsage::ATTACHMENT)
+		};
+		assert_eq!(multisampled.byte_size(), 64 * 4);
+
+		// 5x5, 2x2 and 1x1 all get padded out to whole 4x4 blocks.
+		let compressed = TextureDesc {
+			mip_levels: 3,
+			..TextureDesc::new_2d(5, 5, TextureFormat::BC1RGBAUNorm, TextureUsage::SAMPLED)
+		};
+		assert_eq!(compressed.byte_size(), 4 * 8 + 8 + 8);
+	}
+}
@@ -1,5 +1,86 @@
 use super::*;
 use std::collections::HashSet;
+use std::path::Path;
+use thiserror::Error;
+
+// Problems that make the graph impossible to execute, nothing gets recorded when any of these are found.
+#[derive(Error, Debug, Clone)]
+pub enum RenderGraphError {
+	#[error("No output render pass was added to the render graph")]
+	NoOutput,
+	#[error("Multiple output render passes were found in passes {0:?}")]
+	MultipleOutputs(Vec<&'static str>),
+	#[error("Passes depend on each other in a cycle {0:?}")]
+	DependencyCycle(Vec<&'static str>),
+	#[error("Pass {pass} reads {resource} which is never written by any pass")]
+	ReadOfUnwrittenResource { pass: &'static str, resource: &'static str },
+	#[error("Descriptor set {descriptor} binds a {found} to binding {binding} which expects a {expected:?}")]
+	DescriptorBindingMismatch {
+		descriptor: &'static str,
+		binding: u32,
+		expected: DescriptorBindingType,
+		found: &'static str,
+	},
+	#[error("Descriptor set {descriptor} is missing binding {binding} ({expected:?})")]
+	MissingDescriptorBinding {
+		descriptor: &'static str,
+		binding: u32,
+		expected: DescriptorBindingType,
+	},
+	#[error("Descriptor set {descriptor} binds {binding} which does not exist in its descriptor set info")]
+	UnknownDescriptorBinding { descriptor: &'static str, binding: u32 },
+	#[error("Pass {0} runs on the async compute queue but records graphics commands")]
+	GraphicsWorkOnAsyncComputePass(&'static str),
+	#[error("Pipeline {pipeline} has {blend_states} blend states but its render pass only has {color_attachments} color attachments")]
+	BlendStateCountMismatch { pipeline: &'static str, blend_states: usize, color_attachments: usize },
+	#[error("Pipeline {0} enables stencil testing but its render pass has no stencil attachment")]
+	StencilWithoutStencilAttachment(&'static str),
+	#[error("Descriptor set {descriptor} binds the {aspect:?} aspect of attachment {attachment} which its format does not support")]
+	UnsupportedAttachmentAspect {
+		descriptor: &'static str,
+		attachment: &'static str,
+		aspect: TextureAspect,
+	},
+	#[error("Descriptor set {descriptor} reads multisampled attachment {attachment}, it has to be resolved into a single sample attachment first")]
+	MultisampledAttachmentRead { descriptor: &'static str, attachment: &'static str },
+	#[error("Attachment {attachment} does not have the subresource {subresource:?}")]
+	InvalidAttachmentSubresource { attachment: &'static str, subresource: TextureSubresource },
+	#[error("Render pass {0} has attachments with different sample counts")]
+	MismatchedAttachmentSamples(&'static str),
+	#[error("Render pass {render_pass} has {resolve_attachments} resolve attachments but {color_attachments} color attachments")]
+	ResolveAttachmentCountMismatch {
+		render_pass: &'static str,
+		color_attachments: usize,
+		resolve_attachments: usize,
+	},
+	#[error("Render pass {render_pass} cannot resolve {attachment} into {resolve_attachment}, resolves go from a multisampled attachment into a single sample attachment of the same format")]
+	InvalidResolveAttachment {
+		render_pass: &'static str,
+		attachment: &'static str,
+		resolve_attachment: &'static str,
+	},
+	#[error("Pipeline {pipeline} is created with {samples:?} but its render pass has {render_pass_samples:?} attachments")]
+	PipelineSampleCountMismatch {
+		pipeline: &'static str,
+		samples: SampleCount,
+		render_pass_samples: SampleCount,
+	},
+	#[error("Attachment {attachment} uses the block compressed format {format:?}, which can't be rendered to")]
+	CompressedAttachmentFormat { attachment: &'static str, format: TextureFormat },
+	#[error("Attachment {attachment} is created with {samples:?}, which the device does not support for {format:?} attachments")]
+	UnsupportedAttachmentSamples { attachment: &'static str, format: TextureFormat, samples: SampleCount },
+}
+
+// Problems that don't stop the graph from executing, but are most likely a mistake.
+#[derive(Error, Debug, Clone)]
+pub enum RenderGraphWarning {
+	#[error("Pass {0} does not contribute to the output and was culled")]
+	CulledPass(&'static str),
+	#[error("Attachment {attachment} is written by pass {pass} but never read")]
+	UnreadAttachment { pass: &'static str, attachment: &'static str },
+	#[error("Buffer {buffer} is written by pass {pass} but never read")]
+	UnreadBuffer { pass: &'static str, buffer: &'static str },
+}
 
 #[derive(Debug, Clone)]
 enum PassCmd {
@@ -40,25 +121,58 @@ enum PassCmd {
 	},
 }
 
-#[derive(Clone, Copy, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 struct BufferCacheKey {
 	size: usize,
 	usage: BufferUsage,
 	location: MemoryLocation,
 }
 
+impl BufferCacheKey {
+	// GPU only buffers can share a physical buffer that is as large as the largest of them and created with all of their usages.
+	// NOTE: Host visible buffers are written by the CPU at any point during the frame, so they can't safely be pooled.
+	fn merge(self, other: Self) -> Option<Self> {
+		(self.location == MemoryLocation::GpuOnly && other.location == MemoryLocation::GpuOnly).then_some(Self {
+			size: self.size.max(other.size),
+			usage: self.usage | other.usage,
+			location: self.location,
+		})
+	}
+}
+
 #[derive(Default)]
 struct BufferCache {
 	buffers: Vec<GpuBuffer>,
 	cache: HashMap<BufferCacheKey, Vec<usize>>,
 }
 
-#[derive(Clone, Copy, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 struct AttachmentCacheKey {
 	width: u32,
 	height: u32,
 	format: TextureFormat,
 	usage: TextureUsage,
+	samples: SampleCount,
+	mip_levels: u32,
+	array_layers: u32,
+}
+
+impl AttachmentCacheKey {
+	// Attachments which only differ in their usage can share a physical texture that is created with all of their usages.
+	fn merge(self, other: Self) -> Option<Self> {
+		let usage = self.usage | other.usage;
+		(Self { usage, ..self } == Self { usage, ..other }).then_some(Self { usage, ..self })
+	}
+
+	fn texture_desc(&self) -> TextureDesc {
+		TextureDesc {
+			dimension: if self.array_layers > 1 { TextureDimension::D2Array } else { TextureDimension::D2 },
+			array_layers: self.array_layers,
+			mip_levels: self.mip_levels,
+			samples: self.samples,
+			..TextureDesc::new_2d(self.width, self.height, self.format, self.usage | TextureUsage::ATTACHMENT)
+		}
+	}
 }
 
 #[derive(Default)]
@@ -71,7 +185,7 @@ struct AttachmentCache {
 struct FramebufferCacheKey {
 	width: u32,
 	height: u32,
-	attachments: Vec<usize>,
+	attachments: Vec<(usize, TextureSubresource)>,
 	render_pass: usize,
 }
 
@@ -85,6 +199,7 @@ struct FramebufferCache {
 struct RenderPassCacheKey {
 	color_attachment_descs: Vec<AttachmentDescription>,
 	depth_attachment_desc: Option<AttachmentDescription>,
+	resolve_attachment_descs: Vec<AttachmentDescription>,
 }
 
 #[derive(Default)]
@@ -105,6 +220,9 @@ struct RasterPipelineCacheKey {
 	push_constant_bytes: usize,
 	vertex_input_info: VertexInputInfo,
 	polygon_mode: PolygonMode,
+	blend_states: Vec<ColorBlendState>,
+	stencil: Option<StencilState>,
+	samples: SampleCount,
 }
 
 #[derive(Default)]
@@ -140,6 +258,11 @@ enum DescriptorHeapCacheKeyBinding {
 	},
 	Attachment {
 		attachment: usize,
+		aspect: TextureAspect,
+		subresource: Option<TextureSubresource>,
+	},
+	Sampler {
+		sampler: ash::vk::Sampler,
 	},
 }
 
@@ -161,18 +284,65 @@ pub struct RenderGraphCache {
 	render_pass_cache: RenderPassCache,
 	raster_pipeline_cache: RasterPipelineCache,
 	compute_pipeline_cache: ComputePipelineCache,
+	pipeline_cache: PipelineCache,
 	descriptor_layout_cache: DescriptorLayoutCache,
 	graphics_descriptor_heap_caches: HashMap<*const DescriptorSetInfo, DescriptorHeapCache>,
 	compute_descriptor_heap_caches: HashMap<*const DescriptorSetInfo, DescriptorHeapCache>,
+	pooling_report: RenderGraphPoolingReport,
+	// The state every physical attachment and buffer was left in by the last frame that used it, which the next frame starts from.
+	resource_states: HashMap<GraphPhysicalSubresource, GraphResourceState>,
+}
+
+// How well the transient resources of the last executed graph were pooled. Virtual resources with non-overlapping lifetimes take
+// turns using the same physical resource when they're compatible: attachments need the same size, format, sample count, mips and
+// layers but may differ in usage, GPU only buffers may differ in both size and usage. Attachments of different sizes or
+// formats never share memory, since that would need aliased allocations which the allocator doesn't do.
+// The reused bytes are what creating a separate resource for every virtual resource would have cost on top.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RenderGraphPoolingReport {
+	pub virtual_attachments: usize,
+	pub physical_attachments: usize,
+	pub virtual_buffers: usize,
+	pub physical_buffers: usize,
+	pub attachment_bytes_reused: u64,
+	pub buffer_bytes_reused: u64,
+}
+
+impl RenderGraphPoolingReport {
+	pub fn bytes_reused(&self) -> u64 {
+		self.attachment_bytes_reused + self.buffer_bytes_reused
+	}
 }
 
 impl RenderGraphCache {
+	// Persists compiled pipelines to `pipeline_cache_path` so that later launches don't have to recompile them.
+	// A default constructed cache doesn't keep pipelines around between launches.
+	pub fn new(graphics_device: &GraphicsDevice, pipeline_cache_path: &Path) -> Self {
+		Self {
+			pipeline_cache: graphics_device.load_pipeline_cache(pipeline_cache_path),
+			..Default::default()
+		}
+	}
+
+	pub fn get_pooling_report(&self) -> RenderGraphPoolingReport {
+		self.pooling_report
+	}
+
+	pub fn get_pipeline_cache_stats(&self) -> PipelineCacheStats {
+		self.pipeline_cache.get_stats()
+	}
+
+	// The pipeline cache is saved when the render graph cache is destroyed, this saves it in between.
+	pub fn save_pipeline_cache(&self, graphics_device: &GraphicsDevice) {
+		graphics_device.save_pipeline_cache(&self.pipeline_cache);
+	}
+
 	fn alloc_render_pass(&mut self, graphics_device: &GraphicsDevice, key: &RenderPassCacheKey) -> usize {
 		*self.render_pass_cache.cache.entry(key.clone()).or_insert_with(|| {
 			println!("Allocated render pass! {:?}", key);
 			self.render_pass_cache
 				.render_passes
-				.push(graphics_device.create_render_pass(&key.color_attachment_descs, key.depth_attachment_desc));
+				.push(graphics_device.create_render_pass(&key.color_attachment_descs, key.depth_attachment_desc, &key.resolve_attachment_descs));
 
 			self.render_pass_cache.render_passes.len() - 1
 		})
@@ -191,7 +361,7 @@ impl RenderGraphCache {
 			println!("Allocated framebuffer!");
 
 			let render_pass = &self.render_pass_cache.render_passes[key.render_pass];
-			let attachments = key.attachments.iter().map(|a| &self.attachment_cache.attachments[*a]).collect::<Vec<_>>();
+			let attachments = key.attachments.iter().map(|&(a, subresource)| (&self.attachment_cache.attachments[a], subresource)).collect::<Vec<_>>();
 
 			self.framebuffer_cache
 				.framebuffers
@@ -230,6 +400,9 @@ impl RenderGraphCache {
 					key.push_constant_bytes,
 					key.vertex_input_info,
 					key.polygon_mode,
+					&key.blend_states,
+					key.stencil,
+					&mut self.pipeline_cache,
 				)
 			} else {
 				graphics_device.create_raster_pipeline(
@@ -243,6 +416,10 @@ impl RenderGraphCache {
 					key.push_constant_bytes,
 					key.vertex_input_info,
 					key.polygon_mode,
+					&key.blend_states,
+					key.stencil,
+					key.samples,
+					&mut self.pipeline_cache,
 				)
 			});
 
@@ -263,7 +440,7 @@ impl RenderGraphCache {
 			println!("Allocated compute pipeline");
 			self.compute_pipeline_cache
 				.pipelines
-				.push(graphics_device.create_compute_pipeline(&Shader { module: key.cs }, &key.descriptor_layouts));
+				.push(graphics_device.create_compute_pipeline(&Shader { module: key.cs }, &key.descriptor_layouts, &mut self.pipeline_cache));
 
 			self.compute_pipeline_cache.pipelines.len() - 1
 		})
@@ -282,9 +459,10 @@ impl RenderGraphCache {
 		while attachments.len() < count {
 			println!("Allocated attachment!");
 			attachments.push(self.attachment_cache.attachments.len());
+			// NOTE: Block compressed attachments are rejected when they're added to the graph, so this can't fail.
 			self.attachment_cache
 				.attachments
-				.push(graphics_device.create_texture(key.width, key.height, key.format, key.usage | TextureUsage::ATTACHMENT));
+				.push(graphics_device.create_texture(&key.texture_desc()).expect("Failed to create attachment!"));
 		}
 	}
 
@@ -379,6 +557,7 @@ impl RenderGraphCache {
 		}
 
 		graphics_device.destroy_descriptor_layout_cache(self.descriptor_layout_cache);
+		graphics_device.destroy_pipeline_cache(self.pipeline_cache);
 	}
 }
 
@@ -391,6 +570,10 @@ pub struct AttachmentDesc {
 	pub load_op: LoadOp,
 	pub store_op: StoreOp,
 	pub usage: TextureUsage,
+	pub samples: SampleCount,
+	// Attachments with more than one layer are 2D array textures.
+	pub mip_levels: u32,
+	pub array_layers: u32,
 }
 
 #[derive(Clone, Copy, Hash, PartialEq, Eq)]
@@ -405,6 +588,9 @@ pub struct RenderPassDesc<'a, 'b> {
 	pub name: &'static str,
 	pub color_attachments: &'b mut [&'b mut MutableGraphAttachmentHandle],
 	pub depth_attachment: Option<&'a mut MutableGraphAttachmentHandle>,
+	// Either empty or one single sample attachment for every color attachment, which the multisampled color attachment
+	// with the same index is resolved into at the end of the render pass. Later passes read the resolved attachment.
+	pub resolve_attachments: &'b mut [&'b mut MutableGraphAttachmentHandle],
 }
 
 #[derive(Clone)]
@@ -420,6 +606,12 @@ pub struct RasterPipelineDesc<'a, 'b> {
 	pub push_constant_bytes: usize,
 	pub vertex_input_info: VertexInputInfo,
 	pub polygon_mode: PolygonMode,
+	// Blending for each color attachment of the render pass in order, attachments without an entry are opaque.
+	pub blend_states: &'b [ColorBlendState],
+	// Stencil testing requires the depth attachment of the render pass to have a stencil format.
+	pub stencil: Option<StencilState>,
+	// Has to match the sample count of the render pass attachments.
+	pub samples: SampleCount,
 }
 
 #[derive(Clone)]
@@ -436,6 +628,7 @@ pub enum DescriptorBindingDesc<'a, 'b> {
 	MutableBuffer(&'b mut MutableGraphBufferHandle),
 	Attachment(GraphAttachmentHandle),
 	MutableAttachment(&'b mut MutableGraphAttachmentHandle),
+	Sampler(SamplerDesc),
 }
 
 pub struct DescriptorDesc<'a, 'b> {
@@ -480,6 +673,7 @@ enum GraphOwnedResourceDescriptorBinding {
 	MutableBuffer(MutableGraphBufferHandle),
 	Attachment(GraphAttachmentHandle),
 	MutableAttachment(MutableGraphAttachmentHandle),
+	Sampler(SamplerDesc),
 }
 
 #[derive(Debug, Clone)]
@@ -496,6 +690,9 @@ enum GraphOwnedResource {
 		push_constant_bytes: usize,
 		vertex_input_info: VertexInputInfo,
 		polygon_mode: PolygonMode,
+		blend_states: Vec<ColorBlendState>,
+		stencil: Option<StencilState>,
+		samples: SampleCount,
 	},
 	ComputePipeline {
 		name: &'static str,
@@ -506,6 +703,7 @@ enum GraphOwnedResource {
 		name: &'static str,
 		color_attachments: Vec<MutableGraphAttachmentHandle>,
 		depth_attachment: Option<MutableGraphAttachmentHandle>,
+		resolve_attachments: Vec<MutableGraphAttachmentHandle>,
 	},
 	OutputRenderPass {},
 	Attachment {
@@ -516,6 +714,9 @@ enum GraphOwnedResource {
 		usage: TextureUsage,
 		load_op: LoadOp,
 		store_op: StoreOp,
+		samples: SampleCount,
+		mip_levels: u32,
+		array_layers: u32,
 	},
 	Buffer {
 		name: &'static str,
@@ -545,36 +746,75 @@ pub struct GraphComputePipelineHandle {
 	id: usize,
 }
 
+// NOTE: Handles only carry the layout that the attachment is viewed in. The stages and accesses of every use are derived
+// from how the pass binds the resource, and the graph tracks the state of every resource across passes to generate barriers.
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub struct GraphAttachmentHandle {
 	id: usize,
-	src_stage: ash::vk::PipelineStageFlags,
-	dst_stage: ash::vk::PipelineStageFlags,
-	src_access: ash::vk::AccessFlags,
-	dst_access: ash::vk::AccessFlags,
-	initial_layout: ImageLayout,
-	final_layout: ImageLayout,
+	layout: ImageLayout,
+	aspect: TextureAspect,
+	subresource: Option<TextureSubresource>,
 }
 
+// NOTE: Narrowing a handle down to a subresource changes which view is bound and which mips and layers the barriers of the pass cover,
+// so a pass can read one mip of an attachment while rendering to the next one.
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub struct MutableGraphAttachmentHandle {
 	id: usize,
 	layout: ImageLayout,
-	stage: ash::vk::PipelineStageFlags,
-	access: ash::vk::AccessFlags,
+	subresource: Option<TextureSubresource>,
 }
 
 impl MutableGraphAttachmentHandle {
+	// Views a single mip of every layer. Render passes always render to a single layer, layer 0 unless another one is selected.
+	pub fn mip(self, mip: u32) -> Self {
+		Self {
+			subresource: Some(TextureSubresource { mip, layer: None }),
+			..self
+		}
+	}
+
+	pub fn layer(self, mip: u32, layer: u32) -> Self {
+		Self {
+			subresource: Some(TextureSubresource { mip, layer: Some(layer) }),
+			..self
+		}
+	}
+
 	pub fn read(self) -> GraphAttachmentHandle {
 		GraphAttachmentHandle {
 			id: self.id,
-			src_stage: self.stage,
-			src_access: self.access,
-			initial_layout: self.layout,
-			// TODO(Brandon): In the future, we might need to support different configurations for read attachments. I _think_ this will be fine for now, but it's still hard-coded :/
-			dst_stage: ash::vk::PipelineStageFlags::VERTEX_SHADER | ash::vk::PipelineStageFlags::FRAGMENT_SHADER | ash::vk::PipelineStageFlags::COMPUTE_SHADER,
-			dst_access: ash::vk::AccessFlags::SHADER_READ,
-			final_layout: ImageLayout::ShaderReadOnlyOptimal,
+			layout: ImageLayout::ShaderReadOnlyOptimal,
+			aspect: TextureAspect::All,
+			subresource: self.subresource,
+		}
+	}
+
+	// Depth stencil attachments have to be sampled through one of their aspects.
+	pub fn read_depth(self) -> GraphAttachmentHandle {
+		GraphAttachmentHandle {
+			id: self.id,
+			layout: ImageLayout::DepthStencilReadOnlyOptimal,
+			aspect: TextureAspect::Depth,
+			subresource: self.subresource,
+		}
+	}
+
+	pub fn read_stencil(self) -> GraphAttachmentHandle {
+		GraphAttachmentHandle {
+			id: self.id,
+			layout: ImageLayout::DepthStencilReadOnlyOptimal,
+			aspect: TextureAspect::Stencil,
+			subresource: self.subresource,
+		}
+	}
+
+	// Render passes only ever render to a single layer of the selected mip.
+	fn render_target(self) -> TextureSubresource {
+		let subresource = self.subresource.unwrap_or(TextureSubresource { mip: 0, layer: None });
+		TextureSubresource {
+			mip: subresource.mip,
+			layer: Some(subresource.layer.unwrap_or(0)),
 		}
 	}
 }
@@ -582,28 +822,16 @@ impl MutableGraphAttachmentHandle {
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub struct GraphBufferHandle {
 	id: usize,
-	src_stage: ash::vk::PipelineStageFlags,
-	dst_stage: ash::vk::PipelineStageFlags,
-	src_access: ash::vk::AccessFlags,
-	dst_access: ash::vk::AccessFlags,
 }
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub struct MutableGraphBufferHandle {
 	id: usize,
-	stage: ash::vk::PipelineStageFlags,
-	access: ash::vk::AccessFlags,
 }
 
 impl MutableGraphBufferHandle {
 	pub fn read(self) -> GraphBufferHandle {
-		GraphBufferHandle {
-			id: self.id,
-			src_stage: self.stage,
-			src_access: self.access,
-			dst_stage: ash::vk::PipelineStageFlags::VERTEX_SHADER | ash::vk::PipelineStageFlags::FRAGMENT_SHADER | ash::vk::PipelineStageFlags::COMPUTE_SHADER,
-			dst_access: ash::vk::AccessFlags::SHADER_READ,
-		}
+		GraphBufferHandle { id: self.id }
 	}
 }
 
@@ -627,16 +855,167 @@ struct PassHandle {
 	id: usize,
 }
 
-#[derive(Debug)]
-struct PassDependencyNode {
-	pass: PassHandle,
-	dependencies: Vec<PassDependencyNode>,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PassVisitState {
+	Visiting,
+	Visited,
+}
+
+// A single layer of a single mip of an attachment, which is what the graph tracks the state of. Buffers only have mip 0 of layer 0.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+struct GraphSubresource {
+	id: usize,
+	mip: u32,
+	layer: u32,
+}
+
+// A single layer of a single mip of an attachment or a buffer in the cache, which is what is remembered between frames.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+enum GraphPhysicalSubresource {
+	Attachment { index: usize, mip: u32, layer: u32 },
+	Buffer(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct GraphSubresourceRange {
+	base_mip: u32,
+	mip_count: u32,
+	base_layer: u32,
+	layer_count: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum GraphBarrierKind {
+	Image {
+		old_layout: ImageLayout,
+		new_layout: ImageLayout,
+		range: GraphSubresourceRange,
+	},
+	Buffer,
+}
+
+// A barrier on a virtual resource that gets inserted before a pass executes.
+#[derive(Debug, Clone, Copy)]
+struct GraphBarrier {
+	resource: usize,
+	kind: GraphBarrierKind,
+	src_stage: ash::vk::PipelineStageFlags,
+	dst_stage: ash::vk::PipelineStageFlags,
+	src_access: ash::vk::AccessFlags,
+	dst_access: ash::vk::AccessFlags,
+	// Source and destination queue of a queue family ownership transfer. Both halves of the transfer carry the same value.
+	queue_transfer: Option<(QueueType, QueueType)>,
+}
+
+impl GraphBarrier {
+	// The range that covers both barriers if `next` continues this one on the following layers (or mips) and is otherwise the same.
+	fn merged_range(&self, next: &GraphBarrier, across_mips: bool) -> Option<GraphSubresourceRange> {
+		let (
+			GraphBarrierKind::Image { old_layout, new_layout, range },
+			GraphBarrierKind::Image {
+				old_layout: next_old_layout,
+				new_layout: next_new_layout,
+				range: next_range,
+			},
+		) = (self.kind, next.kind)
+		else {
+			return None;
+		};
+
+		let same = self.resource == next.resource
+			&& old_layout == next_old_layout
+			&& new_layout == next_new_layout
+			&& self.src_stage == next.src_stage
+			&& self.dst_stage == next.dst_stage
+			&& self.src_access == next.src_access
+			&& self.dst_access == next.dst_access
+			&& self.queue_transfer == next.queue_transfer;
+
+		if !same {
+			return None;
+		}
+
+		if across_mips && range.base_layer == next_range.base_layer && range.layer_count == next_range.layer_count && range.base_mip + range.mip_count == next_range.base_mip {
+			Some(GraphSubresourceRange {
+				mip_count: range.mip_count + next_range.mip_count,
+				..range
+			})
+		} else if !across_mips && range.base_mip == next_range.base_mip && range.mip_count == next_range.mip_count && range.base_layer + range.layer_count == next_range.base_layer {
+			Some(GraphSubresourceRange {
+				layer_count: range.layer_count + next_range.layer_count,
+				..range
+			})
+		} else {
+			None
+		}
+	}
+}
+
+// Barriers are planned for every layer of every mip on its own, this merges the ones of neighbouring subresources back together.
+// Layers are merged first and then mips, which relies on the barriers being sorted by subresource.
+fn merge_barriers(mut barriers: Vec<GraphBarrier>) -> Vec<GraphBarrier> {
+	for across_mips in [false, true] {
+		let mut merged = Vec::<GraphBarrier>::with_capacity(barriers.len());
+		for barrier in barriers {
+			let range = merged.last().and_then(|last| last.merged_range(&barrier, across_mips));
+			if let (
+				Some(range),
+				Some(GraphBarrier {
+					kind: GraphBarrierKind::Image { range: last_range, .. },
+					..
+				}),
+			) = (range, merged.last_mut())
+			{
+				*last_range = range;
+			} else {
+				merged.push(barrier);
+			}
+		}
+		barriers = merged;
+	}
+	barriers
+}
+
+// Everything that needs to be synchronized around a single pass.
+#[derive(Debug, Clone, Default)]
+struct GraphPassSync {
+	// Recorded before the pass, includes the acquire half of ownership transfers.
+	barriers: Vec<GraphBarrier>,
+	// The release half of ownership transfers, recorded right after the pass on the queue that gives up the resource.
+	releases: Vec<GraphBarrier>,
+	// Passes on the other queue which have to finish before the given stages of this pass can start.
+	waits: Vec<(usize, ash::vk::PipelineStageFlags)>,
+}
+
+// How a single pass uses a subresource of an attachment or a buffer. Buffers don't have a layout.
+#[derive(Debug, Clone, Copy)]
+struct GraphResourceUsage {
+	layout: Option<ImageLayout>,
+	stage: ash::vk::PipelineStageFlags,
+	access: ash::vk::AccessFlags,
+	write: bool,
+	// The pass overwrites the resource without looking at it, e.g. a render pass attachment which gets cleared.
+	discard: bool,
+}
+
+// The state a subresource is left in after the last pass that used it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct GraphResourceState {
+	layout: ImageLayout,
+	stage: ash::vk::PipelineStageFlags,
+	access: ash::vk::AccessFlags,
+	// Whether `access` contains a write that later passes need to wait on, otherwise it's the union of every read since the last write.
+	written: bool,
+	queue: QueueType,
+	// Index into the sorted pass list of the last pass that used the subresource.
+	last_pass: usize,
 }
 
 #[derive(Debug, Clone)]
 pub struct RecordedPass {
 	name: &'static str,
 	pass: PassHandle,
+	queue: QueueType,
 	cmds: Vec<PassCmd>,
 	read_attachments: HashSet<GraphAttachmentHandle>,
 	write_attachments: HashSet<MutableGraphAttachmentHandle>,
@@ -651,6 +1030,7 @@ pub struct RenderGraph<'a> {
 	resource_to_owning_pass: HashMap<usize, PassHandle>,
 	imported_resources: Vec<GraphImportedResource<'a>>,
 	cache: &'a mut RenderGraphCache,
+	record_errors: Vec<RenderGraphError>,
 }
 
 struct VirtualToPhysicalResourceMap<T: Copy> {
@@ -678,12 +1058,60 @@ struct GraphPhysicalResourceMap {
 	framebuffer_map: VirtualToPhysicalResourceMap<usize>,
 	raster_pipeline_map: VirtualToPhysicalResourceMap<usize>,
 	compute_pipeline_map: VirtualToPhysicalResourceMap<usize>,
+	// Virtual resources which take over a physical resource from another virtual resource earlier in the frame.
+	pooled: HashSet<usize>,
+}
+
+// Greedily packs virtual resources into as few physical slots as possible. Two resources can only share a slot if their
+// [first use, last use] intervals in the sorted pass list don't overlap and `merge` combines their cache keys into one that suits both.
+// A resource takes the first free slot that already suits it as is, and only grows a free slot if none does.
+// Returns the slot of every virtual resource, the key of every slot and the virtual resources that take over a slot from another one.
+fn assign_pool_slots<K: Copy + PartialEq>(
+	virtual_resources: &[(usize, K)],
+	lifetimes: &HashMap<usize, (usize, usize)>,
+	merge: impl Fn(K, K) -> Option<K>,
+) -> (Vec<(usize, usize)>, Vec<K>, Vec<usize>) {
+	let mut sorted = virtual_resources.to_vec();
+	sorted.sort_by_key(|(id, _)| lifetimes[id]);
+
+	let mut slot_keys: Vec<K> = Vec::new();
+	let mut slot_last_use: Vec<usize> = Vec::new();
+	let mut assignments = Vec::with_capacity(sorted.len());
+	let mut handoffs = Vec::new();
+
+	for (id, key) in sorted {
+		let (first, last) = lifetimes[&id];
+
+		let free_slots = (0..slot_keys.len())
+			.filter(|&slot| slot_last_use[slot] < first)
+			.filter_map(|slot| merge(slot_keys[slot], key).map(|merged| (slot, merged)))
+			.collect::<Vec<_>>();
+
+		match free_slots.iter().find(|&&(slot, merged)| merged == slot_keys[slot]).or(free_slots.first()) {
+			Some(&(slot, merged)) => {
+				slot_keys[slot] = merged;
+				slot_last_use[slot] = last;
+				handoffs.push(id);
+				assignments.push((id, slot));
+			}
+			None => {
+				slot_keys.push(key);
+				slot_last_use.push(last);
+				assignments.push((id, slot_keys.len() - 1));
+			}
+		}
+	}
+
+	(assignments, slot_keys, handoffs)
 }
 
 impl GraphPhysicalResourceMap {
-	fn new(graph: &mut RenderGraph, graphics_device: &mut GraphicsDevice, graphics_context: &mut GraphicsContext) -> Self {
-		let attachment_map = Self::alloc_attachments(graph, graphics_device);
-		let buffer_map = Self::alloc_buffers(graph, graphics_device);
+	fn new(graph: &mut RenderGraph, passes: &[PassHandle], graphics_device: &mut GraphicsDevice, graphics_context: &mut GraphicsContext) -> Self {
+		let lifetimes = graph.compute_resource_lifetimes(passes);
+		let mut pooled = HashSet::new();
+
+		let attachment_map = Self::alloc_attachments(graph, graphics_device, &lifetimes, &mut pooled);
+		let buffer_map = Self::alloc_buffers(graph, graphics_device, &lifetimes, &mut pooled);
 		let descriptor_map = Self::alloc_descriptors(graph, graphics_device, graphics_context, &attachment_map, &buffer_map);
 		let (render_pass_map, framebuffer_map) = Self::alloc_render_passes(graph, graphics_device, &attachment_map);
 		let raster_pipeline_map = Self::alloc_raster_pipelines(graph, graphics_device, graphics_context, &render_pass_map);
@@ -697,6 +1125,7 @@ impl GraphPhysicalResourceMap {
 			framebuffer_map,
 			raster_pipeline_map,
 			compute_pipeline_map,
+			pooled,
 		}
 	}
 
@@ -735,83 +1164,174 @@ impl GraphPhysicalResourceMap {
 		(descriptor, graph.cache.get_compute_descriptor_heap(info))
 	}
 
-	fn get_attachment<'a>(&self, graph: &'a RenderGraph, attachment: GraphAttachmentHandle) -> &'a Texture {
-		let physical_attachment = self.attachment_map.get_physical(attachment.id);
+	fn get_attachment<'a>(&self, graph: &'a RenderGraph, id: usize) -> &'a Texture {
+		let physical_attachment = self.attachment_map.get_physical(id);
 
 		&graph.cache.attachment_cache.attachments[physical_attachment]
 	}
 
-	fn get_mutable_attachment<'a>(&self, graph: &'a RenderGraph, attachment: MutableGraphAttachmentHandle) -> &'a Texture {
-		let physical_attachment = self.attachment_map.get_physical(attachment.id);
+	fn get_buffer<'a>(&self, graph: &'a RenderGraph, id: usize) -> &'a GpuBuffer {
+		let physical_buffer = self.buffer_map.get_physical(id);
 
-		&graph.cache.attachment_cache.attachments[physical_attachment]
+		&graph.cache.buffer_cache.buffers[physical_buffer]
+	}
+
+	fn get_physical_subresource(&self, graph: &RenderGraph, subresource: GraphSubresource) -> GraphPhysicalSubresource {
+		match graph.owned_resources[subresource.id] {
+			GraphOwnedResource::Attachment { .. } => GraphPhysicalSubresource::Attachment {
+				index: self.attachment_map.get_physical(subresource.id),
+				mip: subresource.mip,
+				layer: subresource.layer,
+			},
+			GraphOwnedResource::Buffer { .. } => GraphPhysicalSubresource::Buffer(self.buffer_map.get_physical(subresource.id)),
+			_ => unreachable!("Invalid attachment or buffer!"),
+		}
 	}
 
-	fn get_buffer<'a>(&self, graph: &'a RenderGraph, buffer: GraphBufferHandle) -> &'a GpuBuffer {
-		let physical_buffer = self.buffer_map.get_physical(buffer.id);
+	// The state that the previous frame left the physical resource of every attachment and buffer in. Resources that take over a pooled
+	// resource from another one this frame don't get one since they start from scratch, neither do physical resources that were just created.
+	fn get_initial_states(&self, graph: &RenderGraph) -> HashMap<GraphSubresource, GraphResourceState> {
+		let mut initial_states = HashMap::new();
 
-		&graph.cache.buffer_cache.buffers[physical_buffer]
+		for (id, resource) in graph.owned_resources.iter().enumerate() {
+			if self.pooled.contains(&id) {
+				continue;
+			}
+
+			let subresources = match resource {
+				GraphOwnedResource::Attachment { .. } => graph.attachment_subresources(id, None),
+				GraphOwnedResource::Buffer { .. } => vec![GraphSubresource { id, mip: 0, layer: 0 }],
+				_ => continue,
+			};
+
+			for subresource in subresources {
+				if let Some(state) = graph.cache.resource_states.get(&self.get_physical_subresource(graph, subresource)) {
+					initial_states.insert(subresource, *state);
+				}
+			}
+		}
+
+		initial_states
+	}
+
+	// Remembers the state every physical resource is left in at the end of the frame. Pooled physical resources are left in the
+	// state of the last virtual resource that used them.
+	fn store_final_states(&self, graph: &mut RenderGraph, final_states: HashMap<GraphSubresource, GraphResourceState>) {
+		let mut physical_states = HashMap::<GraphPhysicalSubresource, GraphResourceState>::new();
+
+		for (subresource, state) in final_states {
+			let physical = self.get_physical_subresource(graph, subresource);
+			if physical_states.get(&physical).map_or(true, |existing| existing.last_pass < state.last_pass) {
+				physical_states.insert(physical, state);
+			}
+		}
+
+		graph.cache.resource_states.extend(physical_states);
 	}
 
-	fn alloc_attachments(graph: &mut RenderGraph, graphics_device: &mut GraphicsDevice) -> VirtualToPhysicalResourceMap<usize> {
-		let mut attachment_type_to_virtual = HashMap::<AttachmentCacheKey, Vec<usize>>::new();
+	fn alloc_attachments(
+		graph: &mut RenderGraph,
+		graphics_device: &mut GraphicsDevice,
+		lifetimes: &HashMap<usize, (usize, usize)>,
+		pooled: &mut HashSet<usize>,
+	) -> VirtualToPhysicalResourceMap<usize> {
+		let mut virtual_resources = Vec::new();
 
 		for (i, resource) in graph.owned_resources.iter().enumerate() {
 			match resource {
-				&GraphOwnedResource::Attachment { width, height, format, usage, .. } => {
-					let key = AttachmentCacheKey { width, height, format, usage };
+				&GraphOwnedResource::Attachment {
+					width,
+					height,
+					format,
+					usage,
+					samples,
+					mip_levels,
+					array_layers,
+					..
+				} => {
+					let key = AttachmentCacheKey {
+						width,
+						height,
+						format,
+						usage,
+						samples,
+						mip_levels,
+						array_layers,
+					};
 
-					attachment_type_to_virtual.entry(key).or_default().push(i);
+					virtual_resources.push((i, key));
 				}
 				_ => {}
 			}
 		}
 
-		for (key, virtual_resources) in attachment_type_to_virtual.iter() {
-			graph.cache.alloc_attachments(graphics_device, key, virtual_resources.len());
+		let (assignments, slot_keys, handoffs) = assign_pool_slots(&virtual_resources, lifetimes, AttachmentCacheKey::merge);
+		pooled.extend(handoffs);
+
+		// Slots with the same key get different physical attachments of that key.
+		let mut key_counts = HashMap::<AttachmentCacheKey, usize>::new();
+		let mut slot_indices = Vec::with_capacity(slot_keys.len());
+		for key in &slot_keys {
+			let count = key_counts.entry(*key).or_default();
+			*count += 1;
+			graph.cache.alloc_attachments(graphics_device, key, *count);
+			slot_indices.push(graph.cache.attachment_cache.cache[key][*count - 1]);
 		}
 
 		let mut attachment_map = VirtualToPhysicalResourceMap::new();
-
-		// TODO(Brandon): Optimize this by mapping virtual to physical attachments based on existing framebuffers and descriptors to reduce allocations.
-		for (key, virtual_resources) in attachment_type_to_virtual {
-			for (i, virtual_resource) in virtual_resources.into_iter().enumerate() {
-				let index = graph.cache.attachment_cache.cache[&key][i];
-				attachment_map.map_physical(virtual_resource, index);
-			}
+		for (virtual_resource, slot) in assignments {
+			attachment_map.map_physical(virtual_resource, slot_indices[slot]);
 		}
 
+		let virtual_bytes = virtual_resources.iter().map(|(_, key)| key.texture_desc().byte_size()).sum::<u64>();
+		let physical_bytes = slot_keys.iter().map(|key| key.texture_desc().byte_size()).sum::<u64>();
+
+		let report = &mut graph.cache.pooling_report;
+		report.virtual_attachments = virtual_resources.len();
+		report.physical_attachments = slot_keys.len();
+		report.attachment_bytes_reused = virtual_bytes - physical_bytes;
+
 		attachment_map
 	}
 
-	fn alloc_buffers(graph: &mut RenderGraph, graphics_device: &mut GraphicsDevice) -> VirtualToPhysicalResourceMap<usize> {
-		let mut buffer_type_to_virtual = HashMap::<BufferCacheKey, Vec<usize>>::new();
+	fn alloc_buffers(graph: &mut RenderGraph, graphics_device: &mut GraphicsDevice, lifetimes: &HashMap<usize, (usize, usize)>, pooled: &mut HashSet<usize>) -> VirtualToPhysicalResourceMap<usize> {
+		let mut virtual_resources = Vec::new();
 
 		for (i, resource) in graph.owned_resources.iter().enumerate() {
 			match resource {
 				&GraphOwnedResource::Buffer { size, usage, location, .. } => {
-					let key = BufferCacheKey { size, usage, location };
-
-					buffer_type_to_virtual.entry(key).or_default().push(i);
+					virtual_resources.push((i, BufferCacheKey { size, usage, location }));
 				}
 				_ => {}
 			}
 		}
 
-		for (key, virtual_resources) in buffer_type_to_virtual.iter() {
-			graph.cache.alloc_buffers(graphics_device, key, virtual_resources.len());
+		let (assignments, slot_keys, handoffs) = assign_pool_slots(&virtual_resources, lifetimes, BufferCacheKey::merge);
+		pooled.extend(handoffs);
+
+		// Slots with the same key get different physical buffers of that key.
+		let mut key_counts = HashMap::<BufferCacheKey, usize>::new();
+		let mut slot_indices = Vec::with_capacity(slot_keys.len());
+		for key in &slot_keys {
+			let count = key_counts.entry(*key).or_default();
+			*count += 1;
+			graph.cache.alloc_buffers(graphics_device, key, *count);
+			slot_indices.push(graph.cache.buffer_cache.cache[key][*count - 1]);
 		}
 
 		let mut buffer_map = VirtualToPhysicalResourceMap::new();
-
-		// TODO(Brandon): Optimize this by mapping virtual to physical attachments based on existing framebuffers and descriptors to reduce allocations.
-		for (key, virtual_resources) in buffer_type_to_virtual {
-			for (i, virtual_resource) in virtual_resources.into_iter().enumerate() {
-				let index = graph.cache.buffer_cache.cache[&key][i];
-				buffer_map.map_physical(virtual_resource, index);
-			}
+		for (virtual_resource, slot) in assignments {
+			buffer_map.map_physical(virtual_resource, slot_indices[slot]);
 		}
 
+		let virtual_bytes = virtual_resources.iter().map(|(_, key)| key.size as u64).sum::<u64>();
+		let physical_bytes = slot_keys.iter().map(|key| key.size as u64).sum::<u64>();
+
+		let report = &mut graph.cache.pooling_report;
+		report.virtual_buffers = virtual_resources.len();
+		report.physical_buffers = slot_keys.len();
+		report.buffer_bytes_reused = virtual_bytes - physical_bytes;
+
 		buffer_map
 	}
 
@@ -851,9 +1371,16 @@ impl GraphPhysicalResourceMap {
 								},
 								GraphOwnedResourceDescriptorBinding::Attachment(attachment) => DescriptorHeapCacheKeyBinding::Attachment {
 									attachment: attachment_map.get_physical(attachment.id),
+									aspect: attachment.aspect,
+									subresource: attachment.subresource,
 								},
 								GraphOwnedResourceDescriptorBinding::MutableAttachment(attachment) => DescriptorHeapCacheKeyBinding::Attachment {
 									attachment: attachment_map.get_physical(attachment.id),
+									aspect: TextureAspect::All,
+									subresource: attachment.subresource,
+								},
+								GraphOwnedResourceDescriptorBinding::Sampler(sampler) => DescriptorHeapCacheKeyBinding::Sampler {
+									sampler: graphics_device.get_sampler(sampler).raw,
 								},
 							},
 						)
@@ -863,6 +1390,8 @@ impl GraphPhysicalResourceMap {
 
 			fn update_descriptor(
 				graph: &RenderGraph,
+				resource: usize,
+				graphics_device: &GraphicsDevice,
 				graphics_context: &mut GraphicsContext,
 				attachment_map: &VirtualToPhysicalResourceMap<usize>,
 				buffer_map: &VirtualToPhysicalResourceMap<usize>,
@@ -896,29 +1425,67 @@ impl GraphPhysicalResourceMap {
 					})
 					.collect::<Vec<_>>();
 
+				// Same as for render passes, subresources that the pass uses in more than one way are in a shared layout. Every subresource
+				// of a view ends up in the same layout, so the first one stands in for all of them.
+				let usages = graph.pass_resource_usages(graph.resource_to_owning_pass[&resource]).into_iter().collect::<HashMap<_, _>>();
+				let layout = |id: usize, subresource: Option<TextureSubresource>, layout: ImageLayout| {
+					graph
+						.attachment_subresources(id, subresource)
+						.first()
+						.and_then(|s| usages.get(s))
+						.and_then(|usage| usage.layout)
+						.unwrap_or(layout)
+				};
+
 				let images = bindings
 					.iter()
 					.filter(|(_, ty)| match ty {
+						GraphOwnedResourceDescriptorBinding::ImportedTexture(..) => true,
 						GraphOwnedResourceDescriptorBinding::Attachment(..) => true,
 						GraphOwnedResourceDescriptorBinding::MutableAttachment(..) => true,
 						_ => false,
 					})
 					.map(|(binding, image)| match image {
+						// Imported textures are expected to already be in a shader readable layout, which is where uploaded textures end up.
+						GraphOwnedResourceDescriptorBinding::ImportedTexture(texture) => match graph.imported_resources[texture.id] {
+							GraphImportedResource::Texture(texture) => (*binding, texture, TextureAspect::All, None, ImageLayout::ShaderReadOnlyOptimal),
+							_ => unreachable!("Invalid imported texture!"),
+						},
 						GraphOwnedResourceDescriptorBinding::Attachment(attachment) => {
 							let physical_attachment = &graph.cache.attachment_cache.attachments[attachment_map.get_physical(attachment.id)];
 
-							(*binding, physical_attachment, attachment.final_layout)
+							(
+								*binding,
+								physical_attachment,
+								attachment.aspect,
+								attachment.subresource,
+								layout(attachment.id, attachment.subresource, attachment.layout),
+							)
 						}
 						GraphOwnedResourceDescriptorBinding::MutableAttachment(attachment) => {
 							let physical_attachment = &graph.cache.attachment_cache.attachments[attachment_map.get_physical(attachment.id)];
 
-							(*binding, physical_attachment, attachment.layout)
+							(
+								*binding,
+								physical_attachment,
+								TextureAspect::All,
+								attachment.subresource,
+								layout(attachment.id, attachment.subresource, attachment.layout),
+							)
 						}
 						_ => unreachable!(),
 					})
 					.collect::<Vec<_>>();
 
-				graphics_context.update_descriptor(&buffers, &images, descriptor_layout, descriptor_heap, &descriptor);
+				let samplers = bindings
+					.iter()
+					.filter_map(|(binding, sampler)| match sampler {
+						GraphOwnedResourceDescriptorBinding::Sampler(sampler) => Some((*binding, graphics_device.get_sampler(sampler))),
+						_ => None,
+					})
+					.collect::<Vec<_>>();
+
+				graphics_context.update_descriptor(&buffers, &images, &samplers, descriptor_layout, descriptor_heap, &descriptor);
 			}
 			match resource {
 				GraphOwnedResource::GraphicsDescriptorSet { descriptor_layout, bindings, .. } => {
@@ -927,7 +1494,18 @@ impl GraphPhysicalResourceMap {
 
 					let descriptor = graph.cache.alloc_graphics_descriptor(graphics_device, descriptor_layout, &key);
 					let descriptor_heap = &graph.cache.get_graphics_descriptor_heap(descriptor_layout);
-					update_descriptor(graph, graphics_context, attachment_map, buffer_map, bindings, descriptor_heap, &descriptor, descriptor_layout);
+					update_descriptor(
+						graph,
+						id,
+						graphics_device,
+						graphics_context,
+						attachment_map,
+						buffer_map,
+						bindings,
+						descriptor_heap,
+						&descriptor,
+						descriptor_layout,
+					);
 
 					descriptor_map.map_physical(id, (descriptor, *descriptor_layout));
 				}
@@ -937,7 +1515,18 @@ impl GraphPhysicalResourceMap {
 
 					let descriptor = graph.cache.alloc_compute_descriptor(graphics_device, descriptor_layout, &key);
 					let descriptor_heap = &graph.cache.get_compute_descriptor_heap(descriptor_layout);
-					update_descriptor(graph, graphics_context, attachment_map, buffer_map, bindings, descriptor_heap, &descriptor, descriptor_layout);
+					update_descriptor(
+						graph,
+						id,
+						graphics_device,
+						graphics_context,
+						attachment_map,
+						buffer_map,
+						bindings,
+						descriptor_heap,
+						&descriptor,
+						descriptor_layout,
+					);
 
 					descriptor_map.map_physical(id, (descriptor, *descriptor_layout));
 				}
@@ -959,73 +1548,114 @@ impl GraphPhysicalResourceMap {
 		for (id, resource) in graph.owned_resources.iter().enumerate() {
 			match resource {
 				GraphOwnedResource::RenderPass {
-					color_attachments, depth_attachment, ..
+					color_attachments,
+					depth_attachment,
+					resolve_attachments,
+					..
 				} => {
+					// A pass that uses the subresource it renders to in more than one way, e.g. also samples it, gets a single layout for all of
+					// those uses from its barriers. The render pass has to keep the subresource in that layout.
+					let usages = graph.pass_resource_usages(graph.resource_to_owning_pass[&id]).into_iter().collect::<HashMap<_, _>>();
+					let layout = |handle: &MutableGraphAttachmentHandle| {
+						let target = handle.render_target();
+						let subresource = GraphSubresource {
+							id: handle.id,
+							mip: target.mip,
+							layer: target.layer.unwrap_or(0),
+						};
+						usages.get(&subresource).and_then(|usage| usage.layout).unwrap_or(handle.layout)
+					};
+
 					let color_attachment_descs = color_attachments
 						.iter()
 						.map(|handle| match &graph.owned_resources[handle.id] {
-							&GraphOwnedResource::Attachment { format, usage, load_op, store_op, .. } => AttachmentDescription {
+							&GraphOwnedResource::Attachment {
 								format,
 								usage,
 								load_op,
 								store_op,
-								// TODO(Brandon): In the future we might need to support other layout transitions in case we want to write to an attachment that was previously read.
-								initial_layout: ImageLayout::Undefined,
-								final_layout: handle.layout,
+								samples,
+								..
+							} => AttachmentDescription {
+								format,
+								samples,
+								usage,
+								load_op,
+								store_op,
+								// The barrier before the pass already transitioned the attachment, so only loaded contents need to be kept.
+								initial_layout: if load_op == LoadOp::Load { layout(handle) } else { ImageLayout::Undefined },
+								final_layout: layout(handle),
 							},
 							_ => unreachable!(),
 						})
 						.collect::<Vec<_>>();
 
-					let depth_attachment_desc = depth_attachment.map_or(None, |handle| match &graph.owned_resources[handle.id] {
-						&GraphOwnedResource::Attachment { format, usage, load_op, store_op, .. } => Some(AttachmentDescription {
+					let depth_attachment_desc = depth_attachment.as_ref().map(|handle| match &graph.owned_resources[handle.id] {
+						&GraphOwnedResource::Attachment {
+							format,
+							usage,
+							load_op,
+							store_op,
+							samples,
+							..
+						} => AttachmentDescription {
 							format,
+							samples,
 							usage,
 							load_op,
 							store_op,
-							initial_layout: ImageLayout::Undefined,
-							final_layout: handle.layout,
-						}),
+							initial_layout: if load_op == LoadOp::Load { layout(handle) } else { ImageLayout::Undefined },
+							final_layout: layout(handle),
+						},
 						_ => unreachable!(),
 					});
 
+					// The resolve overwrites every pixel of the attachment, so its previous contents never have to be loaded.
+					let resolve_attachment_descs = resolve_attachments
+						.iter()
+						.map(|handle| match &graph.owned_resources[handle.id] {
+							&GraphOwnedResource::Attachment { format, usage, store_op, samples, .. } => AttachmentDescription {
+								format,
+								samples,
+								usage,
+								load_op: LoadOp::DontCare,
+								store_op,
+								initial_layout: ImageLayout::Undefined,
+								final_layout: layout(handle),
+							},
+							_ => unreachable!(),
+						})
+						.collect::<Vec<_>>();
+
 					let render_pass_key = RenderPassCacheKey {
 						color_attachment_descs,
 						depth_attachment_desc,
+						resolve_attachment_descs,
 					};
 
 					let render_pass = graph.cache.alloc_render_pass(graphics_device, &render_pass_key);
 
-					let width = color_attachments
+					let extents = color_attachments
 						.iter()
-						.map(|a| match &graph.owned_resources[a.id] {
-							&GraphOwnedResource::Attachment { width, .. } => width,
+						.chain(depth_attachment.iter())
+						.map(|handle| match &graph.owned_resources[handle.id] {
+							&GraphOwnedResource::Attachment { width, height, .. } => {
+								let mip = handle.render_target().mip;
+								((width >> mip).max(1), (height >> mip).max(1))
+							}
 							_ => unreachable!(),
 						})
-						.chain(depth_attachment.into_iter().map(|d| match &graph.owned_resources[d.id] {
-							&GraphOwnedResource::Attachment { width, .. } => width,
-							_ => unreachable!(),
-						}))
-						.min()
-						.unwrap_or(0);
+						.collect::<Vec<_>>();
 
-					let height = color_attachments
-						.iter()
-						.map(|a| match &graph.owned_resources[a.id] {
-							&GraphOwnedResource::Attachment { height, .. } => height,
-							_ => unreachable!(),
-						})
-						.chain(depth_attachment.into_iter().map(|d| match &graph.owned_resources[d.id] {
-							&GraphOwnedResource::Attachment { height, .. } => height,
-							_ => unreachable!(),
-						}))
-						.min()
-						.unwrap_or(0);
+					let width = extents.iter().map(|e| e.0).min().unwrap_or(0);
+					let height = extents.iter().map(|e| e.1).min().unwrap_or(0);
 
-					let mut attachments = color_attachments.iter().map(|a| attachment_map.get_physical(a.id)).collect::<Vec<_>>();
-					if let Some(a) = depth_attachment {
-						attachments.push(attachment_map.get_physical(a.id));
-					}
+					let attachments = color_attachments
+						.iter()
+						.chain(depth_attachment.iter())
+						.chain(resolve_attachments.iter())
+						.map(|a| (attachment_map.get_physical(a.id), a.render_target()))
+						.collect::<Vec<_>>();
 
 					let framebuffer_key = FramebufferCacheKey {
 						width,
@@ -1071,6 +1701,9 @@ impl GraphPhysicalResourceMap {
 					push_constant_bytes,
 					vertex_input_info,
 					polygon_mode,
+					blend_states,
+					stencil,
+					samples,
 					..
 				} => {
 					// TODO(Brandon): Definitely don't do it like this, this is a hack to get the raw pointer
@@ -1106,6 +1739,9 @@ impl GraphPhysicalResourceMap {
 						push_constant_bytes: *push_constant_bytes,
 						vertex_input_info: *vertex_input_info,
 						polygon_mode: *polygon_mode,
+						blend_states: blend_states.clone(),
+						stencil: *stencil,
+						samples: *samples,
 					};
 
 					let pipeline = graph.cache.alloc_raster_pipeline(graphics_context, graphics_device, &key);
@@ -1156,14 +1792,26 @@ impl<'a> RenderGraph<'a> {
 			resource_to_owning_pass: Default::default(),
 			imported_resources: Default::default(),
 			cache,
+			record_errors: Default::default(),
 		}
 	}
 
 	pub fn add_pass<'b>(&'b mut self, name: &'static str) -> PassBuilder<'a, 'b> {
+		self.add_pass_on_queue(name, QueueType::GRAPHICS)
+	}
+
+	// Adds a pass which runs on the compute queue and overlaps with the graphics work around it. Only compute work can be recorded into it.
+	// Falls back to the graphics queue on devices without a separate compute queue family.
+	pub fn add_async_compute_pass<'b>(&'b mut self, name: &'static str) -> PassBuilder<'a, 'b> {
+		self.add_pass_on_queue(name, QueueType::COMPUTE)
+	}
+
+	fn add_pass_on_queue<'b>(&'b mut self, name: &'static str, queue: QueueType) -> PassBuilder<'a, 'b> {
 		let pass = PassHandle { id: self.passes.len() };
 		let recorded = Some(RecordedPass {
 			name,
 			pass,
+			queue,
 			cmds: Default::default(),
 			read_attachments: Default::default(),
 			write_attachments: Default::default(),
@@ -1174,115 +1822,1119 @@ impl<'a> RenderGraph<'a> {
 		PassBuilder { graph: self, pass, recorded }
 	}
 
-	fn resolve_pass_dependencies(&mut self, pass: PassHandle, pass_order: &mut Vec<PassHandle>) -> PassDependencyNode {
-		let recorded_pass = &self.passes[pass.id];
-		pass_order.push(pass);
-		let dependencies = recorded_pass
-			.read_attachments
-			.iter()
-			.map(|a| self.resource_to_owning_pass[&a.id])
-			.chain(recorded_pass.read_buffers.iter().map(|b| self.resource_to_owning_pass[&b.id]))
-			// .chain(recorded_pass.write_attachments.iter().map(|a| self.resource_to_owning_pass[&a.id]))
-			.collect::<HashSet<_>>()
-			.into_iter()
-			.map(|p| self.resolve_pass_dependencies(p, pass_order))
-			.collect::<Vec<_>>();
+	fn resource_name(&self, id: usize) -> &'static str {
+		match &self.owned_resources[id] {
+			GraphOwnedResource::RasterPipeline { name, .. }
+			| GraphOwnedResource::ComputePipeline { name, .. }
+			| GraphOwnedResource::RenderPass { name, .. }
+			| GraphOwnedResource::Attachment { name, .. }
+			| GraphOwnedResource::Buffer { name, .. }
+			| GraphOwnedResource::GraphicsDescriptorSet { name, .. }
+			| GraphOwnedResource::ComputeDescriptorSet { name, .. } => *name,
+			GraphOwnedResource::OutputRenderPass {} => "Output render pass",
+		}
+	}
 
-		PassDependencyNode { pass, dependencies }
+	// The output render pass always renders into a single color attachment.
+	fn render_pass_color_attachment_count(&self, render_pass: GraphRenderPassHandle) -> usize {
+		match &self.owned_resources[render_pass.id] {
+			GraphOwnedResource::RenderPass { color_attachments, .. } => color_attachments.len(),
+			_ => 1,
+		}
 	}
 
-	pub fn execute(mut self, graphics_context: &mut GraphicsContext, graphics_device: &mut GraphicsDevice) {
-		let output = self
-			.owned_resources
-			.iter()
-			.enumerate()
-			.filter(|(_, r)| matches!(r, GraphOwnedResource::OutputRenderPass {}))
-			.collect::<Vec<_>>();
+	fn attachment_samples(&self, id: usize) -> SampleCount {
+		match &self.owned_resources[id] {
+			&GraphOwnedResource::Attachment { samples, .. } => samples,
+			_ => unreachable!(),
+		}
+	}
 
-		if output.len() != 1 {
-			panic!("Multiple output render pass resources were found!");
+	// Sample count that pipelines used with `render_pass` have to be created with. The output render pass is never multisampled.
+	fn render_pass_samples(&self, render_pass: GraphRenderPassHandle) -> SampleCount {
+		match &self.owned_resources[render_pass.id] {
+			GraphOwnedResource::RenderPass {
+				color_attachments, depth_attachment, ..
+			} => color_attachments
+				.iter()
+				.chain(depth_attachment.iter())
+				.next()
+				.map_or(SampleCount::X1, |a| self.attachment_samples(a.id)),
+			_ => SampleCount::X1,
 		}
+	}
 
-		let (id, _) = output[0];
+	// Maps every attachment and buffer to the passes that write to it, render pass attachments count as writes of the pass that adds the render pass.
+	fn resource_writers(&self) -> HashMap<usize, Vec<PassHandle>> {
+		let mut writers = HashMap::<usize, Vec<PassHandle>>::new();
 
-		let root_pass = self.resource_to_owning_pass[&id];
-		let mut passes = vec![root_pass];
-		// let root =
-		self.resolve_pass_dependencies(root_pass, &mut passes);
-		// dbg!(root);
+		for recorded_pass in self.passes.iter() {
+			let ids = recorded_pass.write_attachments.iter().map(|a| a.id).chain(recorded_pass.write_buffers.iter().map(|b| b.id));
+			for id in ids {
+				writers.entry(id).or_default().push(recorded_pass.pass);
+			}
+		}
 
-		let mut found = HashSet::<PassHandle>::new();
-		passes.reverse();
-		passes.retain(|p| found.insert(*p));
+		for passes in writers.values_mut() {
+			passes.sort_by_key(|p| p.id);
+			passes.dedup();
+		}
 
-		let resource_map = GraphPhysicalResourceMap::new(&mut self, graphics_device, graphics_context);
-		for pass in passes {
-			for &attachment in self.passes[pass.id].read_attachments.iter() {
-				let physical_attachment = resource_map.get_attachment(&self, attachment);
-				// dbg!("Adding read pipeline barrier for pass {}, {:?}", self.passes[pass.id].name, attachment);
-
-				graphics_context.pipeline_barrier(
-					attachment.src_stage,
-					attachment.dst_stage,
-					ash::vk::DependencyFlags::empty(),
-					&[],
-					&[],
-					&[ash::vk::ImageMemoryBarrier::builder()
-						.old_layout(attachment.initial_layout.into())
-						.new_layout(attachment.final_layout.into())
-						.image(physical_attachment.image)
-						.subresource_range(physical_attachment.subresource_range)
-						.src_access_mask(attachment.src_access)
-						.dst_access_mask(attachment.dst_access)
-						.src_queue_family_index(ash::vk::QUEUE_FAMILY_IGNORED)
-						.dst_queue_family_index(ash::vk::QUEUE_FAMILY_IGNORED)
-						.build()],
-				);
-			}
+		writers
+	}
 
-			for &attachment in self.passes[pass.id].write_attachments.iter() {
-				let physical_attachment = resource_map.get_mutable_attachment(&self, attachment);
-				// dbg!("Adding write pipeline barrier for pass {}, {:?}", self.passes[pass.id].name, attachment);
-
-				graphics_context.pipeline_barrier(
-					ash::vk::PipelineStageFlags::TOP_OF_PIPE,
-					attachment.stage,
-					ash::vk::DependencyFlags::empty(),
-					&[],
-					&[],
-					&[ash::vk::ImageMemoryBarrier::builder()
-						.old_layout(ImageLayout::Undefined.into())
-						.new_layout(attachment.layout.into())
-						.image(physical_attachment.image)
-						.subresource_range(physical_attachment.subresource_range)
-						.src_access_mask(ash::vk::AccessFlags::empty())
-						.dst_access_mask(attachment.access)
-						.src_queue_family_index(ash::vk::QUEUE_FAMILY_IGNORED)
-						.dst_queue_family_index(ash::vk::QUEUE_FAMILY_IGNORED)
-						.build()],
-				);
-			}
+	// A pass depends on the owner of every resource it reads or writes, and on every pass recorded before it that writes to one of those resources.
+	// Writes count as well since a pass that writes to a resource without discarding it (read-modify-write) needs the previous contents.
+	fn pass_dependencies(&self, pass: PassHandle, writers: &HashMap<usize, Vec<PassHandle>>) -> Vec<PassHandle> {
+		let recorded_pass = &self.passes[pass.id];
+		let mut dependencies = recorded_pass
+			.read_attachments
+			.iter()
+			.map(|a| a.id)
+			.chain(recorded_pass.write_attachments.iter().map(|a| a.id))
+			.chain(recorded_pass.read_buffers.iter().map(|b| b.id))
+			.chain(recorded_pass.write_buffers.iter().map(|b| b.id))
+			.flat_map(|id| {
+				let resource_writers = writers.get(&id).into_iter().flatten().copied().filter(|w| w.id < pass.id);
+				std::iter::once(self.resource_to_owning_pass[&id]).chain(resource_writers)
+			})
+			.filter(|p| *p != pass)
+			.collect::<Vec<_>>();
 
-			for &buffer in self.passes[pass.id].read_buffers.iter() {
-				let physical_buffer = resource_map.get_buffer(&self, buffer);
-
-				graphics_context.pipeline_barrier(
-					buffer.src_stage,
-					buffer.dst_stage,
-					ash::vk::DependencyFlags::empty(),
-					&[],
-					&[ash::vk::BufferMemoryBarrier::builder()
-						.buffer(physical_buffer.raw)
-						.size(physical_buffer.size as u64)
-						.offset(0)
-						.src_access_mask(buffer.src_access)
-						.dst_access_mask(buffer.dst_access)
-						.src_queue_family_index(ash::vk::QUEUE_FAMILY_IGNORED)
-						.dst_queue_family_index(ash::vk::QUEUE_FAMILY_IGNORED)
-						.build()],
-					&[],
-				)
+		dependencies.sort_by_key(|p| p.id);
+		dependencies.dedup();
+		dependencies
+	}
+
+	// Post-order DFS from `pass`, so every pass ends up in `pass_order` after all of its dependencies.
+	fn resolve_pass_dependencies(
+		&self,
+		pass: PassHandle,
+		writers: &HashMap<usize, Vec<PassHandle>>,
+		states: &mut HashMap<PassHandle, PassVisitState>,
+		stack: &mut Vec<PassHandle>,
+		pass_order: &mut Vec<PassHandle>,
+	) -> Result<(), RenderGraphError> {
+		match states.get(&pass) {
+			Some(PassVisitState::Visited) => return Ok(()),
+			Some(PassVisitState::Visiting) => {
+				let start = stack.iter().position(|p| *p == pass).unwrap();
+				let cycle = stack[start..].iter().chain(std::iter::once(&pass)).map(|p| self.passes[p.id].name).collect();
+
+				return Err(RenderGraphError::DependencyCycle(cycle));
 			}
+			None => {}
+		}
+
+		states.insert(pass, PassVisitState::Visiting);
+		stack.push(pass);
+
+		for dependency in self.pass_dependencies(pass, writers) {
+			self.resolve_pass_dependencies(dependency, writers, states, stack, pass_order)?;
+		}
+
+		stack.pop();
+		states.insert(pass, PassVisitState::Visited);
+		pass_order.push(pass);
+
+		Ok(())
+	}
+
+	// Finds the output pass and sorts every pass it transitively depends on. Everything else gets culled.
+	fn sort_passes(&self, writers: &HashMap<usize, Vec<PassHandle>>) -> Result<Vec<PassHandle>, Vec<RenderGraphError>> {
+		let outputs = self
+			.owned_resources
+			.iter()
+			.enumerate()
+			.filter(|(_, r)| matches!(r, GraphOwnedResource::OutputRenderPass {}))
+			.map(|(id, _)| self.resource_to_owning_pass[&id])
+			.collect::<Vec<_>>();
+
+		let root_pass = match outputs.as_slice() {
+			[] => return Err(vec![RenderGraphError::NoOutput]),
+			&[root_pass] => root_pass,
+			_ => return Err(vec![RenderGraphError::MultipleOutputs(outputs.iter().map(|p| self.passes[p.id].name).collect())]),
+		};
+
+		let mut pass_order = Vec::new();
+		self.resolve_pass_dependencies(root_pass, writers, &mut Default::default(), &mut Vec::new(), &mut pass_order)
+			.map_err(|err| vec![err])?;
+
+		// Dependencies always point to passes recorded earlier, so the recording order of the surviving passes respects them too. It also keeps
+		// write-after-read hazards in the order they were recorded, which the dependency edges alone don't.
+		pass_order.sort_by_key(|p| p.id);
+
+		Ok(pass_order)
+	}
+
+	// Checks everything that doesn't depend on the device, `validate_attachment_samples` covers the rest.
+	fn validate(&self, passes: &[PassHandle], writers: &HashMap<usize, Vec<PassHandle>>) -> (Vec<RenderGraphWarning>, Vec<RenderGraphError>) {
+		let mut warnings = Vec::new();
+		let mut errors = self.record_errors.clone();
+
+		let scheduled = passes.iter().copied().collect::<HashSet<_>>();
+
+		for recorded_pass in self.passes.iter().filter(|p| !scheduled.contains(&p.pass)) {
+			warnings.push(RenderGraphWarning::CulledPass(recorded_pass.name));
+		}
+
+		for recorded_pass in self.passes.iter().filter(|p| scheduled.contains(&p.pass) && p.queue == QueueType::COMPUTE) {
+			let graphics_work = recorded_pass.cmds.iter().any(|cmd| {
+				matches!(
+					cmd,
+					PassCmd::BeginRenderPass { .. } | PassCmd::BindRasterPipeline { .. } | PassCmd::BindGraphicsDescriptor { .. } | PassCmd::DrawMesh { .. } | PassCmd::Draw { .. }
+				)
+			});
+
+			if graphics_work {
+				errors.push(RenderGraphError::GraphicsWorkOnAsyncComputePass(recorded_pass.name));
+			}
+		}
+
+		for (id, resource) in self.owned_resources.iter().enumerate() {
+			match resource {
+				// Descriptor sets of culled passes are never bound, so they can't stop the rest of the graph from executing.
+				GraphOwnedResource::GraphicsDescriptorSet { name, descriptor_layout, bindings } | GraphOwnedResource::ComputeDescriptorSet { name, descriptor_layout, bindings } => {
+					if scheduled.contains(&self.resource_to_owning_pass[&id]) {
+						Self::validate_descriptor_bindings(*name, *descriptor_layout, bindings, &mut errors);
+					}
+				}
+				_ => {}
+			}
+		}
+
+		let mut read = HashSet::new();
+		for &pass in passes {
+			let recorded_pass = &self.passes[pass.id];
+			let ids = recorded_pass.read_attachments.iter().map(|a| a.id).chain(recorded_pass.read_buffers.iter().map(|b| b.id));
+			for id in ids {
+				read.insert(id);
+
+				if !writers.contains_key(&id) {
+					errors.push(RenderGraphError::ReadOfUnwrittenResource {
+						pass: recorded_pass.name,
+						resource: self.resource_name(id),
+					});
+				}
+			}
+		}
+
+		let mut written = writers.iter().collect::<Vec<_>>();
+		written.sort_by_key(|(id, _)| **id);
+
+		for (&id, resource_writers) in written {
+			if read.contains(&id) {
+				continue;
+			}
+
+			if let Some(writer) = resource_writers.iter().rev().find(|w| scheduled.contains(*w)) {
+				let pass = self.passes[writer.id].name;
+				match &self.owned_resources[id] {
+					&GraphOwnedResource::Attachment { name, .. } => warnings.push(RenderGraphWarning::UnreadAttachment { pass, attachment: name }),
+					&GraphOwnedResource::Buffer { name, .. } => warnings.push(RenderGraphWarning::UnreadBuffer { pass, buffer: name }),
+					_ => unreachable!("Only attachments and buffers can be written!"),
+				}
+			}
+		}
+
+		(warnings, errors)
+	}
+
+	// Every attachment gets created, including the ones of culled passes.
+	fn validate_attachment_samples(&self, graphics_device: &GraphicsDevice) -> Vec<RenderGraphError> {
+		self.owned_resources
+			.iter()
+			.filter_map(|resource| match resource {
+				&GraphOwnedResource::Attachment { name, format, samples, .. } if !graphics_device.supports_attachment_samples(format, samples) => {
+					Some(RenderGraphError::UnsupportedAttachmentSamples { attachment: name, format, samples })
+				}
+				_ => None,
+			})
+			.collect()
+	}
+
+	fn validate_descriptor_bindings(
+		descriptor: &'static str,
+		descriptor_layout: &'static DescriptorSetInfo,
+		bindings: &[(u32, GraphOwnedResourceDescriptorBinding)],
+		errors: &mut Vec<RenderGraphError>,
+	) {
+		for (binding, resource) in bindings.iter() {
+			let expected = match descriptor_layout.bindings.get(binding) {
+				Some(expected) => *expected,
+				None => {
+					errors.push(RenderGraphError::UnknownDescriptorBinding { descriptor, binding: *binding });
+					continue;
+				}
+			};
+
+			let (found, compatible) = match resource {
+				GraphOwnedResourceDescriptorBinding::ImportedBuffer(..) => (
+					"imported buffer",
+					matches!(
+						expected,
+						DescriptorBindingType::Buffer
+							| DescriptorBindingType::RWBuffer
+							| DescriptorBindingType::CBuffer
+							| DescriptorBindingType::StructuredBuffer
+							| DescriptorBindingType::RWStructuredBuffer
+					),
+				),
+				GraphOwnedResourceDescriptorBinding::Buffer(..) => (
+					"read only buffer",
+					matches!(expected, DescriptorBindingType::Buffer | DescriptorBindingType::CBuffer | DescriptorBindingType::StructuredBuffer),
+				),
+				GraphOwnedResourceDescriptorBinding::MutableBuffer(..) => ("mutable buffer", matches!(expected, DescriptorBindingType::RWBuffer | DescriptorBindingType::RWStructuredBuffer)),
+				GraphOwnedResourceDescriptorBinding::ImportedTexture(..) => ("imported texture", matches!(expected, DescriptorBindingType::Texture2D)),
+				GraphOwnedResourceDescriptorBinding::Attachment(..) => ("read only attachment", matches!(expected, DescriptorBindingType::Texture2D)),
+				GraphOwnedResourceDescriptorBinding::MutableAttachment(..) => ("mutable attachment", matches!(expected, DescriptorBindingType::RWTexture2D)),
+				GraphOwnedResourceDescriptorBinding::Sampler(..) => ("sampler", matches!(expected, DescriptorBindingType::SamplerState)),
+			};
+
+			if !compatible {
+				errors.push(RenderGraphError::DescriptorBindingMismatch {
+					descriptor,
+					binding: *binding,
+					expected,
+					found,
+				});
+			}
+		}
+
+		let mut missing = descriptor_layout
+			.bindings
+			.entries()
+			.filter(|(binding, _)| !bindings.iter().any(|(b, _)| b == *binding))
+			.map(|(binding, expected)| (*binding, *expected))
+			.collect::<Vec<_>>();
+		missing.sort_by_key(|(binding, _)| *binding);
+
+		for (binding, expected) in missing {
+			errors.push(RenderGraphError::MissingDescriptorBinding { descriptor, binding, expected });
+		}
+	}
+
+	// Computes the [first use, last use] interval of every owned attachment and buffer as indices into the sorted pass list.
+	// Resources which are never touched by a scheduled pass span the entire frame so that they never share a pooled resource with anything.
+	fn compute_resource_lifetimes(&self, passes: &[PassHandle]) -> HashMap<usize, (usize, usize)> {
+		let pass_order = passes.iter().enumerate().map(|(i, p)| (*p, i)).collect::<HashMap<_, _>>();
+		let mut lifetimes = HashMap::<usize, (usize, usize)>::new();
+
+		let mut add_use = |resource: usize, pass: PassHandle| {
+			if let Some(&order) = pass_order.get(&pass) {
+				let lifetime = lifetimes.entry(resource).or_insert((order, order));
+				lifetime.0 = lifetime.0.min(order);
+				lifetime.1 = lifetime.1.max(order);
+			}
+		};
+
+		for (id, resource) in self.owned_resources.iter().enumerate() {
+			let owner = self.resource_to_owning_pass[&id];
+			match resource {
+				GraphOwnedResource::Attachment { .. } | GraphOwnedResource::Buffer { .. } => add_use(id, owner),
+				GraphOwnedResource::RenderPass {
+					color_attachments,
+					depth_attachment,
+					resolve_attachments,
+					..
+				} => {
+					for attachment in color_attachments.iter().chain(depth_attachment.iter()).chain(resolve_attachments.iter()) {
+						add_use(attachment.id, owner);
+					}
+				}
+				GraphOwnedResource::GraphicsDescriptorSet { bindings, .. } | GraphOwnedResource::ComputeDescriptorSet { bindings, .. } => {
+					for (_, binding) in bindings.iter() {
+						match binding {
+							GraphOwnedResourceDescriptorBinding::Buffer(buffer) => add_use(buffer.id, owner),
+							GraphOwnedResourceDescriptorBinding::MutableBuffer(buffer) => add_use(buffer.id, owner),
+							GraphOwnedResourceDescriptorBinding::Attachment(attachment) => add_use(attachment.id, owner),
+							GraphOwnedResourceDescriptorBinding::MutableAttachment(attachment) => add_use(attachment.id, owner),
+							_ => {}
+						}
+					}
+				}
+				_ => {}
+			}
+		}
+
+		for recorded_pass in self.passes.iter() {
+			let pass = recorded_pass.pass;
+			recorded_pass.read_attachments.iter().for_each(|a| add_use(a.id, pass));
+			recorded_pass.write_attachments.iter().for_each(|a| add_use(a.id, pass));
+			recorded_pass.read_buffers.iter().for_each(|b| add_use(b.id, pass));
+			recorded_pass.write_buffers.iter().for_each(|b| add_use(b.id, pass));
+		}
+
+		// The barriers of reused resources only cover a single queue, so nothing used by async compute is reused.
+		for &pass in passes.iter().filter(|p| self.passes[p.id].queue == QueueType::COMPUTE) {
+			for (subresource, _) in self.pass_resource_usages(pass) {
+				lifetimes.insert(subresource.id, (0, passes.len()));
+			}
+		}
+
+		for (id, resource) in self.owned_resources.iter().enumerate() {
+			match resource {
+				// Attachments that load their previous contents expect to get the same memory back every frame.
+				GraphOwnedResource::Attachment { load_op: LoadOp::Load, .. } => {
+					lifetimes.insert(id, (0, passes.len()));
+				}
+				GraphOwnedResource::Attachment { .. } | GraphOwnedResource::Buffer { .. } => {
+					lifetimes.entry(id).or_insert((0, passes.len()));
+				}
+				_ => {}
+			}
+		}
+
+		lifetimes
+	}
+
+	// The subresources of attachment `id` that a view of `subresource` covers, sorted by mip and layer.
+	fn attachment_subresources(&self, id: usize, subresource: Option<TextureSubresource>) -> Vec<GraphSubresource> {
+		let (mip_levels, array_layers) = match &self.owned_resources[id] {
+			&GraphOwnedResource::Attachment { mip_levels, array_layers, .. } => (mip_levels, array_layers),
+			_ => unreachable!("Invalid attachment!"),
+		};
+
+		let mips = match subresource {
+			Some(subresource) => subresource.mip..subresource.mip + 1,
+			None => 0..mip_levels,
+		};
+		let layers = match subresource.and_then(|subresource| subresource.layer) {
+			Some(layer) => layer..layer + 1,
+			None => 0..array_layers,
+		};
+
+		mips.flat_map(|mip| layers.clone().map(move |layer| GraphSubresource { id, mip, layer })).collect()
+	}
+
+	// Every subresource of the attachments and buffers that `pass` uses through its render passes and descriptor sets, sorted by subresource.
+	// A subresource which is used more than once gets a single usage that covers all of them.
+	fn pass_resource_usages(&self, pass: PassHandle) -> Vec<(GraphSubresource, GraphResourceUsage)> {
+		let mut usages = HashMap::<GraphSubresource, GraphResourceUsage>::new();
+		// The subresources of every single use, which is bound as one view and so has to find all of them in the same layout.
+		let mut uses = Vec::<Vec<GraphSubresource>>::new();
+		let mut add_usage = |subresources: Vec<GraphSubresource>, usage: GraphResourceUsage| {
+			for &subresource in subresources.iter() {
+				usages
+					.entry(subresource)
+					.and_modify(|existing| {
+						// Two different layouts in the same pass can only be satisfied by the general layout.
+						if existing.layout != usage.layout {
+							existing.layout = Some(ImageLayout::General);
+						}
+						existing.stage |= usage.stage;
+						existing.access |= usage.access;
+						existing.write |= usage.write;
+						existing.discard &= usage.discard;
+					})
+					.or_insert(usage);
+			}
+			uses.push(subresources);
+		};
+		let render_target = |handle: &MutableGraphAttachmentHandle| {
+			let target = handle.render_target();
+			vec![GraphSubresource {
+				id: handle.id,
+				mip: target.mip,
+				layer: target.layer.unwrap_or(0),
+			}]
+		};
+		let whole_buffer = |id: usize| vec![GraphSubresource { id, mip: 0, layer: 0 }];
+
+		for (id, resource) in self.owned_resources.iter().enumerate() {
+			if self.resource_to_owning_pass[&id] != pass {
+				continue;
+			}
+
+			let (shader_stage, bindings) = match resource {
+				GraphOwnedResource::RenderPass {
+					color_attachments,
+					depth_attachment,
+					resolve_attachments,
+					..
+				} => {
+					for attachment in color_attachments.iter() {
+						let load = matches!(self.owned_resources[attachment.id], GraphOwnedResource::Attachment { load_op: LoadOp::Load, .. });
+						add_usage(
+							render_target(attachment),
+							GraphResourceUsage {
+								layout: Some(attachment.layout),
+								stage: ash::vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+								access: if load {
+									ash::vk::AccessFlags::COLOR_ATTACHMENT_READ | ash::vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+								} else {
+									ash::vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+								},
+								write: true,
+								discard: !load,
+							},
+						);
+					}
+
+					if let Some(attachment) = depth_attachment {
+						let load = matches!(self.owned_resources[attachment.id], GraphOwnedResource::Attachment { load_op: LoadOp::Load, .. });
+						add_usage(
+							render_target(attachment),
+							GraphResourceUsage {
+								layout: Some(attachment.layout),
+								stage: ash::vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS | ash::vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+								// Depth testing reads the attachment even when it was cleared.
+								access: ash::vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ | ash::vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+								write: true,
+								discard: !load,
+							},
+						);
+					}
+
+					// Resolves are done as part of the color attachment output stage and overwrite the whole subresource.
+					for attachment in resolve_attachments.iter() {
+						add_usage(
+							render_target(attachment),
+							GraphResourceUsage {
+								layout: Some(attachment.layout),
+								stage: ash::vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+								access: ash::vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+								write: true,
+								discard: true,
+							},
+						);
+					}
+					continue;
+				}
+				GraphOwnedResource::GraphicsDescriptorSet { bindings, .. } => (ash::vk::PipelineStageFlags::VERTEX_SHADER | ash::vk::PipelineStageFlags::FRAGMENT_SHADER, bindings),
+				GraphOwnedResource::ComputeDescriptorSet { bindings, .. } => (ash::vk::PipelineStageFlags::COMPUTE_SHADER, bindings),
+				_ => continue,
+			};
+
+			for (_, binding) in bindings.iter() {
+				let (subresources, usage) = match binding {
+					GraphOwnedResourceDescriptorBinding::Attachment(attachment) => (
+						self.attachment_subresources(attachment.id, attachment.subresource),
+						GraphResourceUsage {
+							layout: Some(attachment.layout),
+							stage: shader_stage,
+							access: ash::vk::AccessFlags::SHADER_READ,
+							write: false,
+							discard: false,
+						},
+					),
+					GraphOwnedResourceDescriptorBinding::MutableAttachment(attachment) => (
+						self.attachment_subresources(attachment.id, attachment.subresource),
+						GraphResourceUsage {
+							layout: Some(attachment.layout),
+							stage: shader_stage,
+							access: ash::vk::AccessFlags::SHADER_READ | ash::vk::AccessFlags::SHADER_WRITE,
+							write: true,
+							discard: false,
+						},
+					),
+					GraphOwnedResourceDescriptorBinding::Buffer(buffer) => (
+						whole_buffer(buffer.id),
+						GraphResourceUsage {
+							layout: None,
+							stage: shader_stage,
+							access: ash::vk::AccessFlags::SHADER_READ | ash::vk::AccessFlags::UNIFORM_READ,
+							write: false,
+							discard: false,
+						},
+					),
+					GraphOwnedResourceDescriptorBinding::MutableBuffer(buffer) => (
+						whole_buffer(buffer.id),
+						GraphResourceUsage {
+							layout: None,
+							stage: shader_stage,
+							access: ash::vk::AccessFlags::SHADER_READ | ash::vk::AccessFlags::SHADER_WRITE,
+							write: true,
+							discard: false,
+						},
+					),
+					_ => continue,
+				};
+
+				add_usage(subresources, usage);
+			}
+		}
+
+		// A use that only partially overlaps a use in another layout has the rest of its subresources follow them into the general layout,
+		// which can in turn pull in more uses.
+		let mut changed = true;
+		while changed {
+			changed = false;
+			for subresources in uses.iter() {
+				let layout = usages[&subresources[0]].layout;
+				if subresources.iter().all(|subresource| usages[subresource].layout == layout) {
+					continue;
+				}
+
+				for subresource in subresources.iter() {
+					let usage = usages.get_mut(subresource).unwrap();
+					changed |= usage.layout != Some(ImageLayout::General);
+					usage.layout = Some(ImageLayout::General);
+				}
+			}
+		}
+
+		let mut usages = usages.into_iter().collect::<Vec<_>>();
+		usages.sort_by_key(|(subresource, _)| *subresource);
+		usages
+	}
+
+	// The queue that `pass` executes on. Async compute passes run on the graphics queue if the device can't run them in parallel.
+	fn pass_queue(&self, pass: PassHandle, async_compute: bool) -> QueueType {
+		if async_compute {
+			self.passes[pass.id].queue
+		} else {
+			QueueType::GRAPHICS
+		}
+	}
+
+	// Walks the sorted pass list while tracking the layout, stage, access and queue of every layer of every mip of every resource, and
+	// returns what needs to be synchronized around each pass along with the state every subresource is left in at the end of the frame.
+	// Every subresource starts the frame in its `initial_states` entry, which is what the previous frame left it in, or in an undefined
+	// layout if it has none.
+	//
+	// A barrier is needed whenever the layout changes or when either side writes (read-after-write, write-after-write, write-after-read).
+	// Reads which follow other reads in the same layout are merged into the tracked state instead, so that the next write waits on all of them.
+	// Any use on a different queue than the previous one waits on that pass with a semaphore, and transfers ownership of the resource
+	// unless its contents are discarded anyway.
+	//
+	// The first use of a `pooled` resource overwrites whatever the previous owner of its physical resource left behind, so it waits on
+	// every earlier write on the queue and starts from an undefined layout.
+	fn plan_barriers(
+		&self,
+		passes: &[PassHandle],
+		async_compute: bool,
+		pooled: &HashSet<usize>,
+		initial_states: &HashMap<GraphSubresource, GraphResourceState>,
+	) -> (Vec<GraphPassSync>, HashMap<GraphSubresource, GraphResourceState>) {
+		let mut states = HashMap::<GraphSubresource, GraphResourceState>::new();
+		let mut plan = vec![GraphPassSync::default(); passes.len()];
+
+		for (order, &pass) in passes.iter().enumerate() {
+			let queue = self.pass_queue(pass, async_compute);
+
+			for (subresource, usage) in self.pass_resource_usages(pass) {
+				let id = subresource.id;
+				let range = GraphSubresourceRange {
+					base_mip: subresource.mip,
+					mip_count: 1,
+					base_layer: subresource.layer,
+					layer_count: 1,
+				};
+
+				if !states.contains_key(&subresource) && pooled.contains(&id) {
+					plan[order].barriers.push(GraphBarrier {
+						resource: id,
+						kind: match usage.layout {
+							Some(new_layout) => GraphBarrierKind::Image {
+								old_layout: ImageLayout::Undefined,
+								new_layout,
+								range,
+							},
+							None => GraphBarrierKind::Buffer,
+						},
+						src_stage: ash::vk::PipelineStageFlags::ALL_COMMANDS,
+						dst_stage: usage.stage,
+						src_access: ash::vk::AccessFlags::MEMORY_WRITE,
+						dst_access: usage.access,
+						queue_transfer: None,
+					});
+
+					states.insert(
+						subresource,
+						GraphResourceState {
+							layout: usage.layout.unwrap_or(ImageLayout::Undefined),
+							stage: usage.stage,
+							access: usage.access,
+							written: usage.write,
+							queue,
+							last_pass: order,
+						},
+					);
+					continue;
+				}
+
+				let state = states.entry(subresource).or_insert_with(|| match initial_states.get(&subresource) {
+					// NOTE: The previous frame is already submitted, so this frame's passes on the same queue are ordered after it and a barrier is enough.
+					// Nothing released the resource to another queue at the end of that frame though, so its contents can't be carried over to it.
+					Some(initial) if initial.queue == queue => GraphResourceState { last_pass: order, ..*initial },
+					_ => GraphResourceState {
+						layout: ImageLayout::Undefined,
+						stage: ash::vk::PipelineStageFlags::empty(),
+						access: ash::vk::AccessFlags::empty(),
+						written: false,
+						queue,
+						last_pass: order,
+					},
+				});
+
+				let layout = usage.layout.unwrap_or(ImageLayout::Undefined);
+				let layout_changes = usage.layout.is_some() && layout != state.layout;
+				let queue_changes = !state.stage.is_empty() && state.queue != queue;
+				let hazard = !state.stage.is_empty() && (state.written || usage.write || queue_changes);
+
+				if !layout_changes && !hazard {
+					state.stage |= usage.stage;
+					state.access |= usage.access;
+					state.written |= usage.write;
+					state.last_pass = order;
+					continue;
+				}
+
+				let kind = match usage.layout {
+					Some(new_layout) => GraphBarrierKind::Image {
+						old_layout: if usage.discard { ImageLayout::Undefined } else { state.layout },
+						new_layout,
+						range,
+					},
+					None => GraphBarrierKind::Buffer,
+				};
+
+				// Only writes need to be made available, reads just need the execution dependency.
+				let src_access = if state.written { state.access } else { ash::vk::AccessFlags::empty() };
+
+				if queue_changes {
+					plan[order].waits.push((state.last_pass, usage.stage));
+
+					// The semaphore already covers the execution dependency, the barriers on this queue start at the stages that wait on it.
+					let queue_transfer = if usage.discard { None } else { Some((state.queue, queue)) };
+					if queue_transfer.is_some() {
+						plan[state.last_pass].releases.push(GraphBarrier {
+							resource: id,
+							kind,
+							src_stage: state.stage,
+							dst_stage: ash::vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+							src_access,
+							dst_access: ash::vk::AccessFlags::empty(),
+							queue_transfer,
+						});
+					}
+
+					plan[order].barriers.push(GraphBarrier {
+						resource: id,
+						kind,
+						src_stage: usage.stage,
+						dst_stage: usage.stage,
+						src_access: ash::vk::AccessFlags::empty(),
+						dst_access: usage.access,
+						queue_transfer,
+					});
+				} else {
+					plan[order].barriers.push(GraphBarrier {
+						resource: id,
+						kind,
+						src_stage: if state.stage.is_empty() { ash::vk::PipelineStageFlags::TOP_OF_PIPE } else { state.stage },
+						dst_stage: usage.stage,
+						src_access,
+						dst_access: usage.access,
+						queue_transfer: None,
+					});
+				}
+
+				*state = GraphResourceState {
+					layout,
+					stage: usage.stage,
+					access: usage.access,
+					written: usage.write,
+					queue,
+					last_pass: order,
+				};
+			}
+		}
+
+		for sync in plan.iter_mut() {
+			sync.barriers = merge_barriers(std::mem::take(&mut sync.barriers));
+			sync.releases = merge_barriers(std::mem::take(&mut sync.releases));
+		}
+		(plan, states)
+	}
+
+	// Records `barriers` as a single pipeline barrier.
+	fn record_barriers(&self, resource_map: &GraphPhysicalResourceMap, graphics_context: &GraphicsContext, graphics_device: &GraphicsDevice, barriers: &[GraphBarrier]) {
+		if barriers.is_empty() {
+			return;
+		}
+
+		let src_stage = barriers.iter().fold(ash::vk::PipelineStageFlags::empty(), |stage, b| stage | b.src_stage);
+		let dst_stage = barriers.iter().fold(ash::vk::PipelineStageFlags::empty(), |stage, b| stage | b.dst_stage);
+
+		let queue_families = |barrier: &GraphBarrier| match barrier.queue_transfer {
+			Some((src, dst)) => (graphics_device.get_queue_family_index(src), graphics_device.get_queue_family_index(dst)),
+			None => (ash::vk::QUEUE_FAMILY_IGNORED, ash::vk::QUEUE_FAMILY_IGNORED),
+		};
+
+		let image_barriers = barriers
+			.iter()
+			.filter_map(|barrier| match barrier.kind {
+				GraphBarrierKind::Image { old_layout, new_layout, range } => {
+					let physical_attachment = resource_map.get_attachment(self, barrier.resource);
+					let (src_queue_family, dst_queue_family) = queue_families(barrier);
+
+					Some(
+						ash::vk::ImageMemoryBarrier::builder()
+							.old_layout(old_layout.into())
+							.new_layout(new_layout.into())
+							.image(physical_attachment.image)
+							.subresource_range(ash::vk::ImageSubresourceRange {
+								aspect_mask: physical_attachment.subresource_range.aspect_mask,
+								base_mip_level: range.base_mip,
+								level_count: range.mip_count,
+								base_array_layer: range.base_layer,
+								layer_count: range.layer_count,
+							})
+							.src_access_mask(barrier.src_access)
+							.dst_access_mask(barrier.dst_access)
+							.src_queue_family_index(src_queue_family)
+							.dst_queue_family_index(dst_queue_family)
+							.build(),
+					)
+				}
+				GraphBarrierKind::Buffer => None,
+			})
+			.collect::<Vec<_>>();
+
+		let buffer_barriers = barriers
+			.iter()
+			.filter_map(|barrier| match barrier.kind {
+				GraphBarrierKind::Buffer => {
+					let physical_buffer = resource_map.get_buffer(self, barrier.resource);
+					let (src_queue_family, dst_queue_family) = queue_families(barrier);
+
+					Some(
+						ash::vk::BufferMemoryBarrier::builder()
+							.buffer(physical_buffer.raw)
+							.size(physical_buffer.size as u64)
+							.offset(0)
+							.src_access_mask(barrier.src_access)
+							.dst_access_mask(barrier.dst_access)
+							.src_queue_family_index(src_queue_family)
+							.dst_queue_family_index(dst_queue_family)
+							.build(),
+					)
+				}
+				GraphBarrierKind::Image { .. } => None,
+			})
+			.collect::<Vec<_>>();
+
+		graphics_context.pipeline_barrier(src_stage, dst_stage, ash::vk::DependencyFlags::empty(), &[], &buffer_barriers, &image_barriers);
+	}
+
+	fn export_resource_label(&self, id: usize) -> String {
+		match &self.owned_resources[id] {
+			GraphOwnedResource::Attachment { name, width, height, format, .. } => format!("{}\\n{}x{} {:?}", name, width, height, format),
+			GraphOwnedResource::Buffer { name, size, location, .. } => format!("{}\\n{} bytes {:?}", name, size, location),
+			_ => self.resource_name(id).to_string(),
+		}
+	}
+
+	// Imported resources that `pass` references through its descriptor sets and draw calls.
+	fn pass_imported_resources(&self, pass: PassHandle) -> Vec<usize> {
+		let mut imported = self
+			.owned_resources
+			.iter()
+			.enumerate()
+			.filter(|(id, _)| self.resource_to_owning_pass[id] == pass)
+			.flat_map(|(_, resource)| match resource {
+				GraphOwnedResource::GraphicsDescriptorSet { bindings, .. } | GraphOwnedResource::ComputeDescriptorSet { bindings, .. } => bindings
+					.iter()
+					.filter_map(|(_, binding)| match binding {
+						GraphOwnedResourceDescriptorBinding::ImportedBuffer(buffer) => Some(buffer.id),
+						GraphOwnedResourceDescriptorBinding::ImportedTexture(texture) => Some(texture.id),
+						_ => None,
+					})
+					.collect::<Vec<_>>(),
+				_ => Vec::new(),
+			})
+			.chain(self.passes[pass.id].cmds.iter().filter_map(|cmd| match cmd {
+				PassCmd::DrawMesh { mesh } => Some(mesh.id),
+				_ => None,
+			}))
+			.collect::<Vec<_>>();
+
+		imported.sort();
+		imported.dedup();
+		imported
+	}
+
+	fn describe_imported_resource(&self, id: usize) -> &'static str {
+		match self.imported_resources[id] {
+			GraphImportedResource::Shader(_) => "Shader",
+			GraphImportedResource::Mesh(_) => "Mesh",
+			GraphImportedResource::Buffer(_) => "Buffer",
+			GraphImportedResource::Texture(_) => "Texture",
+		}
+	}
+
+	// Graphviz DOT document of the recorded graph. Passes are boxes, owned resources ellipses and imported resources notes.
+	// Culled passes are dashed, async compute passes orange and edges into a pass are labelled with the barriers inserted before it.
+	// Semaphore waits between queues are dashed edges. Barriers are planned as if the device had a separate compute queue, as if every resource
+	// was created this frame and without the barriers of reused resources, since those depend on what the cache already allocated.
+	pub fn export_dot(&self) -> String {
+		let writers = self.resource_writers();
+		let pass_order = self.sort_passes(&writers).unwrap_or_default();
+		let (sync_plan, _) = self.plan_barriers(&pass_order, true, &HashSet::new(), &HashMap::new());
+
+		let mut dot = String::from("digraph render_graph {\n\trankdir=LR;\n\tnode [fontname=\"monospace\"];\n\tedge [fontname=\"monospace\", fontsize=10];\n\n");
+
+		for recorded_pass in self.passes.iter() {
+			let pass = recorded_pass.pass;
+			match pass_order.iter().position(|p| *p == pass) {
+				Some(order) => {
+					let color = match recorded_pass.queue {
+						QueueType::GRAPHICS => "lightblue",
+						QueueType::COMPUTE => "lightsalmon",
+					};
+					dot += &format!(
+						"\tpass_{} [label=\"{}\\n#{} {:?}\", shape=box, style=\"filled\", fillcolor=\"{}\"];\n",
+						pass.id, recorded_pass.name, order, recorded_pass.queue, color
+					)
+				}
+				None => dot += &format!("\tpass_{} [label=\"{}\\nculled\", shape=box, style=\"dashed\"];\n", pass.id, recorded_pass.name),
+			}
+		}
+
+		dot += "\n";
+		for (id, resource) in self.owned_resources.iter().enumerate() {
+			if matches!(resource, GraphOwnedResource::Attachment { .. } | GraphOwnedResource::Buffer { .. }) {
+				dot += &format!("\tresource_{} [label=\"{}\", shape=ellipse];\n", id, self.export_resource_label(id));
+			}
+		}
+
+		for id in 0..self.imported_resources.len() {
+			if !matches!(self.imported_resources[id], GraphImportedResource::Shader(_)) {
+				dot += &format!("\timported_{} [label=\"Imported {} #{}\", shape=note];\n", id, self.describe_imported_resource(id), id);
+			}
+		}
+
+		dot += "\n";
+		let mut written = writers.iter().collect::<Vec<_>>();
+		written.sort_by_key(|(id, _)| **id);
+		for (id, passes) in written {
+			for pass in passes {
+				dot += &format!("\tpass_{} -> resource_{} [label=\"write\", color=\"red\"];\n", pass.id, id);
+			}
+		}
+
+		for recorded_pass in self.passes.iter() {
+			let pass = recorded_pass.pass;
+			let sync = pass_order.iter().position(|p| *p == pass).map(|order| &sync_plan[order]);
+			let barriers = sync.map_or(&[][..], |sync| sync.barriers.as_slice());
+
+			let mut reads = recorded_pass
+				.read_attachments
+				.iter()
+				.map(|a| a.id)
+				.chain(recorded_pass.read_buffers.iter().map(|b| b.id))
+				.collect::<Vec<_>>();
+			reads.sort();
+			reads.dedup();
+
+			for id in reads {
+				let barrier_labels = barriers
+					.iter()
+					.filter(|b| b.resource == id)
+					.map(|b| {
+						let transfer = b.queue_transfer.map_or(String::new(), |(src, dst)| format!(" ({:?} -> {:?})", src, dst));
+						match b.kind {
+							GraphBarrierKind::Image { old_layout, new_layout, range } => format!(
+								"\\n{:?} -> {:?} mips {}..{} layers {}..{}{}",
+								old_layout,
+								new_layout,
+								range.base_mip,
+								range.base_mip + range.mip_count,
+								range.base_layer,
+								range.base_layer + range.layer_count,
+								transfer
+							),
+							GraphBarrierKind::Buffer => format!("\\n{:?} -> {:?}{}", b.src_access, b.dst_access, transfer),
+						}
+					})
+					.collect::<String>();
+
+				dot += &format!("\tresource_{} -> pass_{} [label=\"read{}\"];\n", id, pass.id, barrier_labels);
+			}
+
+			for id in self.pass_imported_resources(pass) {
+				dot += &format!("\timported_{} -> pass_{} [style=\"dotted\"];\n", id, pass.id);
+			}
+
+			for (waited_pass, _) in sync.map_or(&[][..], |sync| sync.waits.as_slice()) {
+				dot += &format!("\tpass_{} -> pass_{} [label=\"semaphore\", style=\"dashed\"];\n", pass_order[*waited_pass].id, pass.id);
+			}
+		}
+
+		dot += "}\n";
+		dot
+	}
+
+	// JSON document with the same information as `export_dot`, meant for tooling.
+	pub fn export_json(&self) -> serde_json::Value {
+		let writers = self.resource_writers();
+		let sorted = self.sort_passes(&writers);
+		let pass_order = sorted.as_ref().map(|passes| passes.clone()).unwrap_or_default();
+		let (sync_plan, _) = self.plan_barriers(&pass_order, true, &HashSet::new(), &HashMap::new());
+
+		let export_barrier = |barrier: &GraphBarrier| {
+			let (kind, old_layout, new_layout, range) = match barrier.kind {
+				GraphBarrierKind::Image { old_layout, new_layout, range } => ("image", Some(old_layout), Some(new_layout), Some(range)),
+				GraphBarrierKind::Buffer => ("buffer", None, None, None),
+			};
+
+			serde_json::json!({
+				"resource": barrier.resource,
+				"kind": kind,
+				"old_layout": old_layout,
+				"new_layout": new_layout,
+				"mips": range.map(|range| [range.base_mip, range.base_mip + range.mip_count]),
+				"layers": range.map(|range| [range.base_layer, range.base_layer + range.layer_count]),
+				"src_stage": format!("{:?}", barrier.src_stage),
+				"dst_stage": format!("{:?}", barrier.dst_stage),
+				"src_access": format!("{:?}", barrier.src_access),
+				"dst_access": format!("{:?}", barrier.dst_access),
+				"queue_transfer": barrier.queue_transfer.map(|(src, dst)| [format!("{:?}", src), format!("{:?}", dst)]),
+			})
+		};
+
+		let passes = self
+			.passes
+			.iter()
+			.map(|recorded_pass| {
+				let pass = recorded_pass.pass;
+
+				let mut reads = recorded_pass
+					.read_attachments
+					.iter()
+					.map(|a| a.id)
+					.chain(recorded_pass.read_buffers.iter().map(|b| b.id))
+					.collect::<Vec<_>>();
+				reads.sort();
+				reads.dedup();
+
+				let mut writes = writers.iter().filter(|(_, passes)| passes.contains(&pass)).map(|(id, _)| *id).collect::<Vec<_>>();
+				writes.sort();
+
+				let sync = pass_order.iter().position(|p| *p == pass).map(|order| &sync_plan[order]);
+				let barriers = sync.map_or(Vec::new(), |sync| sync.barriers.iter().map(export_barrier).collect());
+				let releases = sync.map_or(Vec::new(), |sync| sync.releases.iter().map(export_barrier).collect());
+				let waits = sync.map_or(Vec::new(), |sync| sync.waits.iter().map(|(waited_pass, _)| pass_order[*waited_pass].id).collect());
+
+				serde_json::json!({
+					"id": pass.id,
+					"name": recorded_pass.name,
+					"order": pass_order.iter().position(|p| *p == pass),
+					"culled": !pass_order.contains(&pass),
+					"dependencies": self.pass_dependencies(pass, &writers).iter().map(|p| p.id).collect::<Vec<_>>(),
+					"reads": reads,
+					"writes": writes,
+					"queue": format!("{:?}", recorded_pass.queue),
+					"imported": self.pass_imported_resources(pass),
+					"barriers": barriers,
+					"releases": releases,
+					"waits": waits,
+				})
+			})
+			.collect::<Vec<_>>();
+
+		let resources = self
+			.owned_resources
+			.iter()
+			.enumerate()
+			.filter_map(|(id, resource)| {
+				let owner = self.resource_to_owning_pass[&id].id;
+				match resource {
+					&GraphOwnedResource::Attachment {
+						name,
+						width,
+						height,
+						format,
+						load_op,
+						store_op,
+						samples,
+						mip_levels,
+						array_layers,
+						..
+					} => Some(serde_json::json!({
+						"id": id,
+						"type": "attachment",
+						"name": name,
+						"owner": owner,
+						"width": width,
+						"height": height,
+						"format": format,
+						"load_op": format!("{:?}", load_op),
+						"store_op": format!("{:?}", store_op),
+						"samples": samples.count(),
+						"mip_levels": mip_levels,
+						"array_layers": array_layers,
+					})),
+					&GraphOwnedResource::Buffer { name, size, location, .. } => Some(serde_json::json!({
+						"id": id,
+						"type": "buffer",
+						"name": name,
+						"owner": owner,
+						"size": size,
+						"location": format!("{:?}", location),
+					})),
+					_ => None,
+				}
+			})
+			.collect::<Vec<_>>();
+
+		let imported_resources = (0..self.imported_resources.len())
+			.map(|id| serde_json::json!({ "id": id, "type": self.describe_imported_resource(id) }))
+			.collect::<Vec<_>>();
+
+		serde_json::json!({
+			"pass_order": pass_order.iter().map(|p| p.id).collect::<Vec<_>>(),
+			"errors": sorted.err().unwrap_or_default().iter().map(|err| err.to_string()).collect::<Vec<_>>(),
+			"passes": passes,
+			"resources": resources,
+			"imported_resources": imported_resources,
+		})
+	}
+
+	// Validates, culls and sorts the recorded passes before recording them into the graphics context.
+	// Nothing is recorded if any errors are found.
+	pub fn execute(mut self, graphics_context: &mut GraphicsContext, graphics_device: &mut GraphicsDevice) -> Result<Vec<RenderGraphWarning>, Vec<RenderGraphError>> {
+		let writers = self.resource_writers();
+		let passes = self.sort_passes(&writers)?;
+
+		let (warnings, mut errors) = self.validate(&passes, &writers);
+		errors.extend(self.validate_attachment_samples(graphics_device));
+		if !errors.is_empty() {
+			return Err(errors);
+		}
+
+		let async_compute = graphics_context.has_async_compute();
+
+		let resource_map = GraphPhysicalResourceMap::new(&mut self, &passes, graphics_device, graphics_context);
+		let initial_states = resource_map.get_initial_states(&self);
+		let (sync_plan, final_states) = self.plan_barriers(&passes, async_compute, &resource_map.pooled, &initial_states);
+		resource_map.store_final_states(&mut self, final_states);
+
+		// The queue batch that every pass gets recorded into, a new batch starts whenever the queue changes.
+		let (mut current_batch, mut current_queue) = graphics_context.current_queue_batch();
+		let mut pass_batches = Vec::with_capacity(passes.len());
+
+		for (order, pass) in passes.into_iter().enumerate() {
+			let sync = &sync_plan[order];
+
+			let queue = self.pass_queue(pass, async_compute);
+			if queue != current_queue {
+				current_batch = graphics_context.begin_queue_batch(queue);
+				current_queue = queue;
+			}
+			pass_batches.push(current_batch);
+
+			// Passes are still walked and translated into commands on this thread. Every pass gets its own command list though, which
+			// the backend's worker threads record into separate secondary command buffers in parallel.
+			graphics_context.begin_cmd_list();
+
+			for &(waited_pass, stage) in sync.waits.iter() {
+				graphics_context.wait_for_queue_batch(pass_batches[waited_pass], stage);
+			}
+
+			self.record_barriers(&resource_map, graphics_context, graphics_device, &sync.barriers);
 
 			for cmd in self.passes[pass.id].cmds.iter() {
 				match cmd {
@@ -1333,7 +2985,11 @@ impl<'a> RenderGraph<'a> {
 					} => graphics_context.dispatch(group_count_x, group_count_y, group_count_z),
 				}
 			}
+
+			self.record_barriers(&resource_map, graphics_context, graphics_device, &sync.releases);
 		}
+
+		Ok(warnings)
 	}
 
 	fn import_resource(&mut self, resource: GraphImportedResource<'a>) -> usize {
@@ -1367,6 +3023,13 @@ pub struct PassBuilder<'a, 'b> {
 
 impl<'a, 'b> PassBuilder<'a, 'b> {
 	pub fn add_attachment(&mut self, desc: AttachmentDesc) -> MutableGraphAttachmentHandle {
+		if desc.format.is_compressed() {
+			self.graph.record_errors.push(RenderGraphError::CompressedAttachmentFormat {
+				attachment: desc.name,
+				format: desc.format,
+			});
+		}
+
 		let id = self.graph.create_resource(
 			self.pass,
 			GraphOwnedResource::Attachment {
@@ -1377,14 +3040,16 @@ impl<'a, 'b> PassBuilder<'a, 'b> {
 				load_op: desc.load_op,
 				store_op: desc.store_op,
 				usage: desc.usage,
+				samples: desc.samples,
+				mip_levels: desc.mip_levels,
+				array_layers: desc.array_layers,
 			},
 		);
 
 		MutableGraphAttachmentHandle {
 			id,
 			layout: ImageLayout::Undefined,
-			stage: ash::vk::PipelineStageFlags::empty(),
-			access: ash::vk::AccessFlags::empty(),
+			subresource: None,
 		}
 	}
 
@@ -1399,23 +3064,33 @@ impl<'a, 'b> PassBuilder<'a, 'b> {
 			},
 		);
 
-		MutableGraphBufferHandle {
-			id,
-			stage: ash::vk::PipelineStageFlags::empty(),
-			access: ash::vk::AccessFlags::empty(),
-		}
+		MutableGraphBufferHandle { id }
 	}
 
 	fn decl_read_attachment(&mut self, attachment: GraphAttachmentHandle) {
+		self.validate_subresource(attachment.id, attachment.subresource);
 		let recorded = self.recorded.as_mut().unwrap();
 		recorded.read_attachments.insert(attachment);
 	}
 
 	fn decl_write_attachment(&mut self, attachment: MutableGraphAttachmentHandle) {
+		self.validate_subresource(attachment.id, attachment.subresource);
 		let recorded = self.recorded.as_mut().unwrap();
 		recorded.write_attachments.insert(attachment);
 	}
 
+	fn validate_subresource(&mut self, id: usize, subresource: Option<TextureSubresource>) {
+		let Some(subresource) = subresource else {
+			return;
+		};
+
+		if let &GraphOwnedResource::Attachment { name, mip_levels, array_layers, .. } = &self.graph.owned_resources[id] {
+			if subresource.mip >= mip_levels || subresource.layer.map_or(false, |layer| layer >= array_layers) {
+				self.graph.record_errors.push(RenderGraphError::InvalidAttachmentSubresource { attachment: name, subresource });
+			}
+		}
+	}
+
 	fn decl_read_buffer(&mut self, buffer: GraphBufferHandle) {
 		let recorded = self.recorded.as_mut().unwrap();
 		recorded.read_buffers.insert(buffer);
@@ -1463,6 +3138,41 @@ impl<'a, 'b> PassBuilder<'a, 'b> {
 		let push_constant_bytes = desc.push_constant_bytes;
 		let vertex_input_info = desc.vertex_input_info;
 		let polygon_mode = desc.polygon_mode;
+		let blend_states = desc.blend_states.to_vec();
+		let stencil = desc.stencil;
+		let samples = desc.samples;
+
+		let render_pass_samples = self.graph.render_pass_samples(render_pass);
+		if samples != render_pass_samples {
+			self.graph.record_errors.push(RenderGraphError::PipelineSampleCountMismatch {
+				pipeline: name,
+				samples,
+				render_pass_samples,
+			});
+		}
+
+		let color_attachments = self.graph.render_pass_color_attachment_count(render_pass);
+		if blend_states.len() > color_attachments {
+			self.graph.record_errors.push(RenderGraphError::BlendStateCountMismatch {
+				pipeline: name,
+				blend_states: blend_states.len(),
+				color_attachments,
+			});
+		}
+
+		if stencil.is_some() {
+			let has_stencil = match &self.graph.owned_resources[render_pass.id] {
+				GraphOwnedResource::RenderPass {
+					depth_attachment: Some(depth_attachment),
+					..
+				} => matches!(&self.graph.owned_resources[depth_attachment.id], GraphOwnedResource::Attachment { format, .. } if format.has_stencil()),
+				_ => false,
+			};
+
+			if !has_stencil {
+				self.graph.record_errors.push(RenderGraphError::StencilWithoutStencilAttachment(name));
+			}
+		}
 
 		let id = self.graph.create_resource(
 			self.pass,
@@ -1478,6 +3188,9 @@ impl<'a, 'b> PassBuilder<'a, 'b> {
 				push_constant_bytes,
 				vertex_input_info,
 				polygon_mode,
+				blend_states,
+				stencil,
+				samples,
 			},
 		);
 
@@ -1491,25 +3204,72 @@ impl<'a, 'b> PassBuilder<'a, 'b> {
 			.into_iter()
 			.map(|a| {
 				a.layout = ImageLayout::ColorAttachmentOptimal;
-				a.access = ash::vk::AccessFlags::COLOR_ATTACHMENT_WRITE;
-				a.stage = ash::vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT;
+				self.decl_write_attachment(**a);
 				**a
 			})
 			.collect::<Vec<_>>();
 
 		let depth_attachment = desc.depth_attachment.map_or(None, |a| {
 			a.layout = ImageLayout::DepthStencilAttachmentOptimal;
-			a.access = ash::vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE;
-			a.stage = ash::vk::PipelineStageFlags::LATE_FRAGMENT_TESTS;
+			self.decl_write_attachment(*a);
 			Some(*a)
 		});
 
+		let resolve_attachments = desc
+			.resolve_attachments
+			.into_iter()
+			.map(|a| {
+				a.layout = ImageLayout::ColorAttachmentOptimal;
+				self.decl_write_attachment(**a);
+				**a
+			})
+			.collect::<Vec<_>>();
+
+		let samples = color_attachments.iter().chain(depth_attachment.iter()).map(|a| self.graph.attachment_samples(a.id)).collect::<Vec<_>>();
+		if samples.windows(2).any(|w| w[0] != w[1]) {
+			self.graph.record_errors.push(RenderGraphError::MismatchedAttachmentSamples(name));
+		}
+
+		if !resolve_attachments.is_empty() {
+			if resolve_attachments.len() != color_attachments.len() {
+				self.graph.record_errors.push(RenderGraphError::ResolveAttachmentCountMismatch {
+					render_pass: name,
+					color_attachments: color_attachments.len(),
+					resolve_attachments: resolve_attachments.len(),
+				});
+			}
+
+			for (attachment, resolve_attachment) in color_attachments.iter().zip(resolve_attachments.iter()) {
+				match (&self.graph.owned_resources[attachment.id], &self.graph.owned_resources[resolve_attachment.id]) {
+					(
+						&GraphOwnedResource::Attachment { name: src, format, samples, .. },
+						&GraphOwnedResource::Attachment {
+							name: dst,
+							format: resolve_format,
+							samples: resolve_samples,
+							..
+						},
+					) => {
+						if !samples.is_multisampled() || resolve_samples.is_multisampled() || format != resolve_format {
+							self.graph.record_errors.push(RenderGraphError::InvalidResolveAttachment {
+								render_pass: name,
+								attachment: src,
+								resolve_attachment: dst,
+							});
+						}
+					}
+					_ => unreachable!(),
+				}
+			}
+		}
+
 		let id = self.graph.create_resource(
 			self.pass,
 			GraphOwnedResource::RenderPass {
 				name,
 				color_attachments,
 				depth_attachment,
+				resolve_attachments,
 			},
 		);
 
@@ -1523,6 +3283,7 @@ impl<'a, 'b> PassBuilder<'a, 'b> {
 	}
 
 	fn add_descriptor_set<'c>(&mut self, desc: DescriptorDesc<'a, 'c>) -> Vec<(u32, GraphOwnedResourceDescriptorBinding)> {
+		let descriptor = desc.name;
 		desc.bindings
 			.into_iter()
 			.map(|(i, binding)| {
@@ -1542,22 +3303,33 @@ impl<'a, 'b> PassBuilder<'a, 'b> {
 							GraphOwnedResourceDescriptorBinding::Buffer(*buffer)
 						}
 						DescriptorBindingDesc::MutableBuffer(buffer) => {
-							buffer.stage = ash::vk::PipelineStageFlags::VERTEX_SHADER | ash::vk::PipelineStageFlags::FRAGMENT_SHADER | ash::vk::PipelineStageFlags::COMPUTE_SHADER;
-							buffer.access = ash::vk::AccessFlags::SHADER_READ | ash::vk::AccessFlags::SHADER_WRITE;
 							self.decl_write_buffer(**buffer);
 							GraphOwnedResourceDescriptorBinding::MutableBuffer(**buffer)
 						}
 						DescriptorBindingDesc::Attachment(attachment) => {
+							if let &GraphOwnedResource::Attachment { name, format, samples, .. } = &self.graph.owned_resources[attachment.id] {
+								if !format.supports_aspect(attachment.aspect) {
+									self.graph.record_errors.push(RenderGraphError::UnsupportedAttachmentAspect {
+										descriptor,
+										attachment: name,
+										aspect: attachment.aspect,
+									});
+								}
+
+								if samples.is_multisampled() {
+									self.graph.record_errors.push(RenderGraphError::MultisampledAttachmentRead { descriptor, attachment: name });
+								}
+							}
+
 							self.decl_read_attachment(*attachment);
 							GraphOwnedResourceDescriptorBinding::Attachment(*attachment)
 						}
 						DescriptorBindingDesc::MutableAttachment(attachment) => {
 							attachment.layout = ImageLayout::General;
-							attachment.stage = ash::vk::PipelineStageFlags::VERTEX_SHADER | ash::vk::PipelineStageFlags::FRAGMENT_SHADER | ash::vk::PipelineStageFlags::COMPUTE_SHADER;
-							attachment.access = ash::vk::AccessFlags::SHADER_READ | ash::vk::AccessFlags::SHADER_WRITE;
 							self.decl_write_attachment(**attachment);
 							GraphOwnedResourceDescriptorBinding::MutableAttachment(**attachment)
 						}
+						DescriptorBindingDesc::Sampler(sampler) => GraphOwnedResourceDescriptorBinding::Sampler(*sampler),
 					},
 				)
 			})
@@ -1568,7 +3340,6 @@ impl<'a, 'b> PassBuilder<'a, 'b> {
 		let name = desc.name;
 		let descriptor_layout = desc.descriptor_layout;
 
-		// TODO(Brandon): Validate bindings with descriptor set info.
 		let bindings = self.add_descriptor_set(desc);
 		let id = self.graph.create_resource(self.pass, GraphOwnedResource::GraphicsDescriptorSet { name, bindings, descriptor_layout });
 
@@ -1579,7 +3350,6 @@ impl<'a, 'b> PassBuilder<'a, 'b> {
 		let name = desc.name;
 		let descriptor_layout = desc.descriptor_layout;
 
-		// TODO(Brandon): Validate bindings with descriptor set info.
 		let bindings = self.add_descriptor_set(desc);
 		let id = self.graph.create_resource(self.pass, GraphOwnedResource::ComputeDescriptorSet { name, bindings, descriptor_layout });
 
@@ -1651,3 +3421,656 @@ impl<'a, 'b> Drop for PassBuilder<'a, 'b> {
 		self.graph.record_pass(self.recorded.take().unwrap());
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// A physical resource map which puts every virtual attachment into the physical attachment with the same index.
+	fn identity_resource_map(graph: &RenderGraph) -> GraphPhysicalResourceMap {
+		let mut attachment_map = VirtualToPhysicalResourceMap::new();
+		for (id, resource) in graph.owned_resources.iter().enumerate() {
+			if let GraphOwnedResource::Attachment { .. } = resource {
+				attachment_map.map_physical(id, id);
+			}
+		}
+
+		GraphPhysicalResourceMap {
+			attachment_map,
+			buffer_map: VirtualToPhysicalResourceMap::new(),
+			descriptor_map: VirtualToPhysicalResourceMap::new(),
+			render_pass_map: VirtualToPhysicalResourceMap::new(),
+			framebuffer_map: VirtualToPhysicalResourceMap::new(),
+			raster_pipeline_map: VirtualToPhysicalResourceMap::new(),
+			compute_pipeline_map: VirtualToPhysicalResourceMap::new(),
+			pooled: HashSet::new(),
+		}
+	}
+
+	const TEXTURE_DESC_INFO: &'static DescriptorSetInfo = &DescriptorSetInfo {
+		bindings: phf::phf_map! {
+			0u32 => DescriptorBindingType::Texture2D,
+		},
+	};
+
+	const RW_BUFFER_DESC_INFO: &'static DescriptorSetInfo = &DescriptorSetInfo {
+		bindings: phf::phf_map! {
+			0u32 => DescriptorBindingType::RWStructuredBuffer,
+		},
+	};
+
+	const COPY_BUFFER_DESC_INFO: &'static DescriptorSetInfo = &DescriptorSetInfo {
+		bindings: phf::phf_map! {
+			0u32 => DescriptorBindingType::StructuredBuffer,
+			1u32 => DescriptorBindingType::RWStructuredBuffer,
+		},
+	};
+
+	const TEXTURE_BUFFER_DESC_INFO: &'static DescriptorSetInfo = &DescriptorSetInfo {
+		bindings: phf::phf_map! {
+			0u32 => DescriptorBindingType::Texture2D,
+			1u32 => DescriptorBindingType::StructuredBuffer,
+		},
+	};
+
+	const CBUFFER_TEXTURE_DESC_INFO: &'static DescriptorSetInfo = &DescriptorSetInfo {
+		bindings: phf::phf_map! {
+			0u32 => DescriptorBindingType::CBuffer,
+			1u32 => DescriptorBindingType::Texture2D,
+		},
+	};
+
+	fn color_desc(name: &'static str) -> AttachmentDesc {
+		AttachmentDesc {
+			name,
+			width: 4,
+			height: 4,
+			format: TextureFormat::RGBA8UNorm,
+			load_op: LoadOp::Clear,
+			store_op: StoreOp::Store,
+			usage: TextureUsage::SAMPLED | TextureUsage::ATTACHMENT,
+			samples: SampleCount::X1,
+			mip_levels: 1,
+			array_layers: 1,
+		}
+	}
+
+	fn buffer_desc(name: &'static str) -> BufferDesc {
+		BufferDesc {
+			name,
+			size: 64,
+			usage: BufferUsage::StorageBuffer,
+			location: MemoryLocation::GpuOnly,
+		}
+	}
+
+	// Sorts and validates the graph like `execute` does, without anything that needs the device.
+	// Returns the names of the scheduled passes in order along with the warnings and errors.
+	fn compile(graph: &RenderGraph) -> Result<(Vec<&'static str>, Vec<RenderGraphWarning>, Vec<RenderGraphError>), Vec<RenderGraphError>> {
+		let writers = graph.resource_writers();
+		let passes = graph.sort_passes(&writers)?;
+		let (warnings, errors) = graph.validate(&passes, &writers);
+
+		Ok((passes.iter().map(|pass| graph.passes[pass.id].name).collect(), warnings, errors))
+	}
+
+	#[test]
+	fn passes_that_dont_reach_the_output_are_culled() {
+		let mut cache = RenderGraphCache::default();
+		let mut graph = RenderGraph::new(&mut cache);
+
+		let scene = {
+			let mut pass = graph.add_pass("scene");
+			let mut color = pass.add_attachment(color_desc("scene color"));
+			pass.add_render_pass(RenderPassDesc {
+				name: "scene",
+				color_attachments: &mut [&mut color],
+				depth_attachment: None,
+				resolve_attachments: &mut [],
+			});
+			color
+		};
+
+		{
+			let mut pass = graph.add_pass("debug");
+			let mut color = pass.add_attachment(color_desc("debug color"));
+			pass.add_render_pass(RenderPassDesc {
+				name: "debug",
+				color_attachments: &mut [&mut color],
+				depth_attachment: None,
+				resolve_attachments: &mut [],
+			});
+
+			// Descriptor sets of culled passes are never bound, so this one doesn't fail the graph.
+			pass.add_graphics_descriptor_set(DescriptorDesc {
+				name: "debug descriptor",
+				descriptor_layout: TEXTURE_DESC_INFO,
+				bindings: &mut [],
+			});
+		}
+
+		{
+			let mut pass = graph.add_pass("output");
+			pass.add_graphics_descriptor_set(DescriptorDesc {
+				name: "output descriptor",
+				descriptor_layout: TEXTURE_DESC_INFO,
+				bindings: &mut [(0, DescriptorBindingDesc::Attachment(scene.read()))],
+			});
+			pass.add_output_render_pass();
+		}
+
+		let (passes, warnings, errors) = compile(&graph).unwrap();
+
+		assert_eq!(passes, vec!["scene", "output"]);
+		assert!(matches!(warnings.as_slice(), [RenderGraphWarning::CulledPass("debug")]));
+		assert!(errors.is_empty());
+	}
+
+	#[test]
+	fn resources_written_but_never_read_are_reported() {
+		let mut cache = RenderGraphCache::default();
+		let mut graph = RenderGraph::new(&mut cache);
+
+		let albedo = {
+			let mut pass = graph.add_pass("gbuffer");
+			let mut albedo = pass.add_attachment(color_desc("albedo"));
+			let mut normal = pass.add_attachment(color_desc("normal"));
+			pass.add_render_pass(RenderPassDesc {
+				name: "gbuffer",
+				color_attachments: &mut [&mut albedo, &mut normal],
+				depth_attachment: None,
+				resolve_attachments: &mut [],
+			});
+
+			let mut counters = pass.add_buffer(buffer_desc("counters"));
+			pass.add_graphics_descriptor_set(DescriptorDesc {
+				name: "gbuffer descriptor",
+				descriptor_layout: RW_BUFFER_DESC_INFO,
+				bindings: &mut [(0, DescriptorBindingDesc::MutableBuffer(&mut counters))],
+			});
+			albedo
+		};
+
+		{
+			let mut pass = graph.add_pass("output");
+			pass.add_graphics_descriptor_set(DescriptorDesc {
+				name: "output descriptor",
+				descriptor_layout: TEXTURE_DESC_INFO,
+				bindings: &mut [(0, DescriptorBindingDesc::Attachment(albedo.read()))],
+			});
+			pass.add_output_render_pass();
+		}
+
+		let (passes, warnings, errors) = compile(&graph).unwrap();
+
+		assert_eq!(passes, vec!["gbuffer", "output"]);
+		assert!(matches!(
+			warnings.as_slice(),
+			[
+				RenderGraphWarning::UnreadAttachment {
+					pass: "gbuffer",
+					attachment: "normal"
+				},
+				RenderGraphWarning::UnreadBuffer { pass: "gbuffer", buffer: "counters" },
+			]
+		));
+		assert!(errors.is_empty());
+	}
+
+	#[test]
+	fn reads_of_resources_nothing_writes_fail() {
+		let mut cache = RenderGraphCache::default();
+		let mut graph = RenderGraph::new(&mut cache);
+
+		{
+			let mut pass = graph.add_pass("output");
+			let history = pass.add_attachment(color_desc("history"));
+			let lights = pass.add_buffer(buffer_desc("lights"));
+			pass.add_graphics_descriptor_set(DescriptorDesc {
+				name: "output descriptor",
+				descriptor_layout: TEXTURE_BUFFER_DESC_INFO,
+				bindings: &mut [(0, DescriptorBindingDesc::Attachment(history.read())), (1, DescriptorBindingDesc::Buffer(lights.read()))],
+			});
+			pass.add_output_render_pass();
+		}
+
+		let (_, _, errors) = compile(&graph).unwrap();
+
+		assert_eq!(errors.len(), 2);
+		for resource in ["history", "lights"] {
+			assert!(errors
+				.iter()
+				.any(|error| matches!(error, RenderGraphError::ReadOfUnwrittenResource { pass: "output", resource: r } if *r == resource)));
+		}
+	}
+
+	#[test]
+	fn descriptor_bindings_are_checked_against_their_layout() {
+		let mut cache = RenderGraphCache::default();
+		let mut graph = RenderGraph::new(&mut cache);
+
+		{
+			let mut pass = graph.add_pass("output");
+			pass.add_graphics_descriptor_set(DescriptorDesc {
+				name: "output descriptor",
+				descriptor_layout: CBUFFER_TEXTURE_DESC_INFO,
+				bindings: &mut [
+					(0, DescriptorBindingDesc::Sampler(SamplerDesc::LINEAR_CLAMP)),
+					(2, DescriptorBindingDesc::Sampler(SamplerDesc::LINEAR_CLAMP)),
+				],
+			});
+			pass.add_output_render_pass();
+		}
+
+		let (_, _, errors) = compile(&graph).unwrap();
+
+		assert!(matches!(
+			errors.as_slice(),
+			[
+				RenderGraphError::DescriptorBindingMismatch {
+					descriptor: "output descriptor",
+					binding: 0,
+					expected: DescriptorBindingType::CBuffer,
+					found: "sampler",
+				},
+				RenderGraphError::UnknownDescriptorBinding {
+					descriptor: "output descriptor",
+					binding: 2
+				},
+				RenderGraphError::MissingDescriptorBinding {
+					descriptor: "output descriptor",
+					binding: 1,
+					expected: DescriptorBindingType::Texture2D,
+				},
+			]
+		));
+	}
+
+	#[test]
+	fn dependency_cycles_fail() {
+		let mut cache = RenderGraphCache::default();
+		let mut graph = RenderGraph::new(&mut cache);
+
+		let first = {
+			let mut pass = graph.add_pass("first");
+			let mut buffer = pass.add_buffer(buffer_desc("first buffer"));
+			pass.add_compute_descriptor_set(DescriptorDesc {
+				name: "first descriptor",
+				descriptor_layout: RW_BUFFER_DESC_INFO,
+				bindings: &mut [(0, DescriptorBindingDesc::MutableBuffer(&mut buffer))],
+			});
+			buffer
+		};
+
+		let second = {
+			let mut pass = graph.add_pass("second");
+			let mut buffer = pass.add_buffer(buffer_desc("second buffer"));
+			pass.add_compute_descriptor_set(DescriptorDesc {
+				name: "second descriptor",
+				descriptor_layout: COPY_BUFFER_DESC_INFO,
+				bindings: &mut [(0, DescriptorBindingDesc::Buffer(first.read())), (1, DescriptorBindingDesc::MutableBuffer(&mut buffer))],
+			});
+			pass.add_output_render_pass();
+			buffer
+		};
+
+		// Passes only get handles to the resources of passes recorded before them, so the cycle has to be made by hand.
+		graph.passes[0].read_buffers.insert(second.read());
+
+		let errors = compile(&graph).unwrap_err();
+
+		assert!(matches!(errors.as_slice(), [RenderGraphError::DependencyCycle(cycle)] if *cycle == vec!["second", "first", "second"]));
+	}
+
+	#[test]
+	fn graphs_need_exactly_one_output() {
+		let mut cache = RenderGraphCache::default();
+
+		{
+			let mut graph = RenderGraph::new(&mut cache);
+			graph.add_pass("empty");
+			assert!(matches!(compile(&graph).unwrap_err().as_slice(), [RenderGraphError::NoOutput]));
+		}
+
+		{
+			let mut graph = RenderGraph::new(&mut cache);
+			graph.add_pass("first").add_output_render_pass();
+			graph.add_pass("second").add_output_render_pass();
+			assert!(matches!(
+				compile(&graph).unwrap_err().as_slice(),
+				[RenderGraphError::MultipleOutputs(passes)] if *passes == vec!["first", "second"]
+			));
+		}
+	}
+
+	// Records a pass rendering into an attachment and an output pass sampling it, resource 0 is the attachment.
+	fn record_two_pass_graph(graph: &mut RenderGraph) {
+		let scene = {
+			let mut pass = graph.add_pass("scene");
+			let mut color = pass.add_attachment(color_desc("scene color"));
+			pass.add_render_pass(RenderPassDesc {
+				name: "scene",
+				color_attachments: &mut [&mut color],
+				depth_attachment: None,
+				resolve_attachments: &mut [],
+			});
+			color
+		};
+
+		let mut pass = graph.add_pass("output");
+		pass.add_graphics_descriptor_set(DescriptorDesc {
+			name: "output descriptor",
+			descriptor_layout: TEXTURE_DESC_INFO,
+			bindings: &mut [(0, DescriptorBindingDesc::Attachment(scene.read()))],
+		});
+		pass.add_output_render_pass();
+	}
+
+	fn object_keys(value: &serde_json::Value) -> Vec<&str> {
+		let mut keys = value.as_object().unwrap().keys().map(|key| key.as_str()).collect::<Vec<_>>();
+		keys.sort();
+		keys
+	}
+
+	#[test]
+	fn dot_export_names_passes_resources_and_edges() {
+		let mut cache = RenderGraphCache::default();
+		let mut graph = RenderGraph::new(&mut cache);
+		record_two_pass_graph(&mut graph);
+
+		let dot = graph.export_dot();
+
+		assert!(dot.starts_with("digraph render_graph {\n"));
+		assert!(dot.ends_with("}\n"));
+		assert!(dot.contains("\tpass_0 [label=\"scene\\n#0 "));
+		assert!(dot.contains("\tpass_1 [label=\"output\\n#1 "));
+		assert!(dot.contains("\tresource_0 [label=\"scene color\\n4x4 RGBA8UNorm\", shape=ellipse];\n"));
+		assert!(dot.contains("\tpass_0 -> resource_0 [label=\"write\", color=\"red\"];\n"));
+		assert!(dot.contains("\tresource_0 -> pass_1 [label=\"read\\nColorAttachmentOptimal -> ShaderReadOnlyOptimal mips 0..1 layers 0..1\"];\n"));
+		assert!(!dot.contains("culled"));
+	}
+
+	#[test]
+	fn json_export_has_stable_structure() {
+		let mut cache = RenderGraphCache::default();
+		let mut graph = RenderGraph::new(&mut cache);
+		record_two_pass_graph(&mut graph);
+
+		let json = graph.export_json();
+
+		assert_eq!(object_keys(&json), vec!["errors", "imported_resources", "pass_order", "passes", "resources"]);
+		assert_eq!(json["pass_order"], serde_json::json!([0, 1]));
+		assert_eq!(json["errors"], serde_json::json!([]));
+		assert_eq!(json["imported_resources"], serde_json::json!([]));
+
+		let passes = json["passes"].as_array().unwrap();
+		assert_eq!(passes.len(), 2);
+		for pass in passes {
+			assert_eq!(
+				object_keys(pass),
+				vec!["barriers", "culled", "dependencies", "id", "imported", "name", "order", "queue", "reads", "releases", "waits", "writes"]
+			);
+		}
+
+		assert_eq!(passes[0]["name"], "scene");
+		assert_eq!(passes[0]["order"], 0);
+		assert_eq!(passes[0]["culled"], false);
+		assert_eq!(passes[0]["writes"], serde_json::json!([0]));
+		assert_eq!(passes[1]["name"], "output");
+		assert_eq!(passes[1]["dependencies"], serde_json::json!([0]));
+		assert_eq!(passes[1]["reads"], serde_json::json!([0]));
+		assert_eq!(passes[1]["barriers"][0]["resource"], 0);
+		assert_eq!(passes[1]["barriers"][0]["kind"], "image");
+		assert_eq!(passes[1]["barriers"][0]["new_layout"], "ShaderReadOnlyOptimal");
+
+		let resources = json["resources"].as_array().unwrap();
+		assert_eq!(resources.len(), 1);
+		assert_eq!(
+			object_keys(&resources[0]),
+			vec![
+				"array_layers",
+				"format",
+				"height",
+				"id",
+				"load_op",
+				"mip_levels",
+				"name",
+				"owner",
+				"samples",
+				"store_op",
+				"type",
+				"width"
+			]
+		);
+		assert_eq!(resources[0]["type"], "attachment");
+		assert_eq!(resources[0]["name"], "scene color");
+		assert_eq!(resources[0]["owner"], 0);
+	}
+
+	fn image_barrier(mip: u32, layer: u32, new_layout: ImageLayout) -> GraphBarrier {
+		GraphBarrier {
+			resource: 0,
+			kind: GraphBarrierKind::Image {
+				old_layout: ImageLayout::ColorAttachmentOptimal,
+				new_layout,
+				range: subresource_range(mip, 1, layer, 1),
+			},
+			src_stage: ash::vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+			dst_stage: ash::vk::PipelineStageFlags::FRAGMENT_SHADER,
+			src_access: ash::vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+			dst_access: ash::vk::AccessFlags::SHADER_READ,
+			queue_transfer: None,
+		}
+	}
+
+	fn subresource_range(base_mip: u32, mip_count: u32, base_layer: u32, layer_count: u32) -> GraphSubresourceRange {
+		GraphSubresourceRange {
+			base_mip,
+			mip_count,
+			base_layer,
+			layer_count,
+		}
+	}
+
+	fn merged_ranges(barriers: Vec<GraphBarrier>) -> Vec<GraphSubresourceRange> {
+		merge_barriers(barriers)
+			.iter()
+			.map(|barrier| match barrier.kind {
+				GraphBarrierKind::Image { range, .. } => range,
+				GraphBarrierKind::Buffer => panic!("Expected an image barrier!"),
+			})
+			.collect()
+	}
+
+	#[test]
+	fn adjacent_subresources_merge_into_one_barrier() {
+		let read = ImageLayout::ShaderReadOnlyOptimal;
+
+		let layers = vec![image_barrier(0, 0, read), image_barrier(0, 1, read), image_barrier(0, 2, read)];
+		assert_eq!(merged_ranges(layers), vec![subresource_range(0, 1, 0, 3)]);
+
+		let mips_and_layers = vec![image_barrier(1, 0, read), image_barrier(1, 1, read), image_barrier(2, 0, read), image_barrier(2, 1, read)];
+		assert_eq!(merged_ranges(mips_and_layers), vec![subresource_range(1, 2, 0, 2)]);
+	}
+
+	#[test]
+	fn non_adjacent_subresources_stay_separate() {
+		let read = ImageLayout::ShaderReadOnlyOptimal;
+
+		let skipped_layer = vec![image_barrier(0, 0, read), image_barrier(0, 2, read)];
+		assert_eq!(merged_ranges(skipped_layer), vec![subresource_range(0, 1, 0, 1), subresource_range(0, 1, 2, 1)]);
+
+		let skipped_mip = vec![image_barrier(0, 0, read), image_barrier(2, 0, read)];
+		assert_eq!(merged_ranges(skipped_mip), vec![subresource_range(0, 1, 0, 1), subresource_range(2, 1, 0, 1)]);
+
+		// The layers of mip 0 merge, but mip 1 covers fewer layers so the mips can't.
+		let uneven_layers = vec![image_barrier(0, 0, read), image_barrier(0, 1, read), image_barrier(1, 0, read)];
+		assert_eq!(merged_ranges(uneven_layers), vec![subresource_range(0, 1, 0, 2), subresource_range(1, 1, 0, 1)]);
+	}
+
+	#[test]
+	fn different_barriers_on_adjacent_subresources_stay_separate() {
+		let layouts = vec![image_barrier(0, 0, ImageLayout::ShaderReadOnlyOptimal), image_barrier(0, 1, ImageLayout::General)];
+		assert_eq!(merged_ranges(layouts), vec![subresource_range(0, 1, 0, 1), subresource_range(0, 1, 1, 1)]);
+
+		let other_resource = GraphBarrier {
+			resource: 1,
+			..image_barrier(0, 1, ImageLayout::ShaderReadOnlyOptimal)
+		};
+		let resources = vec![image_barrier(0, 0, ImageLayout::ShaderReadOnlyOptimal), other_resource];
+		assert_eq!(merged_ranges(resources), vec![subresource_range(0, 1, 0, 1), subresource_range(0, 1, 1, 1)]);
+	}
+
+	#[test]
+	fn load_attachment_keeps_layout_across_frames() {
+		let mut cache = RenderGraphCache::default();
+
+		for expected_old_layout in [ImageLayout::Undefined, ImageLayout::ColorAttachmentOptimal] {
+			let mut graph = RenderGraph::new(&mut cache);
+			{
+				let mut pass = graph.add_pass("accumulate");
+				let mut history = pass.add_attachment(AttachmentDesc {
+					name: "history",
+					width: 4,
+					height: 4,
+					format: TextureFormat::RGBA8UNorm,
+					load_op: LoadOp::Load,
+					store_op: StoreOp::Store,
+					usage: TextureUsage::ATTACHMENT,
+					samples: SampleCount::X1,
+					mip_levels: 1,
+					array_layers: 1,
+				});
+				pass.add_render_pass(RenderPassDesc {
+					name: "accumulate",
+					color_attachments: &mut [&mut history],
+					depth_attachment: None,
+					resolve_attachments: &mut [],
+				});
+			}
+
+			let resource_map = identity_resource_map(&graph);
+			let initial_states = resource_map.get_initial_states(&graph);
+			let (sync_plan, final_states) = graph.plan_barriers(&[PassHandle { id: 0 }], false, &resource_map.pooled, &initial_states);
+			resource_map.store_final_states(&mut graph, final_states);
+
+			let barrier = sync_plan[0].barriers.iter().find(|barrier| barrier.resource == 0).unwrap();
+			match barrier.kind {
+				GraphBarrierKind::Image { old_layout, new_layout, .. } => {
+					assert_eq!(old_layout, expected_old_layout);
+					assert_eq!(new_layout, ImageLayout::ColorAttachmentOptimal);
+				}
+				GraphBarrierKind::Buffer => panic!("Expected an image barrier!"),
+			}
+		}
+	}
+
+	fn lifetimes_of(intervals: &[(usize, usize)]) -> HashMap<usize, (usize, usize)> {
+		intervals.iter().copied().enumerate().collect()
+	}
+
+	fn gpu_buffer(size: usize) -> BufferCacheKey {
+		BufferCacheKey {
+			size,
+			usage: BufferUsage::StorageBuffer,
+			location: MemoryLocation::GpuOnly,
+		}
+	}
+
+	fn color_attachment(format: TextureFormat, usage: TextureUsage) -> AttachmentCacheKey {
+		AttachmentCacheKey {
+			width: 16,
+			height: 16,
+			format,
+			usage,
+			samples: SampleCount::X1,
+			mip_levels: 1,
+			array_layers: 1,
+		}
+	}
+
+	#[test]
+	fn overlapping_lifetimes_get_separate_slots() {
+		let lifetimes = lifetimes_of(&[(0, 2), (1, 3), (2, 2)]);
+		let resources = [(0, gpu_buffer(64)), (1, gpu_buffer(64)), (2, gpu_buffer(64))];
+
+		let (assignments, slot_keys, handoffs) = assign_pool_slots(&resources, &lifetimes, BufferCacheKey::merge);
+
+		assert_eq!(assignments, vec![(0, 0), (1, 1), (2, 2)]);
+		assert_eq!(slot_keys.len(), 3);
+		assert!(handoffs.is_empty());
+	}
+
+	#[test]
+	fn adjacent_lifetimes_share_a_slot() {
+		// Resource 1 starts in the pass after the last use of resource 0, resource 2 starts in the last pass of resource 1.
+		let lifetimes = lifetimes_of(&[(0, 1), (2, 3), (3, 4)]);
+		let resources = [(0, gpu_buffer(64)), (1, gpu_buffer(64)), (2, gpu_buffer(64))];
+
+		let (assignments, slot_keys, handoffs) = assign_pool_slots(&resources, &lifetimes, BufferCacheKey::merge);
+
+		assert_eq!(assignments, vec![(0, 0), (1, 0), (2, 1)]);
+		assert_eq!(slot_keys.len(), 2);
+		assert_eq!(handoffs, vec![1]);
+	}
+
+	#[test]
+	fn free_slots_are_reused_in_order() {
+		let lifetimes = lifetimes_of(&[(0, 1), (0, 1), (2, 2), (2, 3), (3, 3)]);
+		let resources = [(0, gpu_buffer(64)), (1, gpu_buffer(64)), (2, gpu_buffer(64)), (3, gpu_buffer(64)), (4, gpu_buffer(64))];
+
+		let (assignments, slot_keys, handoffs) = assign_pool_slots(&resources, &lifetimes, BufferCacheKey::merge);
+
+		assert_eq!(assignments, vec![(0, 0), (1, 1), (2, 0), (3, 1), (4, 0)]);
+		assert_eq!(slot_keys.len(), 2);
+		assert_eq!(handoffs, vec![2, 3, 4]);
+	}
+
+	#[test]
+	fn smaller_buffers_reuse_larger_slots_before_growing_one() {
+		let lifetimes = lifetimes_of(&[(0, 0), (0, 0), (1, 1), (2, 2)]);
+		let resources = [(0, gpu_buffer(16)), (1, gpu_buffer(64)), (2, gpu_buffer(32)), (3, gpu_buffer(128))];
+
+		let (assignments, slot_keys, handoffs) = assign_pool_slots(&resources, &lifetimes, BufferCacheKey::merge);
+
+		assert_eq!(assignments, vec![(0, 0), (1, 1), (2, 1), (3, 0)]);
+		assert_eq!(slot_keys, vec![gpu_buffer(128), gpu_buffer(64)]);
+		assert_eq!(handoffs, vec![2, 3]);
+	}
+
+	#[test]
+	fn host_visible_buffers_are_never_pooled() {
+		let host_visible = BufferCacheKey {
+			location: MemoryLocation::CpuToGpu,
+			..gpu_buffer(64)
+		};
+
+		let lifetimes = lifetimes_of(&[(0, 0), (1, 1)]);
+		let (_, slot_keys, handoffs) = assign_pool_slots(&[(0, host_visible), (1, host_visible)], &lifetimes, BufferCacheKey::merge);
+
+		assert_eq!(slot_keys.len(), 2);
+		assert!(handoffs.is_empty());
+	}
+
+	#[test]
+	fn attachments_with_different_usage_share_a_slot() {
+		let lifetimes = lifetimes_of(&[(0, 0), (1, 1), (2, 2)]);
+		let resources = [
+			(0, color_attachment(TextureFormat::RGBA8UNorm, TextureUsage::ATTACHMENT)),
+			(1, color_attachment(TextureFormat::RGBA8UNorm, TextureUsage::STORAGE)),
+			(2, color_attachment(TextureFormat::Depth, TextureUsage::ATTACHMENT)),
+		];
+
+		let (assignments, slot_keys, handoffs) = assign_pool_slots(&resources, &lifetimes, AttachmentCacheKey::merge);
+
+		assert_eq!(assignments, vec![(0, 0), (1, 0), (2, 1)]);
+		assert_eq!(
+			slot_keys,
+			vec![
+				color_attachment(TextureFormat::RGBA8UNorm, TextureUsage::ATTACHMENT | TextureUsage::STORAGE),
+				color_attachment(TextureFormat::Depth, TextureUsage::ATTACHMENT),
+			]
+		);
+		assert_eq!(handoffs, vec![1]);
+	}
+}
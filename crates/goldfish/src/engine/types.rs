@@ -1,4 +1,5 @@
-#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Size {
 	pub width: u32,
 	pub height: u32,
@@ -0,0 +1,17 @@
+#!/bin/sh
+# Renders the game headless and compares its last frame against crates/goldfish/goldens/game.png.
+# Run it from anywhere with a Vulkan device and the game's assets/ directory. With --update the reference is
+# overwritten by the current output instead, review the new image before committing it.
+set -e
+
+cd "$(dirname "$0")/.."
+
+FRAMES=60
+
+if [ "$1" = "--update" ]; then
+	export GOLDFISH_UPDATE_GOLDENS=1
+fi
+
+mkdir -p .build
+cargo build -p game
+cargo run --bin goldfish_editor -- --headless "$FRAMES" --golden crates/goldfish/goldens